@@ -1,4 +1,8 @@
+use crate::document::{CommentFormat, DumpLayout};
+use crate::integer::Base;
+
 /// Specifies the formatting options to use when serializing.
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
 pub enum Format {
     /// Format a string in block/multiline style.
     Block,
@@ -10,6 +14,14 @@ pub enum Format {
     Hex,
     /// Format an integer as octal.
     Octal,
+    /// Format an integer in `base`, zero-padded to at least `width` digits
+    /// and with an underscore separating every `group` digits.  A `width` or
+    /// `group` of zero disables padding or grouping.
+    Int {
+        base: Base,
+        width: usize,
+        group: usize,
+    },
     /// Format an aggregate in compact mode.
     Compact,
     /// Format a bytes object as a hex string.
@@ -18,13 +30,76 @@ pub enum Format {
     Hexdump,
     /// Format a bytes object as xxd (e.g. `xxd <file>`).
     Xxd,
+    /// Format a bytes object as hexdump with the given layout.
+    HexdumpLayout(DumpLayout),
+    /// Format a bytes object as xxd with the given layout (e.g.
+    /// `xxd -g<group> -c<width> -o<offset> <file>`).
+    XxdLayout(DumpLayout),
+    /// Format a bytes object as base64.
+    Base64,
+    /// Format a bytes object as unpadded URL-safe base64.
+    Base64Url,
+    /// Format a bytes object as base32.
+    Base32,
+    /// Format a bytes object as a C array initializer (e.g. `{0xca, 0xfe}`).
+    CArray,
+    /// Format a bytes object as a C include file (e.g. `xxd -i <file>`).
+    XxdInclude,
+    /// Format a bytes object as an Intel HEX image loaded at `base`.
+    IntelHex { base: u32 },
+    /// Format a bytes object as a Motorola S-record image loaded at `base`.
+    Srec { base: u32 },
+    /// Always quote a string.
+    Quoted,
+    /// Render a string unquoted if allowed by the backend.
+    Unquoted,
+}
+
+/// A named range of bytes within a bytes object.  When the object is
+/// formatted as a hexdump, each region is named in a comment at the end of
+/// the line on which it starts.
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub struct Region {
+    /// The offset of the region within the bytes object.
+    pub offset: usize,
+    /// The length of the region in bytes.
+    pub length: usize,
+    /// The name of the region.
+    pub label: String,
+    /// The format in which to show the value of the region, if any.  Integer
+    /// formats show the region as a little-endian integer of up to 16 bytes.
+    pub format: Option<Format>,
+}
+
+impl Region {
+    /// Creates a region of `length` bytes at `offset` named `label`.
+    pub fn new(offset: usize, length: usize, label: impl Into<String>) -> Self {
+        Region {
+            offset,
+            length,
+            label: label.into(),
+            format: None,
+        }
+    }
+
+    /// Shows the value of the region in `format`.
+    pub fn with_format(mut self, format: Format) -> Self {
+        self.format = Some(format);
+        self
+    }
 }
 
 /// Identifies a field or variant member of a struct/enum.
+///
+/// New kinds of members may be added; implementations of [`Annotate`] should
+/// end their matches with a wildcard arm.
+#[non_exhaustive]
 pub enum MemberId<'a> {
     Name(&'a str),
     Index(u32),
     Variant,
+    /// The struct or enum itself, for annotations which apply to the whole object.
+    Container,
 }
 
 /// Trait implemented on structs to inform the serializer about formatting
@@ -32,4 +107,12 @@ pub enum MemberId<'a> {
 pub trait Annotate {
     fn format(&self, variant: Option<&str>, field: &MemberId) -> Option<Format>;
     fn comment(&self, variant: Option<&str>, field: &MemberId) -> Option<String>;
+    /// Returns the style of a member's comment, if not the standard style.
+    fn comment_style(&self, _variant: Option<&str>, _field: &MemberId) -> Option<CommentFormat> {
+        None
+    }
+    /// Returns the named byte regions of a bytes member.
+    fn regions(&self, _variant: Option<&str>, _field: &MemberId) -> Option<Vec<Region>> {
+        None
+    }
 }
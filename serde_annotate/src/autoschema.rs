@@ -0,0 +1,700 @@
+// Inference of schemas from a corpus of documents.
+use std::collections::{BTreeMap, HashSet};
+use std::fmt::Write;
+
+use crate::annotate::Format;
+use crate::document::{Document, StrFormat};
+use crate::integer::{Base, Int, IntValue};
+use crate::schema::{self, entry, object, string, DIALECT, FORMAT_KEYWORD};
+
+/// The nodes observed at one object-path of a corpus of documents.
+///
+/// Each counter records how many nodes of that kind were seen.  Strings
+/// which hold integers are counted as integers, since that is how the
+/// deserializer and the validator would interpret them.
+#[derive(Clone, Debug, Default)]
+pub struct Shape {
+    /// The number of nodes seen at this path.
+    pub total: usize,
+    pub null: usize,
+    pub boolean: usize,
+    pub string: usize,
+    pub integer: usize,
+    pub float: usize,
+    pub object: usize,
+    pub array: usize,
+    /// The smallest integer seen.
+    pub min: Option<IntValue>,
+    /// The largest integer seen.
+    pub max: Option<IntValue>,
+    /// The bases in which integers were written.
+    pub bases: Vec<Base>,
+    /// The distinct strings seen and their number of occurrences, unless
+    /// there were more distinct strings than the enum limit.
+    pub strings: Option<BTreeMap<String, usize>>,
+    /// The shapes of the properties of objects, in the order first seen.
+    pub properties: Vec<(String, Shape)>,
+    /// The shape of the items of arrays.
+    pub items: Option<Box<Shape>>,
+}
+
+impl Shape {
+    /// Returns the shape of the property `name`.
+    pub fn property(&self, name: &str) -> Option<&Shape> {
+        self.properties
+            .iter()
+            .find_map(|(k, v)| (k == name).then_some(v))
+    }
+
+    /// Returns whether the property `name` was present in every object.
+    pub fn is_required(&self, name: &str) -> bool {
+        self.property(name)
+            .map(|p| p.total == self.object)
+            .unwrap_or(false)
+    }
+
+    /// Returns whether all nodes seen at this path are of the same kind.
+    pub fn is_uniform(&self) -> bool {
+        [
+            self.null,
+            self.boolean,
+            self.string,
+            self.integer,
+            self.float,
+            self.object,
+            self.array,
+        ]
+        .contains(&self.total)
+    }
+
+    // Returns the set of strings if the strings seen look like the values
+    // of an enumeration: a small set of at least two repeated values.
+    fn enumeration(&self) -> Option<Vec<&str>> {
+        let strings = self.strings.as_ref()?;
+        if strings.len() < 2 || self.string <= strings.len() {
+            return None;
+        }
+        Some(strings.keys().map(String::as_str).collect())
+    }
+
+    // Returns the base all integers were written in, if they agree.
+    fn base(&self) -> Option<Base> {
+        match self.bases.as_slice() {
+            [b] => Some(*b),
+            _ => None,
+        }
+    }
+
+    fn observe_int(&mut self, i: &Int) {
+        self.integer += 1;
+        let v = i.value();
+        if self.min.as_ref().map(|m| v < m).unwrap_or(true) {
+            self.min = Some(v.clone());
+        }
+        if self.max.as_ref().map(|m| v > m).unwrap_or(true) {
+            self.max = Some(v.clone());
+        }
+        if !self.bases.contains(&i.base()) {
+            self.bases.push(i.base());
+        }
+    }
+
+    fn observe_str(&mut self, s: &str, enum_limit: usize) {
+        if let Ok(i) = Int::from_str_radix(s, 0) {
+            self.observe_int(&i);
+            return;
+        }
+        if self.string == 0 {
+            self.strings = Some(BTreeMap::new());
+        }
+        self.string += 1;
+        if let Some(strings) = &mut self.strings {
+            *strings.entry(s.to_string()).or_default() += 1;
+            if strings.len() > enum_limit {
+                self.strings = None;
+            }
+        }
+    }
+
+    fn observe(&mut self, doc: &Document, enum_limit: usize) {
+        let Ok(doc) = doc.as_value() else {
+            return;
+        };
+        self.total += 1;
+        match doc {
+            Document::Null => self.null += 1,
+            Document::Boolean(_) => self.boolean += 1,
+            Document::Int(i) => self.observe_int(i),
+            Document::Float(_) => self.float += 1,
+            Document::String(s, _) => self.observe_str(s, enum_limit),
+            Document::StaticStr(s, _) => self.observe_str(s, enum_limit),
+            Document::Bytes(b) => {
+                self.array += 1;
+                let items = self.items.get_or_insert_with(Default::default);
+                for byte in b {
+                    items.observe(&Document::Int((*byte).into()), enum_limit);
+                }
+            }
+            Document::Sequence(s) => {
+                self.array += 1;
+                let items = self.items.get_or_insert_with(Default::default);
+                for item in s {
+                    items.observe(item, enum_limit);
+                }
+            }
+            Document::Mapping(m) => {
+                self.object += 1;
+                for (k, v) in m.iter().filter_map(|kv| kv.as_kv().ok()) {
+                    let key = match k.as_str() {
+                        Ok(k) => k.to_string(),
+                        Err(_) => match k {
+                            Document::Int(i) => i.format(None),
+                            _ => continue,
+                        },
+                    };
+                    let i = match self.properties.iter().position(|(p, _)| *p == key) {
+                        Some(i) => i,
+                        None => {
+                            self.properties.push((key, Shape::default()));
+                            self.properties.len() - 1
+                        }
+                    };
+                    self.properties[i].1.observe(v, enum_limit);
+                }
+            }
+            _ => {}
+        }
+    }
+}
+
+/// `AutoSchema` infers a schema from a corpus of documents.
+///
+/// Each document added to the corpus refines the `Shape` observed at each
+/// object-path.  The result can be emitted as a JSON Schema or as the
+/// source code of Rust types which can deserialize the corpus.
+#[derive(Clone, Debug)]
+pub struct AutoSchema {
+    root: Shape,
+    enum_limit: usize,
+}
+
+impl Default for AutoSchema {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+impl AutoSchema {
+    pub fn new() -> Self {
+        AutoSchema {
+            root: Shape::default(),
+            enum_limit: 8,
+        }
+    }
+
+    /// Sets the maximum number of distinct strings at a path for those
+    /// strings to be considered an enumeration.
+    pub fn enum_limit(mut self, n: usize) -> Self {
+        self.enum_limit = n;
+        self
+    }
+
+    /// Adds a document to the corpus.
+    pub fn add(&mut self, doc: &Document) {
+        self.root.observe(doc, self.enum_limit);
+    }
+
+    /// Returns the shape of the document root.
+    pub fn shape(&self) -> &Shape {
+        &self.root
+    }
+
+    /// Returns the inferred JSON Schema.
+    pub fn to_json_schema(&self) -> Document {
+        let mut schema = json_schema(&self.root);
+        if let Document::Mapping(m) = &mut schema {
+            m.insert(0, entry("$schema", Document::from(DIALECT)));
+        }
+        schema
+    }
+
+    /// Returns the source code of Rust types which can deserialize the
+    /// corpus.  The root type is called `name`.
+    pub fn to_rust(&self, name: &str) -> String {
+        let name = camel_case(name);
+        let mut gen = RustGen::default();
+        let ty = gen.type_of(&name, &self.root);
+        if ty != name {
+            // The root isn't an object; make the root name an alias.
+            gen.items
+                .insert(0, format!("pub type {} = {};\n", name, ty));
+        }
+        let mut s = "use serde::Deserialize;\nuse serde_annotate::Annotate;\n".to_string();
+        for item in gen.items {
+            s.push('\n');
+            s.push_str(&item);
+        }
+        s
+    }
+}
+
+fn base_format(base: Base) -> Option<Format> {
+    match base {
+        Base::Bin => Some(Format::Binary),
+        Base::Oct => Some(Format::Octal),
+        Base::Hex => Some(Format::Hex),
+        Base::Dec => None,
+    }
+}
+
+fn json_schema(shape: &Shape) -> Document {
+    let mut types = Vec::new();
+    let mut keywords = Vec::new();
+    if shape.null > 0 {
+        types.push("null");
+    }
+    if shape.boolean > 0 {
+        types.push("boolean");
+    }
+    if shape.integer > 0 && shape.float == 0 {
+        types.push("integer");
+    }
+    if shape.float > 0 {
+        types.push("number");
+    }
+    // The range of the integers doesn't cover the floats.
+    if shape.integer > 0 && shape.float == 0 {
+        if let Some(min) = &shape.min {
+            keywords.push(entry(
+                "minimum",
+                Document::Int(Int::new(min.clone(), Base::Dec)),
+            ));
+        }
+        if let Some(max) = &shape.max {
+            keywords.push(entry(
+                "maximum",
+                Document::Int(Int::new(max.clone(), Base::Dec)),
+            ));
+        }
+        if let Some(f) = shape.base().and_then(base_format) {
+            keywords.push(entry(
+                FORMAT_KEYWORD,
+                Document::from(schema::format_name(&f)),
+            ));
+        }
+    }
+    if shape.string > 0 {
+        types.push("string");
+        // The enumeration would reject values of other types.
+        let others = shape.total - shape.string - shape.null;
+        if let Some(values) = shape.enumeration().filter(|_| others == 0) {
+            let mut values = values.into_iter().map(string).collect::<Vec<_>>();
+            // A nullable enumeration must also list null.
+            if shape.null > 0 {
+                values.push(Document::Null);
+            }
+            keywords.push(entry("enum", Document::Sequence(values)));
+        }
+    }
+    if shape.object > 0 {
+        types.push("object");
+        let properties = shape
+            .properties
+            .iter()
+            .map(|(k, v)| {
+                Document::Fragment(vec![
+                    Document::String(k.clone(), StrFormat::Standard),
+                    json_schema(v),
+                ])
+            })
+            .collect();
+        keywords.push(entry("properties", object(properties)));
+        let required = shape
+            .properties
+            .iter()
+            .filter(|(k, _)| shape.is_required(k))
+            .map(|(k, _)| string(k))
+            .collect::<Vec<_>>();
+        if !required.is_empty() {
+            keywords.push(entry("required", Document::Sequence(required)));
+        }
+    }
+    if shape.array > 0 {
+        types.push("array");
+        if let Some(items) = shape.items.as_ref().filter(|i| i.total > 0) {
+            keywords.push(entry("items", json_schema(items)));
+        }
+    }
+    let ty = match types.as_slice() {
+        [] => return Document::Boolean(true),
+        [t] => Document::from(*t),
+        _ => Document::Sequence(types.into_iter().map(Document::from).collect()),
+    };
+    keywords.insert(0, entry("type", ty));
+    object(keywords)
+}
+
+const KEYWORDS: &[&str] = &[
+    "as", "async", "await", "box", "break", "const", "continue", "crate", "dyn", "else", "enum",
+    "extern", "false", "fn", "for", "if", "impl", "in", "let", "loop", "match", "mod", "move",
+    "mut", "pub", "ref", "return", "self", "static", "struct", "super", "trait", "true", "type",
+    "unsafe", "use", "where", "while", "yield",
+];
+
+// Splits a name into lowercase words at non-alphanumeric characters and
+// lower-to-upper case transitions.
+fn words(name: &str) -> Vec<String> {
+    let mut words = Vec::new();
+    let mut word = String::new();
+    let mut prev_lower = false;
+    for ch in name.chars() {
+        if !ch.is_alphanumeric() {
+            if !word.is_empty() {
+                words.push(std::mem::take(&mut word));
+            }
+            prev_lower = false;
+            continue;
+        }
+        if ch.is_uppercase() && prev_lower {
+            words.push(std::mem::take(&mut word));
+        }
+        prev_lower = ch.is_lowercase() || ch.is_numeric();
+        word.extend(ch.to_lowercase());
+    }
+    if !word.is_empty() {
+        words.push(word);
+    }
+    words
+}
+
+fn camel_case(name: &str) -> String {
+    let mut s = words(name)
+        .iter()
+        .map(|w| {
+            let mut c = w.chars();
+            c.next()
+                .map(|f| f.to_uppercase().chain(c).collect::<String>())
+                .unwrap_or_default()
+        })
+        .collect::<String>();
+    if !s.starts_with(|c: char| c.is_alphabetic()) {
+        s.insert(0, 'V');
+    }
+    s
+}
+
+fn snake_case(name: &str) -> String {
+    let mut s = words(name).join("_");
+    if !s.starts_with(|c: char| c.is_alphabetic() || c == '_') {
+        s.insert(0, '_');
+    }
+    if KEYWORDS.contains(&s.as_str()) {
+        s.push('_');
+    }
+    s
+}
+
+// Guesses the singular form of a plural name.
+fn singular(name: &str) -> String {
+    if let Some(s) = name.strip_suffix("ies") {
+        format!("{}y", s)
+    } else if name.ends_with('s') && !name.ends_with("ss") && name.len() > 1 {
+        name[..name.len() - 1].to_string()
+    } else {
+        format!("{}Item", name)
+    }
+}
+
+fn int_type(shape: &Shape) -> &'static str {
+    let zero = IntValue::from(0u8);
+    let min = shape.min.clone().unwrap_or(zero.clone());
+    let max = shape.max.clone().unwrap_or(zero.clone());
+    if min >= zero {
+        [
+            ("u8", IntValue::from(u8::MAX)),
+            ("u16", IntValue::from(u16::MAX)),
+            ("u32", IntValue::from(u32::MAX)),
+            ("u64", IntValue::from(u64::MAX)),
+        ]
+        .into_iter()
+        .find_map(|(t, limit)| (max <= limit).then_some(t))
+        .unwrap_or("u128")
+    } else {
+        [
+            ("i8", IntValue::from(i8::MIN), IntValue::from(i8::MAX)),
+            ("i16", IntValue::from(i16::MIN), IntValue::from(i16::MAX)),
+            ("i32", IntValue::from(i32::MIN), IntValue::from(i32::MAX)),
+            ("i64", IntValue::from(i64::MIN), IntValue::from(i64::MAX)),
+        ]
+        .into_iter()
+        .find_map(|(t, lo, hi)| (min >= lo && max <= hi).then_some(t))
+        .unwrap_or("i128")
+    }
+}
+
+#[derive(Default)]
+struct RustGen {
+    items: Vec<String>,
+    names: HashSet<String>,
+}
+
+impl RustGen {
+    fn unique_name(&mut self, name: &str) -> String {
+        let mut unique = name.to_string();
+        let mut n = 1;
+        while self.names.contains(&unique) {
+            n += 1;
+            unique = format!("{}{}", name, n);
+        }
+        self.names.insert(unique.clone());
+        unique
+    }
+
+    // Returns the Rust type for values of `shape`, emitting any new types
+    // needed to represent it.  `name` is the preferred name for new types.
+    fn type_of(&mut self, name: &str, shape: &Shape) -> String {
+        let kinds = [
+            shape.boolean,
+            shape.string,
+            shape.integer,
+            shape.float,
+            shape.object,
+            shape.array,
+        ];
+        let ty = match kinds.iter().filter(|n| **n > 0).count() {
+            0 => "()".to_string(),
+            1 if shape.boolean > 0 => "bool".to_string(),
+            1 if shape.integer > 0 => int_type(shape).to_string(),
+            1 if shape.float > 0 => "f64".to_string(),
+            1 if shape.string > 0 => match shape.enumeration() {
+                Some(values) => self.emit_enum(name, &values),
+                None => "String".to_string(),
+            },
+            1 if shape.object > 0 => self.emit_struct(name, shape),
+            1 => match &shape.items {
+                Some(items) if items.total > 0 => {
+                    format!("Vec<{}>", self.type_of(&singular(name), items))
+                }
+                _ => "Vec<()>".to_string(),
+            },
+            2 if shape.integer > 0 && shape.float > 0 => "f64".to_string(),
+            // Mixed kinds can't be expressed as a single type.  Strings are
+            // the most forgiving choice for the user to refine.
+            _ => "String".to_string(),
+        };
+        if shape.null > 0 {
+            format!("Option<{}>", ty)
+        } else {
+            ty
+        }
+    }
+
+    fn emit_struct(&mut self, name: &str, shape: &Shape) -> String {
+        let name = self.unique_name(name);
+        let index = self.items.len();
+        self.items.push(String::new());
+        let mut s = String::new();
+        writeln!(s, "#[derive(Debug, Deserialize, Annotate)]").unwrap();
+        writeln!(s, "pub struct {} {{", name).unwrap();
+        for (key, prop) in shape.properties.iter() {
+            let field = snake_case(key);
+            let mut ty = self.type_of(&camel_case(key), prop);
+            if !shape.is_required(key) && !ty.starts_with("Option<") {
+                ty = format!("Option<{}>", ty);
+            }
+            if prop.integer > 0 && prop.total == prop.integer + prop.null {
+                if let Some(f) = prop.base().and_then(base_format) {
+                    writeln!(s, "    #[annotate(format = {})]", schema::format_name(&f)).unwrap();
+                }
+            }
+            if field != *key {
+                writeln!(s, "    #[serde(rename = {:?})]", key).unwrap();
+            }
+            writeln!(s, "    pub {}: {},", field, ty).unwrap();
+        }
+        writeln!(s, "}}").unwrap();
+        self.items[index] = s;
+        name
+    }
+
+    fn emit_enum(&mut self, name: &str, values: &[&str]) -> String {
+        let name = self.unique_name(name);
+        let mut variants = HashSet::new();
+        let mut s = String::new();
+        writeln!(s, "#[derive(Debug, Deserialize, Annotate)]").unwrap();
+        writeln!(s, "pub enum {} {{", name).unwrap();
+        for value in values {
+            let mut variant = camel_case(value);
+            while !variants.insert(variant.clone()) {
+                variant.push('_');
+            }
+            if variant != *value {
+                writeln!(s, "    #[serde(rename = {:?})]", value).unwrap();
+            }
+            writeln!(s, "    {},", variant).unwrap();
+        }
+        writeln!(s, "}}").unwrap();
+        self.items.push(s);
+        name
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use anyhow::Result;
+
+    const CORPUS: &[&str] = &[
+        r#"{
+            name: "uart0",
+            mode: "fast",
+            regs: [
+                {addr: 0x10, access: "rw"},
+                {addr: 0x14, access: "ro", reset: 7},
+            ],
+        }"#,
+        r#"{
+            name: "uart1",
+            mode: "slow",
+            regs: [
+                {addr: 0x1000, access: "rw", reset: null},
+            ],
+            "irq-line": -3,
+        }"#,
+        r#"{
+            name: "spi",
+            mode: "fast",
+            regs: [],
+            "irq-line": 4,
+        }"#,
+    ];
+
+    fn infer() -> Result<AutoSchema> {
+        let mut auto = AutoSchema::new().enum_limit(2);
+        for text in CORPUS {
+            auto.add(&Document::parse(text)?);
+        }
+        Ok(auto)
+    }
+
+    #[test]
+    fn test_shape() -> Result<()> {
+        let auto = infer()?;
+        let root = auto.shape();
+        assert_eq!(root.total, 3);
+        assert!(root.is_required("regs"));
+        assert!(!root.is_required("irq-line"));
+        let regs = root.property("regs").unwrap().items.as_ref().unwrap();
+        assert_eq!(regs.object, 3);
+        let addr = regs.property("addr").unwrap();
+        assert_eq!(addr.min, Some(IntValue::from(0x10u8)));
+        assert_eq!(addr.max, Some(IntValue::from(0x1000u16)));
+        assert_eq!(addr.bases, vec![Base::Hex]);
+        // Three distinct names exceed the enum limit.
+        assert!(root.property("name").unwrap().strings.is_none());
+        Ok(())
+    }
+
+    #[test]
+    fn test_json_schema() -> Result<()> {
+        let schema = infer()?.to_json_schema();
+        assert_eq!(
+            schema.clone().to_json5().compact(true).to_string(),
+            concat!(
+                r#"{$schema: "https://json-schema.org/draft/2020-12/schema", type: "object", properties: {"#,
+                r#"name: {type: "string"}, "#,
+                r#"mode: {type: "string", "enum": ["fast", "slow"]}, "#,
+                r#"regs: {type: "array", items: {type: "object", properties: {"#,
+                r#"addr: {type: "integer", minimum: 16, maximum: 4096, "x-format": "hex"}, "#,
+                r#"access: {type: "string", "enum": ["ro", "rw"]}, "#,
+                r#"reset: {type: ["null", "integer"], minimum: 7, maximum: 7}}, "#,
+                r#"required: ["addr", "access"]}}, "#,
+                r#""irq-line": {type: "integer", minimum: -3, maximum: 4}}, "#,
+                r#"required: ["name", "mode", "regs"]}"#,
+            )
+        );
+        // Every document of the corpus is valid under the inferred schema.
+        for text in CORPUS {
+            assert!(Document::parse(text)?.validate(&schema)?.is_empty());
+        }
+        Ok(())
+    }
+
+    #[test]
+    fn test_mixed_corpus() -> Result<()> {
+        const MIXED: &[&str] = &[
+            r#"{level: 1, flag: "true", kind: "a", size: 16}"#,
+            r#"{level: 2.5, flag: "false", kind: "a", size: "0x20"}"#,
+            r#"{level: -7.5, flag: true, kind: 5, size: "big"}"#,
+            r#"{level: 3, flag: "true", kind: "b", size: "big"}"#,
+        ];
+        let mut auto = AutoSchema::new();
+        for text in MIXED {
+            auto.add(&Document::parse(text)?);
+        }
+        let schema = auto.to_json_schema();
+        assert_eq!(
+            schema.clone().to_json5().compact(true).to_string(),
+            concat!(
+                r#"{$schema: "https://json-schema.org/draft/2020-12/schema", type: "object", properties: {"#,
+                r#"level: {type: "number"}, "#,
+                r#"flag: {type: ["boolean", "string"]}, "#,
+                r#"kind: {type: ["integer", "string"], minimum: 5, maximum: 5}, "#,
+                r#"size: {type: ["integer", "string"], minimum: 16, maximum: 32}}, "#,
+                r#"required: ["level", "flag", "kind", "size"]}"#,
+            )
+        );
+        // Every document of the corpus is valid under the inferred schema.
+        for text in MIXED {
+            let doc = Document::parse(text)?;
+            let violations = doc.validate(&schema)?;
+            assert!(violations.is_empty(), "{}: {:?}", text, violations);
+        }
+        Ok(())
+    }
+
+    #[test]
+    fn test_rust() -> Result<()> {
+        let source = infer()?.to_rust("device");
+        assert_eq!(
+            source,
+            r#"use serde::Deserialize;
+use serde_annotate::Annotate;
+
+#[derive(Debug, Deserialize, Annotate)]
+pub struct Device {
+    pub name: String,
+    pub mode: Mode,
+    pub regs: Vec<Reg>,
+    #[serde(rename = "irq-line")]
+    pub irq_line: Option<i8>,
+}
+
+#[derive(Debug, Deserialize, Annotate)]
+pub enum Mode {
+    #[serde(rename = "fast")]
+    Fast,
+    #[serde(rename = "slow")]
+    Slow,
+}
+
+#[derive(Debug, Deserialize, Annotate)]
+pub struct Reg {
+    #[annotate(format = hex)]
+    pub addr: u16,
+    pub access: Access,
+    pub reset: Option<u8>,
+}
+
+#[derive(Debug, Deserialize, Annotate)]
+pub enum Access {
+    #[serde(rename = "ro")]
+    Ro,
+    #[serde(rename = "rw")]
+    Rw,
+}
+"#
+        );
+        Ok(())
+    }
+}
@@ -2,6 +2,8 @@ use std::fmt::Display;
 
 use anstyle::{AnsiColor, Style};
 
+use crate::hexdump;
+
 /// A `ColorProfile` describes how to apply color information when rendering a document.
 #[derive(Default, Clone, Copy)]
 pub struct ColorProfile {
@@ -25,6 +27,16 @@ pub struct ColorProfile {
     pub integer: Style,
     /// The style to use for float values.
     pub float: Style,
+    /// The style to use for the offset column of hexdumps.
+    pub dump_offset: Style,
+    /// The style to use for zero bytes in hexdumps.
+    pub dump_zero: Style,
+    /// The style to use for printable bytes in hexdumps.
+    pub dump_printable: Style,
+    /// The style to use for non-printable bytes in hexdumps.
+    pub dump_nonprintable: Style,
+    /// The style to use for the ASCII gutter of hexdumps.
+    pub dump_ascii: Style,
 }
 
 impl ColorProfile {
@@ -41,6 +53,33 @@ impl ColorProfile {
             boolean: AnsiColor::Blue.on_default(),
             integer: AnsiColor::Blue.on_default().bold(),
             float: AnsiColor::Magenta.on_default(),
+            dump_offset: AnsiColor::Yellow.on_default(),
+            dump_zero: Style::new().dimmed(),
+            dump_printable: AnsiColor::Green.on_default(),
+            dump_nonprintable: AnsiColor::Magenta.on_default(),
+            dump_ascii: AnsiColor::Green.on_default().bold(),
+        }
+    }
+
+    /// Returns whether hexdumps are styled by this profile.
+    pub(crate) fn styles_dumps(&self) -> bool {
+        [
+            self.dump_offset,
+            self.dump_zero,
+            self.dump_printable,
+            self.dump_nonprintable,
+            self.dump_ascii,
+        ]
+        .iter()
+        .any(|s| *s != Style::new())
+    }
+
+    /// Returns the style of a byte in a hexdump.
+    pub(crate) fn dump_byte(&self, byte: u8) -> Style {
+        match byte {
+            0 => self.dump_zero,
+            _ if hexdump::is_printable(byte) => self.dump_printable,
+            _ => self.dump_nonprintable,
         }
     }
 }
@@ -9,24 +9,45 @@ use crate::doc_iter::DocPath;
 use crate::document::Document;
 use crate::error::Error;
 use crate::hexdump;
+use crate::integer::IntValue;
+use crate::relax::{Relax, Verbatim};
+use crate::span::{Segment, SpanMap};
 
 type Result<T, E = Error> = std::result::Result<T, E>;
 
 /// Deserialize an owned document.
 pub struct Deserialize {
     doc: Document,
+    // The locations of the document's nodes, used to locate errors.
+    spans: Option<SpanMap>,
 }
 
 impl TryFrom<&str> for Deserialize {
     type Error = Error;
     /// Parses a document from a `&str` and returns a `Deserialize`.
     fn try_from(text: &str) -> Result<Self> {
-        let doc = Document::parse(text)?;
-        Ok(Deserialize { doc })
+        Ok(Deserialize {
+            doc: Document::parse(text)?,
+            spans: None,
+        })
+    }
+}
+
+impl From<Document> for Deserialize {
+    /// Wraps an already parsed document in a `Deserialize`.
+    fn from(doc: Document) -> Self {
+        Deserialize { doc, spans: None }
     }
 }
 
 impl Deserialize {
+    /// Uses `spans`, the locations of the document's nodes, to report the
+    /// source location of errors.
+    pub fn with_spans(mut self, spans: SpanMap) -> Self {
+        self.spans = Some(spans);
+        self
+    }
+
     /// Tranforms a document by calling `f` on each value-containing node in the
     /// parsed document.  `f` may examine the node's object-path or contained value
     /// to decide whether or not to modify the node.
@@ -53,36 +74,213 @@ impl Deserialize {
     /// Converts the owned document into type `T`.
     pub fn into<T: DeserializeOwned>(self) -> Result<T> {
         let mut ds = Deserializer::from_document(&self.doc)?;
+        if let Some(spans) = &self.spans {
+            ds = ds.with_spans(spans);
+        }
         T::deserialize(&mut ds)
     }
 }
 
 /// A `Deserializer` deserializes a parsed document.
-pub struct Deserializer<'de> {
-    pub(crate) doc: &'de Document,
+///
+/// The document is borrowed for `'doc` and strings are lent to the
+/// deserialized value for `'de`.  A `Deserializer` created by `from_document`
+/// lends strings from the document itself, while `from_str` lends them from
+/// the source text, so the document need not outlive the deserialized value.
+///
+/// Errors encountered while deserializing a nested node are reported as
+/// `Error::PathError`, which carries the object-path of the node and, if the
+/// `Deserializer` was given the document's `SpanMap`, the node's location in
+/// the source text.
+pub struct Deserializer<'doc, 'de> {
+    pub(crate) doc: &'doc Document,
+    scope: Scope<'doc, 'de>,
+    // Whether `doc` is the key of the mapping entry at the scope's path.
+    key: bool,
+}
+
+// The object-path of the current node and where to find its source.
+#[derive(Clone)]
+struct Scope<'doc, 'de> {
+    path: Vec<DocPath<'doc>>,
+    source: Source<'doc, 'de>,
+    // The node at `path` among the verbatim strings of the source text.
+    node: Option<usize>,
 }
 
-impl<'de> Deserializer<'de> {
+// Where a `Deserializer` finds the locations of nodes and the strings it
+// lends to the deserialized value.
+#[derive(Clone, Copy)]
+struct Source<'doc, 'de> {
+    spans: Option<&'doc SpanMap>,
+    // The source text and the strings of the document which appear in it
+    // verbatim.
+    text: Option<(&'de str, &'doc Verbatim)>,
+    // Lends a string from the document, if the document lives for `'de`.
+    lend: fn(&'doc str) -> Option<&'de str>,
+}
+
+impl<'a> Deserializer<'a, 'a> {
     /// Creates a `Deserializer` from a parsed document.
-    pub fn from_document(doc: &'de Document) -> Result<Self> {
+    pub fn from_document(doc: &'a Document) -> Result<Self> {
+        Ok(Deserializer {
+            doc: doc.as_value()?,
+            scope: Scope {
+                path: Vec::new(),
+                source: Source {
+                    spans: None,
+                    text: None,
+                    lend: Some,
+                },
+                node: None,
+            },
+            key: false,
+        })
+    }
+}
+
+impl<'doc, 'de> Deserializer<'doc, 'de> {
+    // Creates a `Deserializer` for a document parsed from `text`, which
+    // lends the strings in `verbatim` from `text` and copies all others.
+    fn from_text(doc: &'doc Document, verbatim: &'doc Verbatim, text: &'de str) -> Result<Self> {
         Ok(Deserializer {
             doc: doc.as_value()?,
+            scope: Scope {
+                path: Vec::new(),
+                source: Source {
+                    spans: None,
+                    text: Some((text, verbatim)),
+                    lend: |_| None,
+                },
+                node: Some(Verbatim::ROOT),
+            },
+            key: false,
+        })
+    }
+
+    /// Uses `spans` to report the source location of errors.
+    pub fn with_spans(mut self, spans: &'doc SpanMap) -> Self {
+        self.scope.source.spans = Some(spans);
+        self
+    }
+
+    // Returns the current string node as a string which lives for `'de`,
+    // or `None` if the string can't be lent and must be copied.
+    fn lend_str(&self) -> Result<Option<&'de str>> {
+        if let Document::StaticStr(s, _) = self.doc {
+            return Ok(Some(s));
+        }
+        let s = self.doc.as_str()?;
+        if s.is_empty() {
+            return Ok(Some(""));
+        }
+        if let (Some((text, verbatim)), Some(node)) = (self.scope.source.text, self.scope.node) {
+            let range = if self.key {
+                verbatim.key(node)
+            } else {
+                verbatim.value(node)
+            };
+            return Ok(range.and_then(|r| text.get(r.clone())));
+        }
+        Ok((self.scope.source.lend)(s))
+    }
+}
+
+impl<'doc, 'de> Scope<'doc, 'de> {
+    // Calls `f` with a deserializer for `doc`, the child of the current
+    // node named by `segment`, and annotates any error with the location
+    // of `doc`.  `index` is the position of `doc` among the values of the
+    // current node and `key` whether `doc` is the key of a mapping entry
+    // rather than its value.
+    fn nested<T>(
+        &self,
+        doc: &'doc Document,
+        segment: DocPath<'doc>,
+        index: usize,
+        key: bool,
+        f: impl FnOnce(&mut Deserializer<'doc, 'de>) -> Result<T>,
+    ) -> Result<T> {
+        let mut scope = self.clone();
+        scope.path.push(segment);
+        // Verbatim strings are recorded by position, as the entries of a
+        // mapping with duplicate keys share a path.
+        scope.node = match (self.source.text, self.node) {
+            (Some((_, verbatim)), Some(node)) => verbatim.get_child(node, &DocPath::Index(index)),
+            _ => None,
+        };
+        let mut ds = Deserializer { doc, scope, key };
+        let result = doc.as_value().and_then(|doc| {
+            ds.doc = doc;
+            f(&mut ds)
+        });
+        result.map_err(|e| match e {
+            Error::PathError(..) => e,
+            _ => Error::PathError(
+                Box::new(e),
+                ds.scope.path.iter().map(Segment::from).collect(),
+                self.source
+                    .spans
+                    .and_then(|s| s.get(&ds.scope.path))
+                    .copied(),
+            ),
         })
     }
 }
 
+/// Formats an object-path as a string of the form `regs[3].addr`.
+pub(crate) fn path_to_string(path: &[DocPath]) -> String {
+    let mut s = String::new();
+    for p in path {
+        match p {
+            DocPath::Name(n) => {
+                if !s.is_empty() {
+                    s.push('.');
+                }
+                s.push_str(n);
+            }
+            DocPath::Index(i) => s.push_str(&format!("[{}]", i)),
+        }
+    }
+    s
+}
+
 /// Parses and deserializes a `str` into a `T`.  The parser is
 /// maximally permissive.
-pub fn from_str<T>(text: &str) -> Result<T>
+///
+/// `T` may borrow strings, such as `&str` or `Cow<str>` fields, from
+/// `text`.  Strings containing escape sequences can only be borrowed
+/// as a `Cow<str>`.
+pub fn from_str<'de, T>(text: &'de str) -> Result<T>
 where
-    T: DeserializeOwned,
+    T: de::Deserialize<'de>,
 {
-    let doc = Document::parse(text)?;
-    let mut ds = Deserializer::from_document(&doc)?;
-    T::deserialize(&mut ds)
+    let relax = Relax::default();
+    let (doc, verbatim) = relax.from_str_verbatim(text)?;
+    let mut ds = Deserializer::from_text(&doc, &verbatim, text)?;
+    T::deserialize(&mut ds).map_err(|e| match e {
+        // Locating the nodes of the document is only worthwhile when there
+        // is an error to report, so parse again with spans to locate it.
+        Error::PathError(_, _, None) => match relax.from_str_with_spans(text) {
+            Ok((_, spans)) => locate(e, &spans),
+            Err(_) => e,
+        },
+        _ => e,
+    })
+}
+
+// Fills in the source location of a `PathError` from `spans`.
+fn locate(e: Error, spans: &SpanMap) -> Error {
+    match e {
+        Error::PathError(e, segments, None) => {
+            let path = segments.iter().map(Segment::as_path).collect::<Vec<_>>();
+            let span = spans.get(&path).copied();
+            Error::PathError(e, segments, span)
+        }
+        _ => e,
+    }
 }
 
-impl<'de, 'a> de::Deserializer<'de> for &'a mut Deserializer<'de> {
+impl<'doc, 'de> de::Deserializer<'de> for &mut Deserializer<'doc, 'de> {
     type Error = Error;
 
     fn deserialize_any<V>(self, v: V) -> Result<V::Value>
@@ -90,29 +288,52 @@ impl<'de, 'a> de::Deserializer<'de> for &'a mut Deserializer<'de> {
         V: Visitor<'de>,
     {
         match self.doc {
-            Document::String(s, _) => v.visit_str(s.as_str()),
-            Document::StaticStr(s, _) => v.visit_str(s),
+            Document::String(..) | Document::StaticStr(..) => self.deserialize_str(v),
             Document::Boolean(b) => v.visit_bool(*b),
-            Document::Int(i) => v.visit_i64(i.into()),
+            Document::Int(i) => match *i.value() {
+                IntValue::U8(x) => v.visit_u8(x),
+                IntValue::U16(x) => v.visit_u16(x),
+                IntValue::U32(x) => v.visit_u32(x),
+                IntValue::U64(x) => v.visit_u64(x),
+                IntValue::I8(x) => v.visit_i8(x),
+                IntValue::I16(x) => v.visit_i16(x),
+                IntValue::I32(x) => v.visit_i32(x),
+                IntValue::I64(x) => v.visit_i64(x),
+                // Parsed integers are always 128 bits wide, but few visitors
+                // accept 128-bit values, so prefer 64 bits when the value fits.
+                IntValue::U128(x) => match u64::try_from(x) {
+                    Ok(x) => v.visit_u64(x),
+                    Err(_) => v.visit_u128(x),
+                },
+                IntValue::I128(x) => match i64::try_from(x) {
+                    Ok(x) => v.visit_i64(x),
+                    Err(_) => v.visit_i128(x),
+                },
+            },
             Document::Float(f) => v.visit_f64(*f),
             Document::Mapping(map) => {
-                v.visit_map(Sequence::new(map.iter().filter(|f| f.has_value())))
+                v.visit_map(Sequence::new(self, map.iter().filter(|f| f.has_value())))
             }
             Document::Sequence(seq) => {
-                v.visit_seq(Sequence::new(seq.iter().filter(|f| f.has_value())))
+                v.visit_seq(Sequence::new(self, seq.iter().filter(|f| f.has_value())))
             }
             Document::Bytes(b) => v.visit_bytes(b.as_slice()),
             Document::Null => v.visit_unit(),
-            Document::Compact(_) => unimplemented!(),
-            Document::Fragment(_) => unimplemented!(),
-            Document::Comment(_, _) => unimplemented!(),
+            // A node without a value, such as a comment, is treated as a unit.
+            Document::Comment(_, _) => v.visit_unit(),
+            Document::Compact(_) | Document::Fragment(_) if !self.doc.has_value() => v.visit_unit(),
+            Document::Compact(_) | Document::Fragment(_) => {
+                self.doc = self.doc.as_value()?;
+                self.deserialize_any(v)
+            }
         }
     }
-    fn deserialize_ignored_any<V>(self, _v: V) -> Result<V::Value>
+    fn deserialize_ignored_any<V>(self, v: V) -> Result<V::Value>
     where
         V: Visitor<'de>,
     {
-        unimplemented!();
+        // The document is already parsed, so there is nothing to skip over.
+        v.visit_unit()
     }
 
     fn deserialize_bool<V>(self, v: V) -> Result<V::Value>
@@ -204,7 +425,10 @@ impl<'de, 'a> de::Deserializer<'de> for &'a mut Deserializer<'de> {
     where
         V: Visitor<'de>,
     {
-        v.visit_borrowed_str(self.doc.as_str()?)
+        match self.lend_str()? {
+            Some(s) => v.visit_borrowed_str(s),
+            None => v.visit_str(self.doc.as_str()?),
+        }
     }
     fn deserialize_string<V>(self, v: V) -> Result<V::Value>
     where
@@ -268,8 +492,8 @@ impl<'de, 'a> de::Deserializer<'de> for &'a mut Deserializer<'de> {
     where
         V: Visitor<'de>,
     {
-        if let Document::Sequence(seq) = self.doc {
-            v.visit_seq(Sequence::new(seq.iter().filter(|f| f.has_value())))
+        if let Document::Sequence(seq) = self.doc.as_value()? {
+            v.visit_seq(Sequence::new(self, seq.iter().filter(|f| f.has_value())))
         } else {
             Err(Error::StructureError("Sequence", self.doc.variant()))
         }
@@ -291,8 +515,8 @@ impl<'de, 'a> de::Deserializer<'de> for &'a mut Deserializer<'de> {
     where
         V: Visitor<'de>,
     {
-        if let Document::Mapping(map) = self.doc {
-            v.visit_map(Sequence::new(map.iter().filter(|f| f.has_value())))
+        if let Document::Mapping(map) = self.doc.as_value()? {
+            v.visit_map(Sequence::new(self, map.iter().filter(|f| f.has_value())))
         } else {
             Err(Error::StructureError("Mapping", self.doc.variant()))
         }
@@ -321,7 +545,7 @@ impl<'de, 'a> de::Deserializer<'de> for &'a mut Deserializer<'de> {
         match self.doc.as_value()? {
             Document::String(s, _) => v.visit_enum(s.as_str().into_deserializer()),
             Document::StaticStr(s, _) => v.visit_enum(s.into_deserializer()),
-            Document::Mapping(frags) => v.visit_enum(Enum::new(frags)?),
+            Document::Mapping(frags) => v.visit_enum(Enum::new(self, frags)?),
             _ => Err(Error::StructureError(
                 "String or Mapping",
                 self.doc.variant(),
@@ -338,21 +562,30 @@ impl<'de, 'a> de::Deserializer<'de> for &'a mut Deserializer<'de> {
 
 // The `Sequence` struct is used to provide sequence and map access to
 // `Document::Sequence` and `Document::Mapping` nodes.
-struct Sequence<'de, T: Iterator<Item = &'de Document>> {
+struct Sequence<'doc, 'de, T: Iterator<Item = &'doc Document>> {
     iter: T,
-    value: Option<&'de Document>,
+    scope: Scope<'doc, 'de>,
+    index: usize,
+    key: Option<DocPath<'doc>>,
+    value: Option<&'doc Document>,
 }
 
-impl<'de, T: Iterator<Item = &'de Document>> Sequence<'de, T> {
-    fn new<I: IntoIterator<Item = T::Item, IntoIter = T>>(ii: I) -> Self {
+impl<'doc, 'de, T: Iterator<Item = &'doc Document>> Sequence<'doc, 'de, T> {
+    fn new<I: IntoIterator<Item = T::Item, IntoIter = T>>(
+        de: &Deserializer<'doc, 'de>,
+        ii: I,
+    ) -> Self {
         Sequence {
             iter: ii.into_iter(),
+            scope: de.scope.clone(),
+            index: 0,
+            key: None,
             value: None,
         }
     }
 }
 
-impl<'de, T: Iterator<Item = &'de Document>> SeqAccess<'de> for Sequence<'de, T> {
+impl<'doc, 'de, T: Iterator<Item = &'doc Document>> SeqAccess<'de> for Sequence<'doc, 'de, T> {
     type Error = Error;
 
     fn next_element_seed<E>(&mut self, seed: E) -> Result<Option<E::Value>>
@@ -360,15 +593,21 @@ impl<'de, T: Iterator<Item = &'de Document>> SeqAccess<'de> for Sequence<'de, T>
         E: DeserializeSeed<'de>,
     {
         match self.iter.next() {
-            Some(next) => seed
-                .deserialize(&mut Deserializer::from_document(next)?)
-                .map(Some),
+            Some(next) => {
+                let index = self.index;
+                self.index += 1;
+                self.scope
+                    .nested(next, DocPath::Index(index), index, false, |ds| {
+                        seed.deserialize(ds)
+                    })
+                    .map(Some)
+            }
             None => Ok(None),
         }
     }
 }
 
-impl<'de, T: Iterator<Item = &'de Document>> MapAccess<'de> for Sequence<'de, T> {
+impl<'doc, 'de, T: Iterator<Item = &'doc Document>> MapAccess<'de> for Sequence<'doc, 'de, T> {
     type Error = Error;
 
     fn next_key_seed<K>(&mut self, seed: K) -> Result<Option<K::Value>>
@@ -378,8 +617,12 @@ impl<'de, T: Iterator<Item = &'de Document>> MapAccess<'de> for Sequence<'de, T>
         match self.iter.next() {
             Some(doc) => {
                 let (k, v) = doc.as_kv()?;
+                let key = DocPath::Name(k.as_str().unwrap_or("?"));
+                self.key = Some(key.clone());
                 self.value = Some(v);
-                seed.deserialize(&mut Deserializer::from_document(k)?)
+                self.index += 1;
+                self.scope
+                    .nested(k, key, self.index - 1, true, |ds| seed.deserialize(ds))
                     .map(Some)
             }
             None => Ok(None),
@@ -390,37 +633,51 @@ impl<'de, T: Iterator<Item = &'de Document>> MapAccess<'de> for Sequence<'de, T>
     where
         V: DeserializeSeed<'de>,
     {
-        match self.value.take() {
-            Some(v) => seed.deserialize(&mut Deserializer::from_document(v)?),
-            None => Err(Error::Unknown("kvpair missing the value".into())),
+        match (self.key.take(), self.value.take()) {
+            (Some(key), Some(v)) => self
+                .scope
+                .nested(v, key, self.index - 1, false, |ds| seed.deserialize(ds)),
+            _ => Err(Error::Unknown("kvpair missing the value".into())),
         }
     }
 }
 
 // The `Enum` struct is used to provide access to the different enum kinds
 // supported by the serde data model.
-struct Enum<'de> {
-    enm: &'de Document,
-    var: &'de Document,
+struct Enum<'doc, 'de> {
+    enm: &'doc Document,
+    var: &'doc Document,
+    scope: Scope<'doc, 'de>,
 }
 
-impl<'de> Enum<'de> {
-    fn new(ev: &'de [Document]) -> Result<Self> {
+impl<'doc, 'de> Enum<'doc, 'de> {
+    fn new(de: &Deserializer<'doc, 'de>, ev: &'doc [Document]) -> Result<Self> {
         // We expect only one document node will contain a value.
         // Filter out non-value-containing nodes and extract the value.
         let mut values = ev.iter().filter(|&e| Document::has_value(e));
         let ev = values
             .next()
-            .ok_or_else(|| Error::StructureError("one value", "none"))?;
+            .ok_or(Error::StructureError("one value", "none"))?;
         if values.next().is_some() {
             return Err(Error::StructureError("one value", "many"));
         }
         let (e, v) = ev.as_kv()?;
-        Ok(Enum { enm: e, var: v })
+        Ok(Enum {
+            enm: e,
+            var: v,
+            scope: de.scope.clone(),
+        })
+    }
+
+    // Calls `f` with a deserializer for the variant's value.
+    fn nested<T>(self, f: impl FnOnce(&mut Deserializer<'doc, 'de>) -> Result<T>) -> Result<T> {
+        let name = DocPath::Name(self.enm.as_str().unwrap_or("?"));
+        // The variant is the only entry of the enum's mapping.
+        self.scope.nested(self.var, name, 0, false, f)
     }
 }
 
-impl<'de> EnumAccess<'de> for Enum<'de> {
+impl<'doc, 'de> EnumAccess<'de> for Enum<'doc, 'de> {
     type Error = Error;
     type Variant = Self;
 
@@ -428,14 +685,19 @@ impl<'de> EnumAccess<'de> for Enum<'de> {
     where
         V: DeserializeSeed<'de>,
     {
-        Ok((
-            seed.deserialize(&mut Deserializer::from_document(self.enm)?)?,
-            self,
-        ))
+        let mut ds = Deserializer {
+            doc: self.enm.as_value()?,
+            scope: Scope {
+                node: None,
+                ..self.scope.clone()
+            },
+            key: false,
+        };
+        Ok((seed.deserialize(&mut ds)?, self))
     }
 }
 
-impl<'de> VariantAccess<'de> for Enum<'de> {
+impl<'doc, 'de> VariantAccess<'de> for Enum<'doc, 'de> {
     type Error = Error;
 
     fn unit_variant(self) -> Result<()> {
@@ -446,27 +708,29 @@ impl<'de> VariantAccess<'de> for Enum<'de> {
     where
         T: DeserializeSeed<'de>,
     {
-        seed.deserialize(&mut Deserializer::from_document(self.var)?)
+        self.nested(|ds| seed.deserialize(ds))
     }
 
     fn tuple_variant<V>(self, _len: usize, visitor: V) -> Result<V::Value>
     where
         V: Visitor<'de>,
     {
-        de::Deserializer::deserialize_seq(&mut Deserializer::from_document(self.var)?, visitor)
+        self.nested(|ds| de::Deserializer::deserialize_seq(ds, visitor))
     }
 
     fn struct_variant<V>(self, _fields: &'static [&'static str], visitor: V) -> Result<V::Value>
     where
         V: Visitor<'de>,
     {
-        de::Deserializer::deserialize_map(&mut Deserializer::from_document(self.var)?, visitor)
+        self.nested(|ds| de::Deserializer::deserialize_map(ds, visitor))
     }
 }
 
 #[cfg(test)]
 mod tests {
     use super::*;
+    use crate::document::{CommentFormat, StrFormat};
+    use crate::integer::Int;
     use serde::Deserialize;
     #[test]
     fn test_struct() {
@@ -510,4 +774,200 @@ mod tests {
         let expected = E::Struct { a: 1 };
         assert_eq!(expected, from_str(j).unwrap());
     }
+
+    #[test]
+    fn test_borrow() {
+        use std::borrow::Cow;
+        use std::collections::BTreeMap;
+        #[derive(Deserialize, PartialEq, Debug)]
+        struct Test<'a> {
+            name: &'a str,
+            #[serde(borrow)]
+            plain: Cow<'a, str>,
+            #[serde(borrow)]
+            escaped: Cow<'a, str>,
+            #[serde(borrow)]
+            map: BTreeMap<&'a str, &'a str>,
+        }
+
+        let j = r#"{
+            name: "rom",
+            plain: 'flash',
+            escaped: "a\tb",
+            map: {bare: "x", "quoted": "y"},
+        }"#;
+        let t = from_str::<Test>(j).unwrap();
+        assert_eq!(t.name, "rom");
+        assert!(matches!(t.plain, Cow::Borrowed("flash")));
+        assert!(matches!(&t.escaped, Cow::Owned(s) if s == "a\tb"));
+        assert_eq!(
+            t.map.into_iter().collect::<Vec<_>>(),
+            vec![("bare", "x"), ("quoted", "y")]
+        );
+        // Borrowed strings point into the source text.
+        let range = j.as_bytes().as_ptr_range();
+        assert!(range.contains(&t.name.as_ptr()));
+
+        // Quoteless and empty strings are lent as well.
+        let h = "{\n  text: some words  \n  empty: ''\n}";
+        let m = from_str::<BTreeMap<&str, &str>>(h).unwrap();
+        assert_eq!(
+            m.into_iter().collect::<Vec<_>>(),
+            vec![("empty", ""), ("text", "some words")]
+        );
+
+        // Strings of entries with duplicate keys are lent from their own
+        // text, and keys are lent separately from their values.
+        let m = from_str::<BTreeMap<&str, &str>>(r#"{k: "a", k: "b"}"#).unwrap();
+        assert_eq!(m["k"], "b");
+        let m = from_str::<BTreeMap<&str, &str>>(r#"{a: "b", "b": "a"}"#).unwrap();
+        assert_eq!(
+            m.into_iter().collect::<Vec<_>>(),
+            vec![("a", "b"), ("b", "a")]
+        );
+        let v = from_str::<Vec<(&str, &str)>>(r#"[["a", "b"], ["b", "a"]]"#).unwrap();
+        assert_eq!(v, [("a", "b"), ("b", "a")]);
+
+        // A string containing an escape sequence can't be a `&str`.
+        let err = from_str::<Test>(&j.replace("\"rom\"", "\"r\\u006fm\"")).unwrap_err();
+        assert!(err.to_string().contains("borrowed string"), "{}", err);
+
+        // Strings are lent from a document which outlives the value.
+        let doc = Document::parse(j).unwrap();
+        let mut ds = Deserializer::from_document(&doc).unwrap();
+        let t = Test::deserialize(&mut ds).unwrap();
+        assert!(matches!(t.escaped, Cow::Borrowed("a\tb")));
+    }
+
+    #[test]
+    fn test_any() {
+        #[derive(Deserialize, PartialEq, Debug)]
+        #[serde(untagged)]
+        enum Value {
+            Small(i8),
+            Big(u64),
+            Text(String),
+            List(Vec<Value>),
+        }
+        #[derive(Deserialize, PartialEq, Debug)]
+        struct Test {
+            values: Vec<Value>,
+        }
+
+        let j = r#"{
+            // Unknown fields are ignored.
+            ignored: {a: [1, 2]},
+            values: [-5, 0xffffffffffffffff, "x", [1]],
+        }"#;
+        let expected = Test {
+            values: vec![
+                Value::Small(-5),
+                Value::Big(u64::MAX),
+                Value::Text("x".into()),
+                Value::List(vec![Value::Small(1)]),
+            ],
+        };
+        assert_eq!(expected, from_str(j).unwrap());
+
+        let v = from_str::<serde_json::Value>(j).unwrap();
+        assert_eq!(v["ignored"], serde_json::json!({"a": [1, 2]}));
+        assert_eq!(v["values"][0], -5);
+        assert_eq!(v["values"][1], u64::MAX);
+
+        // Integers wider than 64 bits are visited as 128-bit values.
+        struct Wide;
+        impl Visitor<'_> for Wide {
+            type Value = u128;
+            fn expecting(&self, f: &mut std::fmt::Formatter) -> std::fmt::Result {
+                f.write_str("an unsigned integer")
+            }
+            fn visit_u128<E: de::Error>(self, v: u128) -> Result<u128, E> {
+                Ok(v)
+            }
+        }
+        let doc = Document::parse("0x10000000000000000").unwrap();
+        let mut ds = Deserializer::from_document(&doc).unwrap();
+        assert_eq!(
+            de::Deserializer::deserialize_any(&mut ds, Wide).unwrap(),
+            1 << 64
+        );
+
+        // Fragments, compact nodes and comments in a document built in code.
+        let doc = Document::Mapping(vec![
+            Document::Fragment(vec![
+                Document::Comment("A comment".into(), CommentFormat::Standard),
+                Document::String("a".into(), StrFormat::Standard),
+                Document::Compact(Box::new(Document::Sequence(vec![
+                    Document::Int(Int::from(200u8)),
+                    Document::Comment("Another".into(), CommentFormat::Standard),
+                    Document::Int(Int::from(i64::MIN)),
+                ]))),
+            ]),
+            Document::Fragment(vec![Document::Comment(
+                "Only a comment".into(),
+                CommentFormat::Standard,
+            )]),
+        ]);
+        let v = serde_json::Value::deserialize(&mut Deserializer::from_document(&doc).unwrap());
+        assert_eq!(v.unwrap(), serde_json::json!({"a": [200, i64::MIN]}));
+    }
+
+    #[test]
+    fn test_error_location() {
+        #[derive(Deserialize, PartialEq, Debug)]
+        struct Reg {
+            addr: u32,
+        }
+        #[derive(Deserialize, PartialEq, Debug)]
+        struct Regs {
+            regs: Vec<Reg>,
+        }
+
+        let j = r#"{
+            regs: [
+                {addr: 0},
+                // A comment.
+                {addr: "one"},
+            ]
+        }"#;
+        let err = from_str::<Regs>(j).unwrap_err();
+        assert!(matches!(err, Error::PathError(..)));
+        assert!(
+            err.to_string()
+                .ends_with(" at `regs[1].addr` (line 5, col 24)"),
+            "{}",
+            err
+        );
+
+        let (doc, spans) = Document::parse_with_spans(j).unwrap();
+        let err = crate::Deserialize::from(doc)
+            .with_spans(spans)
+            .into::<Regs>()
+            .unwrap_err();
+        assert!(
+            err.to_string()
+                .ends_with(" at `regs[1].addr` (line 5, col 24)"),
+            "{}",
+            err
+        );
+
+        // Without spans there is no location to report, but the error
+        // still carries the path.
+        let err = crate::Deserialize::try_from(j)
+            .unwrap()
+            .into::<Regs>()
+            .unwrap_err();
+        assert!(err.to_string().ends_with(" at `regs[1].addr`"), "{}", err);
+        let Error::PathError(_, path, None) = err else {
+            panic!("{:?}", err);
+        };
+        assert_eq!(
+            path,
+            [
+                Segment::Name("regs".into()),
+                Segment::Index(1),
+                Segment::Name("addr".into())
+            ]
+        );
+    }
 }
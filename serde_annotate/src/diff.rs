@@ -0,0 +1,365 @@
+// Structural differences between documents.
+use std::fmt;
+
+use crate::de::path_to_string;
+use crate::doc_iter::DocPath;
+use crate::document::{CommentFormat, Document};
+
+/// A change to the value at an object-path.
+#[derive(Clone, Debug)]
+pub enum Change<'a> {
+    /// The value only exists in the new document.
+    Added(&'a Document),
+    /// The value only exists in the old document.
+    Removed(&'a Document),
+    /// The value differs between the old and new documents.
+    Changed(&'a Document, &'a Document),
+}
+
+/// A difference between two documents.
+#[derive(Clone, Debug)]
+pub struct Difference<'a> {
+    /// The object-path of the changed node.
+    pub path: Vec<DocPath<'a>>,
+    /// The change to the node.
+    pub change: Change<'a>,
+}
+
+// Renders a value in compact json5 form for use in messages and notes.
+fn show(doc: &Document) -> String {
+    doc.clone().to_json5().compact(true).to_string()
+}
+
+impl fmt::Display for Change<'_> {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            Change::Added(v) => write!(f, "added {}", show(v)),
+            Change::Removed(v) => write!(f, "removed {}", show(v)),
+            Change::Changed(a, b) => write!(f, "changed from {} to {}", show(a), show(b)),
+        }
+    }
+}
+
+impl fmt::Display for Difference<'_> {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        if self.path.is_empty() {
+            write!(f, "{}", self.change)
+        } else {
+            write!(f, "`{}`: {}", path_to_string(&self.path), self.change)
+        }
+    }
+}
+
+fn note(text: String) -> Document {
+    Document::Comment(text, CommentFormat::Standard)
+}
+
+// Attaches a trailing comment to a mapping entry or sequence element.
+fn with_note(mut node: Document, text: String) -> Document {
+    match &mut node {
+        Document::Fragment(f) => {
+            f.push(note(text));
+            node
+        }
+        _ => Document::Fragment(vec![node, note(text)]),
+    }
+}
+
+// Returns the value-containing node of a fragment.  Unlike `as_value`,
+// compact hints are kept.
+fn value_node(doc: &Document) -> Option<&Document> {
+    match doc {
+        Document::Comment(..) => None,
+        Document::Fragment(f) => {
+            let mut values = f.iter().filter(|d| d.has_value());
+            match (values.next(), values.next()) {
+                (Some(v), None) => value_node(v),
+                _ => None,
+            }
+        }
+        _ => Some(doc),
+    }
+}
+
+fn value_node_mut(doc: &mut Document) -> Option<&mut Document> {
+    match doc {
+        Document::Comment(..) => None,
+        Document::Fragment(f) => {
+            let mut values = f.iter_mut().filter(|d| d.has_value());
+            match (values.next(), values.next()) {
+                (Some(v), None) => value_node_mut(v),
+                _ => None,
+            }
+        }
+        _ => Some(doc),
+    }
+}
+
+struct Differ<'a> {
+    path: Vec<DocPath<'a>>,
+    differences: Vec<Difference<'a>>,
+}
+
+impl<'a> Differ<'a> {
+    fn record(&mut self, change: Change<'a>) {
+        self.differences.push(Difference {
+            path: self.path.clone(),
+            change,
+        });
+    }
+
+    // Compares the `old` and `new` value nodes, returning the rendering of
+    // `new` and a note if the node itself changed.
+    fn diff(&mut self, old: &'a Document, new: &'a Document) -> (Document, Option<String>) {
+        let (Some(o), Some(n)) = (value_node(old), value_node(new)) else {
+            return (new.clone(), None);
+        };
+        match (o, n) {
+            // Compact hints only affect the layout: those of `old` are
+            // looked through and those of `new` are kept.
+            (Document::Compact(c), _) => self.diff(c, n),
+            (_, Document::Compact(c)) => {
+                let (doc, note) = self.diff(o, c);
+                (Document::Compact(Box::new(doc)), note)
+            }
+            (Document::Mapping(o), Document::Mapping(n)) => (self.mapping(o, n), None),
+            (Document::Sequence(o), Document::Sequence(n)) => (self.sequence(o, n), None),
+            _ if o.value_eq(n) => (new.clone(), None),
+            _ => {
+                self.record(Change::Changed(o, n));
+                (new.clone(), Some(format!("changed from {}", show(o))))
+            }
+        }
+    }
+
+    fn mapping(&mut self, old: &'a [Document], new: &'a [Document]) -> Document {
+        let find = |entries: &'a [Document], key: &Document| {
+            entries
+                .iter()
+                .filter_map(|e| e.as_kv().ok())
+                .find(|(k, _)| k.value_eq(key))
+        };
+        let mut result = Vec::new();
+        for entry in new {
+            let Ok((k, v)) = entry.as_kv() else {
+                result.push(entry.clone());
+                continue;
+            };
+            self.path
+                .push(DocPath::Name(k.as_str().unwrap_or_default()));
+            match find(old, k) {
+                Some((_, ov)) => {
+                    let (doc, text) = self.diff(ov, v);
+                    let mut entry = entry.clone();
+                    // Unwrap is ok: `entry` is a kvpair.
+                    *entry.as_kv_mut().unwrap().1 = doc;
+                    result.push(match text {
+                        Some(text) => with_note(entry, text),
+                        None => entry,
+                    });
+                }
+                None => {
+                    self.record(Change::Added(v));
+                    result.push(with_note(entry.clone(), "added".into()));
+                }
+            }
+            self.path.pop();
+        }
+        // Removed keys are noted after the nearest preceding key that
+        // still exists.
+        let mut position = 0;
+        for (k, v) in old.iter().filter_map(|e| e.as_kv().ok()) {
+            if let Some(i) = result
+                .iter()
+                .position(|e| e.as_kv().is_ok_and(|(rk, _)| rk.value_eq(k)))
+            {
+                position = i + 1;
+                continue;
+            }
+            self.path
+                .push(DocPath::Name(k.as_str().unwrap_or_default()));
+            self.record(Change::Removed(v));
+            self.path.pop();
+            let key = k.as_str().map(String::from).unwrap_or_else(|_| show(k));
+            let text = format!("removed {}: {}", key, show(v));
+            result.insert(position, Document::Fragment(vec![note(text)]));
+            position += 1;
+        }
+        Document::Mapping(result)
+    }
+
+    fn sequence(&mut self, old: &'a [Document], new: &'a [Document]) -> Document {
+        let mut old_values = old.iter().filter(|v| v.has_value());
+        let mut result = Vec::new();
+        let mut index = 0;
+        for elem in new {
+            if !elem.has_value() {
+                result.push(elem.clone());
+                continue;
+            }
+            self.path.push(DocPath::Index(index));
+            match (old_values.next(), value_node(elem)) {
+                (Some(ov), Some(v)) => {
+                    let (doc, text) = self.diff(ov, v);
+                    let mut elem = elem.clone();
+                    // Unwrap is ok: `elem` has a value.
+                    *value_node_mut(&mut elem).unwrap() = doc;
+                    result.push(match text {
+                        Some(text) => with_note(elem, text),
+                        None => elem,
+                    });
+                }
+                (_, v) => {
+                    self.record(Change::Added(v.unwrap_or(elem).as_value().unwrap_or(elem)));
+                    result.push(with_note(elem.clone(), "added".into()));
+                }
+            }
+            self.path.pop();
+            index += 1;
+        }
+        for ov in old_values {
+            self.path.push(DocPath::Index(index));
+            let ov = ov.as_value().unwrap_or(ov);
+            self.record(Change::Removed(ov));
+            self.path.pop();
+            result.push(note(format!("removed {}", show(ov))));
+            index += 1;
+        }
+        Document::Sequence(result)
+    }
+}
+
+impl Document {
+    /// Compares this document against a `new` version of it.
+    ///
+    /// Mappings are compared by key and sequences by position.  Values are
+    /// compared with `value_eq`, so integers which differ only in their
+    /// base are equal.  The differences are returned in document order.
+    pub fn diff<'a>(&'a self, new: &'a Document) -> Vec<Difference<'a>> {
+        let mut differ = Differ {
+            path: Vec::new(),
+            differences: Vec::new(),
+        };
+        differ.diff(self, new);
+        differ.differences
+    }
+
+    /// Renders the differences between this document and a `new` version
+    /// of it as a copy of `new` annotated with comments describing each
+    /// added, removed and changed node.
+    pub fn diff_document(&self, new: &Document) -> Document {
+        let mut differ = Differ {
+            path: Vec::new(),
+            differences: Vec::new(),
+        };
+        match differ.diff(self, new) {
+            (doc, Some(text)) => Document::Fragment(vec![note(text), doc]),
+            (doc, None) => doc,
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use anyhow::Result;
+
+    const OLD: &str = r#"{
+  // The register map.
+  regs: [
+    {name: "rom", addr: 0x0000},
+    {name: "ram", addr: 0x1000},
+  ],
+  version: 2,
+  debug: true,
+}"#;
+
+    const NEW: &str = r#"{
+  // The register map.
+  regs: [
+    {name: "rom", addr: 0},
+    {name: "ram", addr: 0x2000, size: 0x400},
+    {name: "mmio", addr: 0x4000},
+  ],
+  version: 3,
+}"#;
+
+    #[test]
+    fn test_diff() -> Result<()> {
+        let old = Document::parse(OLD)?;
+        let new = Document::parse(NEW)?;
+        let diff = old
+            .diff(&new)
+            .iter()
+            .map(|d| d.to_string())
+            .collect::<Vec<_>>();
+        assert_eq!(
+            diff,
+            vec![
+                "`regs[1].addr`: changed from 0x1000 to 0x2000",
+                "`regs[1].size`: added 0x400",
+                r#"`regs[2]`: added {name: "mmio", addr: 0x4000}"#,
+                "`version`: changed from 2 to 3",
+                "`debug`: removed true",
+            ]
+        );
+        assert!(old.diff(&old).is_empty());
+        Ok(())
+    }
+
+    #[test]
+    fn test_diff_document() -> Result<()> {
+        let old = Document::parse(OLD)?;
+        let new = Document::parse(NEW)?;
+        let doc = old.diff_document(&new).to_json5().to_string();
+        assert_eq!(
+            doc,
+            r#"{
+  // The register map.
+  regs: [
+    {
+      name: "rom",
+      addr: 0
+    },
+    {
+      name: "ram",
+      addr: 0x2000, // changed from 0x1000
+      size: 0x400 // added
+    },
+    {
+      name: "mmio",
+      addr: 0x4000
+    } // added
+  ],
+  version: 3 // changed from 2
+  // removed debug: true
+}"#
+        );
+        let yaml = old.diff_document(&new).to_yaml().to_string();
+        assert!(yaml.contains("version: 3 # changed from 2\n# removed debug: true"));
+        Ok(())
+    }
+    #[test]
+    fn test_diff_keeps_compact() -> Result<()> {
+        let compact = |text: &str| -> Result<Document> {
+            Ok(Document::Compact(Box::new(Document::parse(text)?)))
+        };
+        let old = Document::parse("{a: [1, 2], b: [[1, 2]]}")?;
+        let new = Document::Mapping(vec![
+            Document::Fragment(vec![Document::from("a"), compact("[1, 3]")?]),
+            Document::Fragment(vec![
+                Document::from("b"),
+                Document::Sequence(vec![compact("[1, 2]")?]),
+            ]),
+        ]);
+        let diff = old
+            .diff(&new)
+            .iter()
+            .map(|d| d.to_string())
+            .collect::<Vec<_>>();
+        assert_eq!(diff, vec!["`a[1]`: changed from 2 to 3"]);
+        let doc = old.diff_document(&new).to_json5().to_string();
+        assert_eq!(doc, "{\n  a: [1, 3],\n  b: [\n    [1, 2]\n  ]\n}");
+        Ok(())
+    }
+}
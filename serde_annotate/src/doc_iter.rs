@@ -53,10 +53,7 @@ impl<'a> Iterator for DocIter<'a> {
     type Item = &'a Document;
     fn next(&mut self) -> Option<Self::Item> {
         let val = loop {
-            let top = match self.stack.last_mut() {
-                Some(top) => top,
-                None => return None,
-            };
+            let top = self.stack.last_mut()?;
             if let Some(val) = top.next() {
                 break val;
             }
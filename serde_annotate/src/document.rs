@@ -1,9 +1,14 @@
 // Document Enum for serialization
 use std::convert::TryFrom;
 
+use crate::annotate::Region;
 use crate::error::Error;
+use crate::hexdump;
 use crate::integer::Int;
 use crate::relax::Relax;
+use crate::span::SpanMap;
+use crate::toml_parser::TomlParser;
+use crate::yaml_parser::YamlParser;
 
 /// Represents possible serialized string formats.
 #[derive(Clone, Copy, Debug, PartialEq, Eq, Hash)]
@@ -29,6 +34,50 @@ pub enum BytesFormat {
     Hexdump,
     /// Hexdump like `xxd ...`.
     Xxd,
+    /// Hexdump like `hexdump -vC ...` with the given layout.
+    HexdumpLayout(DumpLayout),
+    /// Hexdump like `xxd -g<group> -c<width> -o<offset> ...`.
+    XxdLayout(DumpLayout),
+    /// Base64 string (e.g. "mKtFzer/").
+    Base64,
+    /// Unpadded URL-safe base64 string (e.g. "mKtFzer_").
+    Base64Url,
+    /// Base32 string (e.g. "TCVULTPK74======").
+    Base32,
+    /// C array initializer (e.g. "{0x98, 0xab, 0x45}").
+    CArray,
+    /// C include file like `xxd -i ...`.
+    XxdInclude,
+    /// Intel HEX image loaded at the `base` address.
+    IntelHex { base: u32 },
+    /// Motorola S-record image loaded at the `base` address.
+    Srec { base: u32 },
+}
+
+/// The layout of the lines of a hexdump.
+#[derive(Clone, Copy, Debug, PartialEq, Eq, Hash)]
+pub struct DumpLayout {
+    /// The number of bytes in each group of hex digits.
+    pub group: usize,
+    /// The number of bytes on each line.
+    pub width: usize,
+    /// The address of the first byte.
+    pub offset: u64,
+}
+
+impl DumpLayout {
+    /// The layout of `hexdump -vC ...`.
+    pub const HEXDUMP: DumpLayout = DumpLayout {
+        group: 8,
+        width: 16,
+        offset: 0,
+    };
+    /// The layout of `xxd ...`.
+    pub const XXD: DumpLayout = DumpLayout {
+        group: 2,
+        width: 16,
+        offset: 0,
+    };
 }
 
 #[derive(Clone, Copy, Debug, PartialEq, Eq, Hash)]
@@ -85,6 +134,13 @@ impl Document {
         relax.from_str(text)
     }
 
+    /// Parses a string into a `Document` using the maximally permissive parser,
+    /// also returning the location of every value node in the document.
+    pub fn parse_with_spans(text: &str) -> Result<(Document, SpanMap), Error> {
+        let relax = Relax::default();
+        relax.from_str_with_spans(text)
+    }
+
     /// Parses a string into a `Document` using strict json.
     pub fn from_json(text: &str) -> Result<Document, Error> {
         let relax = Relax::json();
@@ -103,6 +159,40 @@ impl Document {
         relax.from_str(text)
     }
 
+    /// Parses a string into a `Document` using toml.
+    pub fn from_toml(text: &str) -> Result<Document, Error> {
+        TomlParser::parse(text)
+    }
+
+    /// Parses a string into a `Document` using yaml.
+    pub fn from_yaml(text: &str) -> Result<Document, Error> {
+        YamlParser::parse(text)
+    }
+
+    /// Creates a `Document` from a byte buffer in `format`.  If `format` is a
+    /// hexdump, the `regions` of the buffer are named in comments at the end
+    /// of the lines on which they start.
+    pub fn from_bytes(
+        data: &[u8],
+        format: BytesFormat,
+        regions: &[Region],
+    ) -> Result<Document, Error> {
+        let Some(string) = hexdump::to_string_with_regions(data, format, regions)? else {
+            return Ok(Document::Bytes(data.to_vec()));
+        };
+        let strformat = match format {
+            BytesFormat::Hexdump
+            | BytesFormat::Xxd
+            | BytesFormat::HexdumpLayout(_)
+            | BytesFormat::XxdLayout(_)
+            | BytesFormat::XxdInclude
+            | BytesFormat::IntelHex { .. }
+            | BytesFormat::Srec { .. } => StrFormat::Multiline,
+            _ => StrFormat::Standard,
+        };
+        Ok(Document::String(string, strformat))
+    }
+
     /// Returns the variant of this `Document`.
     pub fn variant(&self) -> &'static str {
         match self {
@@ -139,6 +229,66 @@ impl Document {
         }
     }
 
+    /// Returns the nodes of a mapping entry.  A value which is itself a
+    /// fragment, such as a commented unit variant, is flattened: its value
+    /// takes the place of the fragment.  Comments directly on a scalar trail
+    /// the entry, while the others, such as comments on a mapping or sequence,
+    /// precede the key.
+    pub(crate) fn entry_nodes(&self) -> Result<Vec<&Document>, Error> {
+        fn flatten<'a>(
+            node: &'a Document,
+            nodes: &mut Vec<&'a Document>,
+            leading: &mut Vec<&'a Document>,
+            trailing: &mut Vec<&'a Document>,
+        ) {
+            match node {
+                Document::Fragment(f) => {
+                    let scalar = node.is_commented_scalar();
+                    for n in f {
+                        match n {
+                            Document::Comment(..) if scalar => trailing.push(n),
+                            Document::Comment(..) => leading.push(n),
+                            _ => flatten(n, nodes, leading, trailing),
+                        }
+                    }
+                }
+                _ => nodes.push(node),
+            }
+        }
+        let mut nodes = Vec::new();
+        let mut leading = Vec::new();
+        let mut trailing = Vec::new();
+        for node in self.fragments()? {
+            flatten(node, &mut nodes, &mut leading, &mut trailing);
+        }
+        let key = nodes
+            .iter()
+            .position(|n| !matches!(n, Document::Comment(..)))
+            .unwrap_or(nodes.len());
+        nodes.splice(key..key, leading);
+        nodes.extend(trailing);
+        Ok(nodes)
+    }
+
+    // Returns whether the value of this node is a mapping or sequence.
+    pub(crate) fn is_aggregate(&self) -> bool {
+        match self {
+            Document::Mapping(_) | Document::Sequence(_) | Document::Compact(_) => true,
+            Document::Fragment(f) => f.iter().any(Document::is_aggregate),
+            _ => false,
+        }
+    }
+
+    // Returns whether this node is a fragment holding comments on a scalar.
+    pub(crate) fn is_commented_scalar(&self) -> bool {
+        match self {
+            Document::Fragment(f) => f
+                .iter()
+                .all(|n| !matches!(n, Document::Fragment(_)) && !n.is_aggregate()),
+            _ => false,
+        }
+    }
+
     /// Returns this node as a kvpair.
     pub fn as_kv(&self) -> Result<(&Document, &Document), Error> {
         let frags = self.fragments()?;
@@ -181,7 +331,7 @@ impl Document {
                 let values = frags.iter().filter(|f| f.has_value()).collect::<Vec<_>>();
                 match values.len() {
                     0 => Err(Error::StructureError("one value", "zero")),
-                    1 => Ok(values[0]),
+                    1 => values[0].as_value(),
                     _ => Err(Error::StructureError("one value", "many")),
                 }
             }
@@ -203,7 +353,8 @@ impl Document {
                     .collect::<Vec<_>>();
                 match values.len() {
                     0 => Err(Error::StructureError("one value", "zero")),
-                    1 => Ok(values.pop().unwrap()),
+                    // Unwrap is ok: there is exactly one value.
+                    1 => values.pop().unwrap().as_value_mut(),
                     _ => Err(Error::StructureError("one value", "many")),
                 }
             }
@@ -251,6 +402,64 @@ impl Document {
         }
     }
 
+    /// Returns whether this node and `other` hold the same value.
+    ///
+    /// Comments and presentation (such as integer bases, string styles and
+    /// compact hints) are ignored, integers and floats compare numerically,
+    /// mappings compare without regard to key order and `Bytes` nodes are
+    /// equal to sequences of the same integers.
+    pub fn value_eq(&self, other: &Document) -> bool {
+        let (Ok(a), Ok(b)) = (self.as_value(), other.as_value()) else {
+            return false;
+        };
+        match (a, b) {
+            (Document::Int(a), Document::Int(b)) => a.value() == b.value(),
+            (Document::Int(_) | Document::Float(_), Document::Int(_) | Document::Float(_)) => {
+                f64::try_from(a).ok() == f64::try_from(b).ok()
+            }
+            (
+                Document::String(..) | Document::StaticStr(..),
+                Document::String(..) | Document::StaticStr(..),
+            ) => a.as_str().ok() == b.as_str().ok(),
+            (Document::Boolean(a), Document::Boolean(b)) => a == b,
+            (Document::Null, Document::Null) => true,
+            (Document::Bytes(a), Document::Bytes(b)) => a == b,
+            (Document::Bytes(_), Document::Sequence(_)) => b.value_eq(a),
+            (Document::Sequence(a), Document::Bytes(b)) => {
+                let a = a.iter().filter(|v| v.has_value()).collect::<Vec<_>>();
+                a.len() == b.len()
+                    && a.iter()
+                        .zip(b)
+                        .all(|(a, b)| a.value_eq(&Document::Int((*b).into())))
+            }
+            (Document::Sequence(a), Document::Sequence(b)) => {
+                let a = a.iter().filter(|v| v.has_value()).collect::<Vec<_>>();
+                let b = b.iter().filter(|v| v.has_value()).collect::<Vec<_>>();
+                a.len() == b.len() && a.iter().zip(b).all(|(a, b)| a.value_eq(b))
+            }
+            (Document::Mapping(a), Document::Mapping(b)) => {
+                let a = a.iter().filter_map(|v| v.as_kv().ok()).collect::<Vec<_>>();
+                let mut b = b.iter().filter_map(|v| v.as_kv().ok()).collect::<Vec<_>>();
+                // Each entry of `a` must be matched by a distinct entry of
+                // `b`, so duplicate entries don't match other entries twice.
+                a.len() == b.len()
+                    && a.iter().all(|(ak, av)| {
+                        match b
+                            .iter()
+                            .position(|(bk, bv)| ak.value_eq(bk) && av.value_eq(bv))
+                        {
+                            Some(i) => {
+                                b.swap_remove(i);
+                                true
+                            }
+                            None => false,
+                        }
+                    })
+            }
+            _ => false,
+        }
+    }
+
     /// Converts the document into a null value or returns an error.
     pub fn as_null(&self) -> Result<(), Error> {
         match self.as_value()? {
@@ -1,4 +1,6 @@
+use crate::de::path_to_string;
 use crate::relax::ParseError;
+use crate::span::{Segment, Span};
 use serde::{de, ser};
 use std::char::CharTryFromError;
 use std::fmt::Display;
@@ -18,6 +20,8 @@ pub enum Error {
     EscapeError(char),
     #[error("formatter error: {0:?}")]
     FmtError(#[from] std::fmt::Error),
+    #[error("I/O error: {0}")]
+    IoError(#[from] std::io::Error),
     #[error("Hexdump error: {0}")]
     HexdumpError(String),
     #[error("Type {0:?} is not valid as a mapping key")]
@@ -34,6 +38,26 @@ pub enum Error {
     StructureError(&'static str, &'static str),
     #[error("syntax error: {0} at {1}:{col}\n| {3}\n| {4:>col$}", col = .2)]
     SyntaxError(String, usize, usize, String, &'static str),
+    #[error("schema error: {0}")]
+    SchemaError(String),
+    #[error("query error: {0}")]
+    QueryError(String),
+    #[error("patch error: {0}")]
+    PatchError(String),
+    /// An error encountered while deserializing the node at an object-path,
+    /// with the source location of the node if it is known.
+    #[error("{0} at `{}`{}", path(.1), location(.2))]
+    PathError(#[source] Box<Error>, Vec<Segment>, Option<Span>),
+}
+
+fn path(segments: &[Segment]) -> String {
+    let path = segments.iter().map(Segment::as_path).collect::<Vec<_>>();
+    path_to_string(&path)
+}
+
+fn location(span: &Option<Span>) -> String {
+    span.map(|s| format!(" (line {}, col {})", s.line, s.column))
+        .unwrap_or_default()
 }
 
 impl ser::Error for Error {
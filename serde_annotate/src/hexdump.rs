@@ -1,6 +1,11 @@
-use crate::document::BytesFormat;
+use crate::annotate::{Format, Region};
+use crate::color::ColorProfile;
+use crate::document::{BytesFormat, DumpLayout};
 use crate::error::Error;
-use regex::RegexBuilder;
+use crate::integer::{Base, Int};
+use anstyle::Style;
+use once_cell::sync::OnceCell;
+use regex::{Captures, Regex, RegexBuilder};
 use std::fmt::Write;
 
 const HEX: &[u8; 16] = b"0123456789abcdef";
@@ -15,21 +20,47 @@ fn hexstr(data: &[u8]) -> String {
     s
 }
 
-// Emit bytes as a hexdump in the style of `hexdump -vC`.
-fn hexdump(data: &[u8]) -> String {
-    // Hexdump always emits a full line of output (78 chars plus newline)
-    // regardless of the input length.  Round the input length up to the next
-    // multple of 16 while calculating the output length.
-    let mut s = String::with_capacity((data.len() + 15) * 79 / 16);
-    for (i, chunk) in data.chunks(16).enumerate() {
+// Returns whether a byte is shown as itself in the ASCII column of a
+// hexdump, as `isprint` would.
+pub(crate) fn is_printable(byte: u8) -> bool {
+    (0x20..=0x7e).contains(&byte)
+}
+
+// Returns the printable representation of a byte in a hexdump.
+fn printable(byte: u8) -> char {
+    if is_printable(byte) {
+        byte as char
+    } else {
+        '.'
+    }
+}
+
+// Returns the address of line `i` of a dump with `width` bytes per line
+// starting at address `offset`.
+fn line_address(offset: u64, i: usize, width: usize) -> Result<u64, Error> {
+    (i as u64)
+        .checked_mul(width as u64)
+        .and_then(|n| offset.checked_add(n))
+        .ok_or_else(|| Error::HexdumpError(format!("address overflow at line {}", i + 1)))
+}
+
+// Emit bytes as a hexdump in the style of `hexdump -vC`, with `width` bytes
+// per line in groups of `group` bytes, starting at address `offset`.
+fn hexdump(data: &[u8], group: usize, width: usize, offset: u64) -> Result<String, Error> {
+    let (group, width) = (group.max(1), width.max(1));
+    // Hexdump always emits a full line of output regardless of the input
+    // length.  Round the input length up to the next multiple of `width`
+    // while calculating the output length.
+    let line = 3 * width + width.div_ceil(group) + width + 12;
+    let mut s = String::with_capacity(data.len().div_ceil(width) * line);
+    for (i, chunk) in data.chunks(width).enumerate() {
         if i > 0 {
             s.push('\n');
         }
-        write!(s, "{:08x}", i * 16).unwrap();
-        let mut buf = [b'.'; 16];
-        let mut space = 51;
+        write!(s, "{:08x}", line_address(offset, i, width)?).unwrap();
+        let mut space = 3 * width + width.div_ceil(group) + 1;
         for (j, &byte) in chunk.iter().enumerate() {
-            if j % 8 == 0 {
+            if j % group == 0 {
                 s.push(' ');
                 space -= 1;
             }
@@ -37,63 +68,403 @@ fn hexdump(data: &[u8]) -> String {
             s.push(HEX[(byte >> 4) as usize] as char);
             s.push(HEX[(byte & 0x0F) as usize] as char);
             space -= 3;
-            buf[j] = match byte {
-                0x20..=0x7f => byte,
-                _ => b'.',
-            };
-        }
-        // Utf8Error is impossible here because all of the codepoints
-        // inside `buf` are ASCII.
-        let chars = std::str::from_utf8(&buf[..chunk.len()]).unwrap();
+        }
+        let chars = chunk.iter().copied().map(printable).collect::<String>();
         write!(s, "{0:>1$} |{2}|", " ", space, chars).unwrap();
     }
-    s
+    Ok(s)
 }
 
-// Emit bytes as a hexdump in the style of `xxd -g<grouping>``.
-fn xxd(data: &[u8], grouping: usize) -> String {
+// Emit bytes as a hexdump in the style of `xxd -g<group> -c<width>`,
+// starting at address `offset`.
+fn xxd(data: &[u8], group: usize, width: usize, offset: u64) -> Result<String, Error> {
+    let (group, width) = (group.max(1), width.max(1));
     // Xxd always emits a full line of output regardless of the input length.
-    // In smallest grouping mode (-g1), each line is 75 chars plus a newline.
-    // Round the input length up to the next multple of 16 while calculating
-    // the output length.
-    let mut s = String::with_capacity((data.len() + 15) * 76 / 16);
-    for (i, chunk) in data.chunks(16).enumerate() {
+    // Round the input length up to the next multiple of `width` while
+    // calculating the output length.
+    let line = 2 * width + width.div_ceil(group) + width + 12;
+    let mut s = String::with_capacity(data.len().div_ceil(width) * line);
+    for (i, chunk) in data.chunks(width).enumerate() {
         if i > 0 {
             s.push('\n');
         }
-        write!(s, "{:08x}:", i * 16).unwrap();
-        let mut buf = [b'.'; 16];
-        let mut space = (16 / grouping) * (grouping * 2 + 1) + 1;
+        write!(s, "{:08x}:", line_address(offset, i, width)?).unwrap();
+        let mut space = 2 * width + width.div_ceil(group) + 1;
         for (j, &byte) in chunk.iter().enumerate() {
-            if j % grouping == 0 {
+            if j % group == 0 {
                 s.push(' ');
                 space -= 1;
             }
             s.push(HEX[(byte >> 4) as usize] as char);
             s.push(HEX[(byte & 0x0F) as usize] as char);
             space -= 2;
-            buf[j] = match byte {
-                0x20..=0x7f => byte,
-                _ => b'.',
-            };
-        }
-        // Utf8Error is impossible here because all of the codepoints
-        // inside `buf` are ASCII.
-        let chars = std::str::from_utf8(&buf[..chunk.len()]).unwrap();
+        }
+        let chars = chunk.iter().copied().map(printable).collect::<String>();
         write!(s, "{0:>1$} {2}", " ", space, chars).unwrap();
     }
+    Ok(s)
+}
+
+const BASE64: &[u8; 64] = b"ABCDEFGHIJKLMNOPQRSTUVWXYZabcdefghijklmnopqrstuvwxyz0123456789+/";
+const BASE64URL: &[u8; 64] = b"ABCDEFGHIJKLMNOPQRSTUVWXYZabcdefghijklmnopqrstuvwxyz0123456789-_";
+const BASE32: &[u8; 32] = b"ABCDEFGHIJKLMNOPQRSTUVWXYZ234567";
+
+// Text in the RFC 4648 encodings may be prefixed with the name of the
+// encoding, which older versions emitted to tell it apart from a hex string.
+const BASE64_PREFIX: &str = "base64:";
+const BASE64URL_PREFIX: &str = "base64url:";
+const BASE32_PREFIX: &str = "base32:";
+
+// Emit bytes in one of the RFC 4648 encodings, where each character of
+// `alphabet` encodes `bits` bits.  If `pad` is non-zero, the output is
+// padded with `=` to a multiple of `pad` characters.
+fn encode_base(data: &[u8], alphabet: &[u8], bits: u32, pad: usize) -> String {
+    let mask = (1u32 << bits) - 1;
+    let mut s = String::with_capacity(data.len() * 8 / bits as usize + pad + 1);
+    let mut acc = 0u32;
+    let mut nbits = 0;
+    for &byte in data {
+        acc = (acc << 8 | byte as u32) & 0xFFFF;
+        nbits += 8;
+        while nbits >= bits {
+            nbits -= bits;
+            s.push(alphabet[(acc >> nbits & mask) as usize] as char);
+        }
+    }
+    if nbits > 0 {
+        s.push(alphabet[(acc << (bits - nbits) & mask) as usize] as char);
+    }
+    while pad > 0 && !s.len().is_multiple_of(pad) {
+        s.push('=');
+    }
     s
 }
 
-/// Convers a byte buffer to a hexadecimal string in `format`.
-pub fn to_string(data: &[u8], format: BytesFormat) -> Option<String> {
+// Emit bytes as a C array initializer (e.g. `{0xca, 0xfe}`).
+fn c_array(data: &[u8]) -> String {
+    let mut s = String::with_capacity(6 * data.len() + 2);
+    s.push('{');
+    for (i, byte) in data.iter().enumerate() {
+        if i > 0 {
+            s.push_str(", ");
+        }
+        write!(s, "{:#04x}", byte).unwrap();
+    }
+    s.push('}');
+    s
+}
+
+// Emit bytes as a C include file in the style of `xxd -i`.
+fn xxd_include(data: &[u8]) -> String {
+    let mut s = String::with_capacity(6 * data.len() + 64);
+    s.push_str("unsigned char data[] = {\n");
+    for (i, chunk) in data.chunks(12).enumerate() {
+        if i > 0 {
+            s.push_str(",\n");
+        }
+        s.push(' ');
+        for byte in chunk {
+            write!(s, " {:#04x},", byte).unwrap();
+        }
+        s.pop();
+    }
+    if !data.is_empty() {
+        s.push('\n');
+    }
+    write!(s, "}};\nunsigned int data_len = {};", data.len()).unwrap();
+    s
+}
+
+// The largest image which is assembled from Intel HEX or S-records, so that a
+// few records at distant addresses can't demand gigabytes of memory.
+const MAX_IMAGE: usize = 64 << 20;
+
+// Returns the `(address, data)` records of an image loaded at `base`.
+// Records are aligned to 16 bytes.  Erased (all 0xFF) records are omitted
+// from the interior of the image, so the first and last records still
+// determine the extent of the image.
+fn records(data: &[u8], base: u32) -> Result<Vec<(u32, &[u8])>, Error> {
+    if base as u64 + data.len() as u64 > 1 << 32 {
+        return Err(Error::HexdumpError(format!(
+            "an image of {} bytes at {:#x} exceeds the 32-bit address space",
+            data.len(),
+            base
+        )));
+    }
+    let mut records = Vec::new();
+    let mut offset = 0;
+    while offset < data.len() {
+        // Can't overflow: the image fits in the address space.
+        let addr = base + offset as u32;
+        let len = std::cmp::min(16 - addr as usize % 16, data.len() - offset);
+        let chunk = &data[offset..offset + len];
+        offset += len;
+        let interior = !records.is_empty() && offset < data.len();
+        if !interior || chunk.iter().any(|&b| b != 0xFF) {
+            records.push((addr, chunk));
+        }
+    }
+    Ok(records)
+}
+
+// Emit a record as upper-case hex digits followed by a newline.
+fn push_record(s: &mut String, prefix: &str, record: &[u8]) {
+    s.push_str(prefix);
+    for byte in record {
+        write!(s, "{:02X}", byte).unwrap();
+    }
+    s.push('\n');
+}
+
+// Emit bytes as an Intel HEX image loaded at `base`.
+fn intel_hex(data: &[u8], base: u32) -> Result<String, Error> {
+    let mut s = String::with_capacity(data.len() * 45 / 16 + 32);
+    let push = |s: &mut String, kind: u8, addr: u16, payload: &[u8]| {
+        let mut record = vec![payload.len() as u8];
+        record.extend(addr.to_be_bytes());
+        record.push(kind);
+        record.extend(payload);
+        let sum = record.iter().fold(0u8, |a, b| a.wrapping_add(*b));
+        record.push(sum.wrapping_neg());
+        push_record(s, ":", &record);
+    };
+    let mut upper = 0;
+    for (addr, chunk) in records(data, base)? {
+        if addr >> 16 != upper {
+            // Extended linear address record.
+            upper = addr >> 16;
+            push(&mut s, 4, 0, &(upper as u16).to_be_bytes());
+        }
+        push(&mut s, 0, addr as u16, chunk);
+    }
+    // End of file record.
+    push(&mut s, 1, 0, &[]);
+    s.pop();
+    Ok(s)
+}
+
+// Emit bytes as a Motorola S-record image loaded at `base`, using the
+// smallest address size which can express the whole image.
+fn srec(data: &[u8], base: u32) -> Result<String, Error> {
+    let mut s = String::with_capacity(data.len() * 46 / 16 + 32);
+    let end = base as u64 + data.len() as u64;
+    let (kind, term, width) = match end {
+        0..=0x1_0000 => (1, 9, 2),
+        0x1_0001..=0x100_0000 => (2, 8, 3),
+        _ => (3, 7, 4),
+    };
+    let push = |s: &mut String, kind: u8, addr: u32, payload: &[u8]| {
+        let mut record = vec![(width + payload.len() + 1) as u8];
+        record.extend(&addr.to_be_bytes()[4 - width..]);
+        record.extend(payload);
+        let sum = record.iter().fold(0u8, |a, b| a.wrapping_add(*b));
+        record.push(!sum);
+        push_record(s, &format!("S{}", kind), &record);
+    };
+    // An empty header record.
+    push(&mut s, 0, 0, &[]);
+    for (addr, chunk) in records(data, base)? {
+        push(&mut s, kind, addr, chunk);
+    }
+    push(&mut s, term, 0, &[]);
+    s.pop();
+    Ok(s)
+}
+
+// Returns the layout of the lines of `format`, if it is a hexdump.
+fn layout(format: BytesFormat) -> Option<DumpLayout> {
     match format {
+        BytesFormat::Hexdump => Some(DumpLayout::HEXDUMP),
+        BytesFormat::Xxd => Some(DumpLayout::XXD),
+        BytesFormat::HexdumpLayout(layout) | BytesFormat::XxdLayout(layout) => Some(layout),
+        _ => None,
+    }
+}
+
+/// Convers a byte buffer to a hexadecimal string in `format`.
+pub fn to_string(data: &[u8], format: BytesFormat) -> Result<Option<String>, Error> {
+    Ok(match format {
         BytesFormat::HexStr => Some(hexstr(data)),
-        BytesFormat::Hexdump => Some(hexdump(data)),
-        // By default, `xxd` emits outputs with grouping 2.
-        BytesFormat::Xxd => Some(xxd(data, 2)),
+        BytesFormat::Hexdump | BytesFormat::HexdumpLayout(_) => {
+            // Unwrap is ok: the format is a hexdump.
+            let l = layout(format).unwrap();
+            Some(hexdump(data, l.group, l.width, l.offset)?)
+        }
+        BytesFormat::Xxd | BytesFormat::XxdLayout(_) => {
+            // Unwrap is ok: the format is a hexdump.
+            let l = layout(format).unwrap();
+            Some(xxd(data, l.group, l.width, l.offset)?)
+        }
+        BytesFormat::Base64 => Some(encode_base(data, BASE64, 6, 4)),
+        // The URL-safe encoding is commonly used without padding.
+        BytesFormat::Base64Url => Some(encode_base(data, BASE64URL, 6, 0)),
+        BytesFormat::Base32 => Some(encode_base(data, BASE32, 5, 8)),
+        BytesFormat::CArray => Some(c_array(data)),
+        BytesFormat::XxdInclude => Some(xxd_include(data)),
+        BytesFormat::IntelHex { base } => Some(intel_hex(data, base)?),
+        BytesFormat::Srec { base } => Some(srec(data, base)?),
         _ => None,
+    })
+}
+
+// Formats the bytes of a region in `format`, for the comment which names
+// the region in a hexdump.
+fn region_value(region: &Region, data: &[u8], format: Format) -> Result<String, Error> {
+    let int = |base, width, group| {
+        if data.len() > 16 {
+            return Err(Error::HexdumpError(format!(
+                "region `{}` is too long to show as an integer",
+                region.label
+            )));
+        }
+        let value = data.iter().rev().fold(0u128, |v, &b| v << 8 | b as u128);
+        Ok(Int::new_with_padding(value, base, width)
+            .with_group(group)
+            .to_string())
+    };
+    match format {
+        Format::Binary => int(Base::Bin, 0, 0),
+        Format::Decimal => int(Base::Dec, 0, 0),
+        Format::Hex => int(Base::Hex, 0, 0),
+        Format::Octal => int(Base::Oct, 0, 0),
+        Format::Int { base, width, group } => int(base, width, group),
+        Format::HexStr => Ok(hexstr(data)),
+        Format::Base64 => Ok(encode_base(data, BASE64, 6, 4)),
+        Format::Base64Url => Ok(encode_base(data, BASE64URL, 6, 0)),
+        Format::Base32 => Ok(encode_base(data, BASE32, 5, 8)),
+        Format::CArray => Ok(c_array(data)),
+        _ => Err(Error::HexdumpError(format!(
+            "region `{}` can't be shown as {:?}",
+            region.label, format
+        ))),
+    }
+}
+
+// Appends a comment to each line of a hexdump of `data` with `width` bytes
+// per line, naming the regions which start on the line.
+fn annotate_lines(
+    dump: &str,
+    data: &[u8],
+    width: usize,
+    regions: &[Region],
+) -> Result<String, Error> {
+    let mut comments = vec![Vec::new(); data.len().div_ceil(width)];
+    let mut regions = regions.iter().collect::<Vec<_>>();
+    regions.sort_by_key(|r| r.offset);
+    for r in regions {
+        let bytes = r
+            .offset
+            .checked_add(r.length)
+            .and_then(|end| data.get(r.offset..end))
+            .filter(|_| r.offset < data.len())
+            .ok_or_else(|| {
+                Error::HexdumpError(format!(
+                    "region `{}` is outside of the {} bytes of data",
+                    r.label,
+                    data.len()
+                ))
+            })?;
+        comments[r.offset / width].push(match r.format {
+            Some(format) => format!("{} = {}", r.label, region_value(r, bytes, format)?),
+            None => r.label.clone(),
+        });
+    }
+    // Align the comments, which follow the ASCII gutter of each line.
+    let pad = dump.split('\n').map(str::len).max().unwrap_or(0);
+    let mut s = String::with_capacity(dump.len());
+    for (i, (line, comment)) in dump.split('\n').zip(&comments).enumerate() {
+        if i > 0 {
+            s.push('\n');
+        }
+        if comment.is_empty() {
+            s.push_str(line);
+        } else {
+            write!(s, "{:<pad$}  # {}", line, comment.join(", ")).unwrap();
+        }
+    }
+    Ok(s)
+}
+
+/// Converts a byte buffer to a string in `format`, naming the `regions` of
+/// the buffer in comments if `format` is a hexdump.
+pub fn to_string_with_regions(
+    data: &[u8],
+    format: BytesFormat,
+    regions: &[Region],
+) -> Result<Option<String>, Error> {
+    let Some(dump) = to_string(data, format)? else {
+        return Ok(None);
+    };
+    match layout(format) {
+        Some(layout) if !regions.is_empty() => {
+            annotate_lines(&dump, data, layout.width.max(1), regions).map(Some)
+        }
+        _ => Ok(Some(dump)),
+    }
+}
+
+// Splits the hex bytes of a line of a hexdump into styled segments,
+// returning the number of bytes.
+fn colorize_hex<'a>(
+    hex: &'a str,
+    color: &ColorProfile,
+    segments: &mut Vec<(Style, &'a str)>,
+) -> Option<usize> {
+    let mut i = 0;
+    let mut bytes = 0;
+    while i < hex.len() {
+        let space = hex[i..].len() - hex[i..].trim_start().len();
+        if space > 0 {
+            segments.push((color.punctuation, &hex[i..i + space]));
+            i += space;
+        } else {
+            let pair = hex.get(i..i + 2)?;
+            let byte = u8::from_str_radix(pair, 16).ok()?;
+            segments.push((color.dump_byte(byte), pair));
+            i += 2;
+            bytes += 1;
+        }
     }
+    Some(bytes)
+}
+
+/// Splits each line of a hexdump in the style of `hexdump -vC` or `xxd` into
+/// segments styled by `color`.  Returns `None` if `text` isn't a hexdump or
+/// `color` doesn't style hexdumps.
+pub(crate) fn colorize<'a>(
+    text: &'a str,
+    color: &ColorProfile,
+) -> Option<Vec<Vec<(Style, &'a str)>>> {
+    static XXD: OnceCell<Regex> = OnceCell::new();
+    static HEXDUMP: OnceCell<Regex> = OnceCell::new();
+    if !color.styles_dumps() {
+        return None;
+    }
+    let xxd = XXD.get_or_init(|| {
+        Regex::new(r"^([[:xdigit:]]{8,})(:\s+)((?:[[:xdigit:]]{2,}\s)+)(\s+)(.+)$").unwrap()
+    });
+    let hexdump = HEXDUMP.get_or_init(|| {
+        Regex::new(r"^([[:xdigit:]]{8,})(\s+)((?:[[:xdigit:]]{2}\s+?)+)(\s+\|)(.*\|.*)$").unwrap()
+    });
+    text.split('\n')
+        .map(|line| {
+            let c = xxd.captures(line).or_else(|| hexdump.captures(line))?;
+            let part = |i| c.get(i).map_or("", |m| m.as_str());
+            let mut segments = vec![(color.dump_offset, part(1)), (color.punctuation, part(2))];
+            let bytes = colorize_hex(part(3), color, &mut segments)?;
+            segments.push((color.punctuation, part(4)));
+            // The gutter has one character per byte and may be followed by
+            // the closing `|` of a hexdump and a comment naming regions.
+            let tail = part(5);
+            let (gutter, rest) = tail.split_at(tail.get(..bytes).map_or(tail.len(), str::len));
+            let close = usize::from(part(4).ends_with('|') && rest.starts_with('|'));
+            segments.push((color.dump_ascii, gutter));
+            segments.push((color.punctuation, &rest[..close]));
+            segments.push((color.comment, &rest[close..]));
+            segments.retain(|(_, s)| !s.is_empty());
+            Some(segments)
+        })
+        .collect()
 }
 
 // Translate an ASCII byte into its hex numerical value.
@@ -121,19 +492,261 @@ fn from_hex(text: &str, vec: &mut Vec<u8>) -> Result<(), Error> {
     Ok(())
 }
 
+// Given text in one of the RFC 4648 encodings, where each character of
+// `alphabet` encodes `bits` bits, decode the bytes.  Whitespace is ignored.
+// The text must be padded with `=` to a whole number of blocks if `padded`
+// is set, and may be otherwise.
+fn decode_base(text: &str, alphabet: &[u8], bits: u32, padded: bool) -> Result<Vec<u8>, Error> {
+    let text = text
+        .bytes()
+        .filter(|b| !b.is_ascii_whitespace())
+        .collect::<Vec<_>>();
+    // The number of characters in a block which encodes a whole number of
+    // bytes: 4 for base64 and 8 for base32.
+    let block = 8 >> bits.trailing_zeros().min(3);
+    let end = text.iter().position(|&b| b == b'=').unwrap_or(text.len());
+    let (data, padding) = text.split_at(end);
+    if padding.iter().any(|&b| b != b'=') {
+        return Err(Error::HexdumpError("misplaced padding".into()));
+    }
+    // The last character must carry at least one bit of the last byte.
+    let rem = data.len() % block;
+    if rem * bits as usize % 8 >= bits as usize {
+        return Err(Error::HexdumpError(format!(
+            "bad length of {} characters",
+            data.len()
+        )));
+    }
+    if (padded || !padding.is_empty()) && padding.len() != (block - rem) % block {
+        return Err(Error::HexdumpError("bad padding".into()));
+    }
+    let mut vec = Vec::with_capacity(data.len() * bits as usize / 8);
+    let mut acc = 0u32;
+    let mut nbits = 0;
+    for &ch in data {
+        let value = alphabet
+            .iter()
+            .position(|&a| a == ch)
+            .ok_or_else(|| Error::HexdumpError(format!("bad character {:?}", ch as char)))?;
+        acc = (acc << bits | value as u32) & 0xFFFF;
+        nbits += bits;
+        if nbits >= 8 {
+            nbits -= 8;
+            vec.push((acc >> nbits) as u8);
+        }
+    }
+    if acc & ((1 << nbits) - 1) != 0 {
+        return Err(Error::HexdumpError("non-zero trailing bits".into()));
+    }
+    Ok(vec)
+}
+
+// Given text in an unknown RFC 4648 encoding, decode the bytes.  Text which
+// is valid in more than one encoding is decoded as base64.
+fn from_rfc4648(text: &str) -> Result<Vec<u8>, Error> {
+    if text.contains(['-', '_']) {
+        return decode_base(text, BASE64URL, 6, false);
+    }
+    decode_base(text, BASE64, 6, true)
+        .or_else(|e| decode_base(text, BASE32, 5, true).map_err(|_| e))
+        .or_else(|e| decode_base(text, BASE64URL, 6, false).map_err(|_| e))
+}
+
+// Given the body of a C array initializer, parse the hex bytes.
+fn from_c_array(body: &str) -> Result<Vec<u8>, Error> {
+    body.split(',')
+        .map(str::trim)
+        .filter(|item| !item.is_empty())
+        .map(|item| {
+            let digits = item.strip_prefix("0x").or_else(|| item.strip_prefix("0X"));
+            digits
+                .and_then(|d| u8::from_str_radix(d, 16).ok())
+                .ok_or_else(|| Error::HexdumpError(format!("bad array element {:?}", item)))
+        })
+        .collect()
+}
+
+// Returns whether every line of the text is a record starting with `start`
+// followed by at least `min` hex digits.
+fn is_records(text: &str, start: char, min: usize) -> bool {
+    let mut lines = text.lines().map(str::trim).filter(|l| !l.is_empty());
+    lines.clone().next().is_some()
+        && lines.all(|l| {
+            l.len() > min && l.starts_with(start) && l[1..].bytes().all(|b| b.is_ascii_hexdigit())
+        })
+}
+
+// Decodes the hex digits of the records in the text, skipping the first
+// `skip` characters of each record.
+fn decode_records(text: &str, skip: usize) -> Result<Vec<(&str, Vec<u8>)>, Error> {
+    let mut records = Vec::new();
+    for line in text.lines().map(str::trim).filter(|l| !l.is_empty()) {
+        let mut record = Vec::new();
+        from_hex(&line[skip..], &mut record)?;
+        records.push((line, record));
+    }
+    Ok(records)
+}
+
+// Assembles the `(address, data)` segments of a sparse image into a buffer
+// starting at the lowest address.  The gaps between segments are filled with
+// the erased value 0xFF.
+fn assemble(segments: Vec<(u32, Vec<u8>)>) -> Result<Vec<u8>, Error> {
+    let Some(start) = segments.iter().map(|s| s.0).min() else {
+        return Ok(Vec::new());
+    };
+    let mut end = 0;
+    for (addr, data) in &segments {
+        end = end.max(*addr as u64 + data.len() as u64);
+        if end > 1 << 32 {
+            return Err(Error::HexdumpError(format!(
+                "record at {:#x} exceeds the 32-bit address space",
+                addr
+            )));
+        }
+    }
+    let len = (end - start as u64) as usize;
+    if len > MAX_IMAGE {
+        return Err(Error::HexdumpError(format!(
+            "image spans {} bytes, more than the limit of {} bytes",
+            len, MAX_IMAGE
+        )));
+    }
+    let mut image = vec![0xFF; len];
+    for (addr, data) in segments {
+        let offset = (addr - start) as usize;
+        image[offset..offset + data.len()].copy_from_slice(&data);
+    }
+    Ok(image)
+}
+
+// Given Intel HEX text, parse the image.
+fn from_intel_hex(text: &str) -> Result<Vec<u8>, Error> {
+    let mut segments = Vec::new();
+    let mut upper = 0u32;
+    for (line, record) in decode_records(text, 1)? {
+        if record.len() < 5 || record.len() != record[0] as usize + 5 {
+            return Err(Error::HexdumpError(format!("bad record length: {}", line)));
+        }
+        if record.iter().fold(0u8, |a, b| a.wrapping_add(*b)) != 0 {
+            return Err(Error::HexdumpError(format!("bad checksum: {}", line)));
+        }
+        let addr = u16::from_be_bytes([record[1], record[2]]) as u32;
+        let payload = &record[4..record.len() - 1];
+        match (record[3], payload) {
+            (0, _) => {
+                let addr = upper
+                    .checked_add(addr)
+                    .ok_or_else(|| Error::HexdumpError(format!("address overflow: {}", line)))?;
+                segments.push((addr, payload.to_vec()))
+            }
+            (1, _) => break,
+            // Extended segment and extended linear address records.
+            (2, &[hi, lo]) => upper = (u16::from_be_bytes([hi, lo]) as u32) << 4,
+            (4, &[hi, lo]) => upper = (u16::from_be_bytes([hi, lo]) as u32) << 16,
+            (3 | 5, _) => {}
+            _ => return Err(Error::HexdumpError(format!("bad record: {}", line))),
+        }
+    }
+    assemble(segments)
+}
+
+// Given Motorola S-record text, parse the image.
+fn from_srec(text: &str) -> Result<Vec<u8>, Error> {
+    let mut segments = Vec::new();
+    for (line, record) in decode_records(text, 2)? {
+        if record.is_empty() || record.len() != record[0] as usize + 1 {
+            return Err(Error::HexdumpError(format!("bad record length: {}", line)));
+        }
+        if record.iter().fold(0u8, |a, b| a.wrapping_add(*b)) != 0xFF {
+            return Err(Error::HexdumpError(format!("bad checksum: {}", line)));
+        }
+        let width = match &line[..2] {
+            "S1" => 2,
+            "S2" => 3,
+            "S3" => 4,
+            // Header, count and termination records.
+            "S0" | "S5" | "S6" | "S7" | "S8" | "S9" => continue,
+            _ => return Err(Error::HexdumpError(format!("bad record: {}", line))),
+        };
+        if record.len() < width + 2 {
+            return Err(Error::HexdumpError(format!("bad record length: {}", line)));
+        }
+        let addr = record[1..=width]
+            .iter()
+            .fold(0u32, |a, b| a << 8 | *b as u32);
+        segments.push((addr, record[width + 1..record.len() - 1].to_vec()));
+    }
+    assemble(segments)
+}
+
+// Given the `(offset, hex)` captures of the lines of a hexdump, parse the
+// bytes.  Every line must start at the offset which follows the previous
+// line and only the last line may be shorter than the others.
+fn from_dump<'a>(lines: impl Iterator<Item = Captures<'a>>) -> Result<Vec<u8>, Error> {
+    let mut res = Vec::new();
+    let mut width = None;
+    let mut next = None;
+    for c in lines {
+        let offset = u64::from_str_radix(&c[1], 16)
+            .map_err(|_| Error::HexdumpError(format!("bad offset {:?}", &c[1])))?;
+        let start = res.len();
+        from_hex(&c[2], &mut res)?;
+        let len = res.len() - start;
+        if next.is_some_and(|n| n != offset) {
+            return Err(Error::HexdumpError(format!(
+                "expected offset {:08x}, found {:08x}",
+                next.unwrap(),
+                offset
+            )));
+        }
+        // The width of the dump is the length of its first line.
+        let width = *width.get_or_insert(len);
+        if len > width || start % width != 0 {
+            return Err(Error::HexdumpError(format!(
+                "inconsistent line width at offset {:08x}",
+                offset
+            )));
+        }
+        next = Some(
+            offset
+                .checked_add(len as u64)
+                .ok_or_else(|| Error::HexdumpError(format!("offset {:08x} overflows", offset)))?,
+        );
+    }
+    Ok(res)
+}
+
 /// Parses a hexdump string in a variety of forms, returning the resulting bytes.
+///
+/// Base32 and base64 text is recognized when it isn't a hex string.  Text
+/// which is valid in several of the encodings is decoded as base64 rather
+/// than base32.  A `base32:`, `base64:` or `base64url:` prefix selects the
+/// encoding explicitly.
 pub fn from_str(text: &str) -> Result<Vec<u8>, Error> {
-    // Detects `xxd -g<n>` formats.
-    let xxd = RegexBuilder::new(r"^[[:xdigit:]]{8}:\s+((?:[[:xdigit:]]{2,}\s)+)\s+.{1,16}$")
+    // Detects `xxd -g<n> -c<n> -o<n>` formats.
+    let xxd = RegexBuilder::new(r"^([[:xdigit:]]{8,}):\s+((?:[[:xdigit:]]{2,}\s)+)\s+.+$")
         .multi_line(true)
         .build()
         .unwrap();
-    // Detects `hexdump -vC`
+    // Detects `hexdump -vC`, with any number of bytes per line and an
+    // optional comment naming the regions on each line.
     let hexdump =
-        RegexBuilder::new(r"^[[:xdigit:]]{8}\s+((?:[[:xdigit:]]{2}\s+?){1,16})\s+\|.*\|$")
+        RegexBuilder::new(r"^([[:xdigit:]]{8,})\s+((?:[[:xdigit:]]{2}\s+?)+)\s+\|.*\|(?:\s+#.*)?$")
             .multi_line(true)
             .build()
             .unwrap();
+    // Detects a C array initializer, possibly within the declaration
+    // emitted by `xxd -i`.
+    let c_array = RegexBuilder::new(r"^\s*(?:unsigned\s+char\s+\w+\[\]\s*=\s*)?\{([^}]*)\}")
+        .build()
+        .unwrap();
+    // Detects text which is entirely hex digits, and text in the alphabets
+    // of the RFC 4648 encodings.
+    let hex = RegexBuilder::new(r"^(?:0[xX])?[[:xdigit:]]*$")
+        .build()
+        .unwrap();
+    let rfc4648 = RegexBuilder::new(r"^[A-Za-z0-9+/_-]+=*$").build().unwrap();
     // Detects a simple hex string with optional whitespace.
     let hexstr = RegexBuilder::new(r"(?:0[xX])?((?:[[:xdigit:]]{2}\s*)+)")
         .multi_line(false)
@@ -141,10 +754,32 @@ pub fn from_str(text: &str) -> Result<Vec<u8>, Error> {
         .unwrap();
 
     let mut res = Vec::new();
-    let captures = if xxd.is_match(text) {
-        xxd.captures_iter(text)
+    let compact = text
+        .chars()
+        .filter(|c| !c.is_ascii_whitespace())
+        .collect::<String>();
+    if compact.is_empty() {
+        // Every encoding of an empty buffer is empty.
+        return Ok(res);
+    } else if let Some(data) = compact.strip_prefix(BASE64_PREFIX) {
+        return decode_base(data, BASE64, 6, true);
+    } else if let Some(data) = compact.strip_prefix(BASE64URL_PREFIX) {
+        return decode_base(data, BASE64URL, 6, false);
+    } else if let Some(data) = compact.strip_prefix(BASE32_PREFIX) {
+        return decode_base(data, BASE32, 5, true);
+    } else if xxd.is_match(text) {
+        return from_dump(xxd.captures_iter(text));
     } else if hexdump.is_match(text) {
-        hexdump.captures_iter(text)
+        return from_dump(hexdump.captures_iter(text));
+    }
+    let captures = if let Some(c) = c_array.captures(text) {
+        return from_c_array(c.get(1).unwrap().as_str());
+    } else if is_records(text, ':', 10) {
+        return from_intel_hex(text);
+    } else if is_records(text, 'S', 9) {
+        return from_srec(text);
+    } else if rfc4648.is_match(&compact) && !hex.is_match(&compact) {
+        return from_rfc4648(&compact);
     } else if hexstr.is_match(text) {
         hexstr.captures_iter(text)
     } else {
@@ -203,21 +838,172 @@ mod tests {
     #[test]
     fn test_hexdump() -> Result<()> {
         let buf = TEST_STR;
-        let res = hexdump(buf.as_bytes());
+        let res = hexdump(buf.as_bytes(), 8, 16, 0)?;
         assert_eq!(res, HEXDUMP_C);
+        // DEL isn't printable.
+        assert!(hexdump(&[0x7e, 0x7f], 8, 16, 0)?.ends_with("|~.|"));
+        assert!(xxd(&[0x7e, 0x7f], 2, 16, 0)?.ends_with(" ~."));
         Ok(())
     }
 
     #[test]
     fn test_xxd() -> Result<()> {
         let buf = TEST_STR;
-        for n in 0..XXD.len() {
-            let res = xxd(buf.as_bytes(), 1 << n);
-            assert_eq!(res, XXD[n]);
+        for (n, expect) in XXD.iter().enumerate() {
+            let res = xxd(buf.as_bytes(), 1 << n, 16, 0)?;
+            assert_eq!(&res, expect);
         }
         Ok(())
     }
 
+    // Output from `hexdump -vC -e ...` with 8 bytes per line in groups of 4,
+    // and from `xxd -g4 -c32 -o0x20000000 ...`.
+    const HEXDUMP_W8: &str = "\
+00000100  54 68 65 20  71 75 69 63  |The quic|\n\
+00000108  6b 20 62 72  6f 77 6e 20  |k brown |\n\
+00000110  66 6f 78                  |fox|";
+
+    const XXD_W32: &str = "\
+20000000: 54686520 71756963 6b206272 6f776e20 666f7820 6a756d70 6564206f 76657220  The quick brown fox jumped over \n\
+20000020: 74686520 6c617a79 20646f67 21                                            the lazy dog!";
+
+    #[test]
+    fn test_geometry() -> Result<()> {
+        let buf = TEST_STR.as_bytes();
+        assert_eq!(hexdump(&buf[..19], 4, 8, 0x100)?, HEXDUMP_W8);
+        assert_eq!(xxd(buf, 4, 32, 0x2000_0000)?, XXD_W32);
+        assert_eq!(from_str(HEXDUMP_W8)?, &buf[..19]);
+        assert_eq!(from_str(XXD_W32)?, buf);
+        assert_eq!(
+            to_string(buf, BytesFormat::Hexdump)?,
+            to_string(buf, BytesFormat::HexdumpLayout(DumpLayout::HEXDUMP))?
+        );
+        assert_eq!(
+            to_string(buf, BytesFormat::Xxd)?,
+            to_string(buf, BytesFormat::XxdLayout(DumpLayout::XXD))?
+        );
+
+        // Addresses past the end of the address space are errors.
+        let layout = DumpLayout {
+            offset: u64::MAX - 8,
+            ..DumpLayout::XXD
+        };
+        assert!(to_string(buf, BytesFormat::XxdLayout(layout)).is_err());
+        assert!(to_string(buf, BytesFormat::HexdumpLayout(layout)).is_err());
+        assert!(from_str("ffffffffffffffff: 4142  AB").is_err());
+        assert!(from_str("ffffffffffffffff  41 42  |AB|").is_err());
+        Ok(())
+    }
+
+    #[test]
+    fn test_geometry_errors() {
+        // A missing line.
+        let res = from_str(&HEXDUMP_W8.replace("00000108", "00000110"));
+        assert_eq!(
+            res.unwrap_err().to_string(),
+            "Hexdump error: expected offset 00000108, found 00000110"
+        );
+        // A line longer than the first line.
+        let res = from_str("00000000  54 68  |Th|\n00000002  65 20 71  |e q|");
+        assert_eq!(
+            res.unwrap_err().to_string(),
+            "Hexdump error: inconsistent line width at offset 00000002"
+        );
+    }
+
+    #[test]
+    fn test_regions() -> Result<()> {
+        let buf = &TEST_STR.as_bytes()[..19];
+        let format = BytesFormat::HexdumpLayout(DumpLayout {
+            group: 4,
+            width: 8,
+            offset: 0x100,
+        });
+        let regions = [
+            Region::new(4, 5, "adjective"),
+            Region::new(0, 3, "article").with_format(Format::Hex),
+            Region::new(16, 3, "animal").with_format(Format::Base64),
+        ];
+        let dump = to_string_with_regions(buf, format, &regions)?.unwrap();
+        assert_eq!(
+            dump,
+            "\
+00000100  54 68 65 20  71 75 69 63  |The quic|  # article = 0x656854, adjective\n\
+00000108  6b 20 62 72  6f 77 6e 20  |k brown |\n\
+00000110  66 6f 78                  |fox|       # animal = Zm94"
+        );
+        assert_eq!(from_str(&dump)?, buf);
+
+        // The comments are styled as comments.
+        let color = ColorProfile {
+            dump_offset: anstyle::AnsiColor::Yellow.on_default(),
+            comment: Style::new().italic(),
+            ..Default::default()
+        };
+        let lines = colorize(&dump, &color).unwrap();
+        assert_eq!(
+            lines[0].last(),
+            Some(&(color.comment, "  # article = 0x656854, adjective"))
+        );
+        assert_eq!(lines[1].last(), Some(&(Style::new(), "|")));
+        assert_eq!(
+            lines[2].last(),
+            Some(&(color.comment, "       # animal = Zm94"))
+        );
+
+        let res = to_string_with_regions(buf, format, &[Region::new(16, 4, "animal")]);
+        assert_eq!(
+            res.unwrap_err().to_string(),
+            "Hexdump error: region `animal` is outside of the 19 bytes of data"
+        );
+        let res = to_string_with_regions(
+            buf,
+            format,
+            &[Region::new(0, 17, "text").with_format(Format::Hex)],
+        );
+        assert_eq!(
+            res.unwrap_err().to_string(),
+            "Hexdump error: region `text` is too long to show as an integer"
+        );
+        Ok(())
+    }
+
+    #[test]
+    fn test_colorize() {
+        use anstyle::AnsiColor;
+        let color = ColorProfile {
+            dump_offset: AnsiColor::Yellow.on_default(),
+            dump_zero: Style::new().dimmed(),
+            dump_printable: AnsiColor::Green.on_default(),
+            dump_nonprintable: AnsiColor::Magenta.on_default(),
+            dump_ascii: AnsiColor::Cyan.on_default(),
+            ..Default::default()
+        };
+        let text = xxd(&[0x00, 0x41, 0x0a], 2, 16, 0).unwrap();
+        let plain = Style::new();
+        let gap = " ".repeat(33);
+        assert_eq!(
+            colorize(&text, &color).unwrap(),
+            vec![vec![
+                (color.dump_offset, "00000000"),
+                (plain, ": "),
+                (color.dump_zero, "00"),
+                (color.dump_printable, "41"),
+                (plain, " "),
+                (color.dump_nonprintable, "0a"),
+                (plain, " "),
+                (plain, gap.as_str()),
+                (color.dump_ascii, ".A."),
+            ]]
+        );
+        let lines = colorize(HEXDUMP_C, &color).unwrap();
+        assert_eq!(lines.len(), 3);
+        assert_eq!(lines[2].last(), Some(&(plain, "|")));
+        // Other text and profiles which don't style hexdumps are left alone.
+        assert!(colorize("The quick brown fox", &color).is_none());
+        assert!(colorize(&text, &ColorProfile::default()).is_none());
+    }
+
     #[test]
     fn test_from_hexstr() -> Result<()> {
         let buf = "5468652071756963\n6b2062726f776e20";
@@ -237,11 +1023,193 @@ mod tests {
 
     #[test]
     fn test_from_xxd() -> Result<()> {
-        for n in 0..XXD.len() {
-            let res = from_str(XXD[n])?;
+        for x in XXD {
+            let res = from_str(x)?;
             let s = std::str::from_utf8(&res)?;
             assert_eq!(s, TEST_STR);
         }
         Ok(())
     }
+
+    #[test]
+    fn test_base_encodings() -> Result<()> {
+        // Test vectors from RFC 4648.
+        let vectors = [
+            ("f", "Zg==", "MY======"),
+            ("fo", "Zm8=", "MZXQ===="),
+            ("foo", "Zm9v", "MZXW6==="),
+            ("foob", "Zm9vYg==", "MZXW6YQ="),
+            ("fooba", "Zm9vYmE=", "MZXW6YTB"),
+            ("foobar", "Zm9vYmFy", "MZXW6YTBOI======"),
+        ];
+        for (data, b64, b32) in vectors {
+            assert_eq!(
+                to_string(data.as_bytes(), BytesFormat::Base64)?.unwrap(),
+                b64
+            );
+            assert_eq!(
+                to_string(data.as_bytes(), BytesFormat::Base32)?.unwrap(),
+                b32
+            );
+            assert_eq!(from_str(b64)?, data.as_bytes());
+            assert_eq!(from_str(&format!("base32:{b32}"))?, data.as_bytes());
+        }
+        let buf = [0xfb, 0xff, 0xbf];
+        assert_eq!(to_string(&buf, BytesFormat::Base64)?.unwrap(), "+/+/");
+        assert_eq!(to_string(&buf, BytesFormat::Base64Url)?.unwrap(), "-_-_");
+        assert_eq!(from_str("+/+/")?, buf);
+        assert_eq!(from_str("-_-_")?, buf);
+        assert_eq!(from_str("-_8")?, buf[..2]);
+        assert_eq!(from_str("base64url:-_8=")?, buf[..2]);
+        assert_eq!(from_str("base64:Zm9v\nYmFy")?, b"foobar");
+
+        // Malformed text is rejected.
+        for text in [
+            "Zm9vY",
+            "base64:Zm9vYg",
+            "Zm9vYg=",
+            "Zm9vYg===",
+            "Zm=9v",
+            "Zh==",
+            "Zm9=",
+            "MZXW6===Q",
+            "base32:MY",
+            "base32:MZ======",
+        ] {
+            assert!(from_str(text).is_err(), "{text}");
+        }
+        Ok(())
+    }
+
+    #[test]
+    fn test_ambiguous_encodings() -> Result<()> {
+        // Text which is a hex string decodes as hex, and text which is valid
+        // in both base64 and base32 decodes as base64.
+        assert_eq!(
+            to_string(&[0x00, 0x10, 0x83], BytesFormat::Base64)?.unwrap(),
+            "ABCD"
+        );
+        assert_eq!(from_str("ABCD")?, [0xab, 0xcd]);
+        assert_eq!(
+            to_string(b"fooba", BytesFormat::Base32)?.unwrap(),
+            "MZXW6YTB"
+        );
+        assert_eq!(from_str("MZXW6YTB")?, [0x31, 0x95, 0xd6, 0xe9, 0x84, 0xc1]);
+        assert_eq!(from_str("base32:MZXW6YTB")?, b"fooba");
+        // Base32 with more padding than base64 permits decodes as base32.
+        assert_eq!(from_str("MZXW6===")?, b"foo");
+        Ok(())
+    }
+
+    // Output from `xxd -i < ...`, wrapped in a declaration as `xxd -i <file>`
+    // does.
+    const XXD_I: &str = "\
+unsigned char data[] = {
+  0x54, 0x68, 0x65, 0x20, 0x71, 0x75, 0x69, 0x63, 0x6b, 0x20, 0x62, 0x72,
+  0x6f, 0x77, 0x6e, 0x20, 0x66, 0x6f, 0x78, 0x20, 0x6a, 0x75, 0x6d, 0x70,
+  0x65, 0x64, 0x20, 0x6f, 0x76, 0x65, 0x72, 0x20, 0x74, 0x68, 0x65, 0x20,
+  0x6c, 0x61, 0x7a, 0x79, 0x20, 0x64, 0x6f, 0x67, 0x21
+};
+unsigned int data_len = 45;";
+
+    #[test]
+    fn test_c_array() -> Result<()> {
+        let buf = TEST_STR.as_bytes();
+        assert_eq!(
+            to_string(&buf[..4], BytesFormat::CArray)?.unwrap(),
+            "{0x54, 0x68, 0x65, 0x20}"
+        );
+        assert_eq!(to_string(buf, BytesFormat::XxdInclude)?.unwrap(), XXD_I);
+        assert_eq!(from_str(XXD_I)?, buf);
+        assert_eq!(from_str("{0x54, 0X68,\n 0x65,}")?, &buf[..3]);
+        assert!(from_str("{0x54, 104}").is_err());
+        Ok(())
+    }
+
+    #[test]
+    fn test_intel_hex() -> Result<()> {
+        let buf = [
+            0x21, 0x46, 0x01, 0x36, 0x01, 0x21, 0x47, 0x01, 0x36, 0x00, 0x7e, 0xfe, 0x09, 0xd2,
+            0x19, 0x01,
+        ];
+        let res = intel_hex(&buf, 0x100)?;
+        assert_eq!(
+            res,
+            ":10010000214601360121470136007EFE09D2190140\n:00000001FF"
+        );
+        assert_eq!(from_str(&res)?, buf);
+        assert!(from_str(":10010000214601360121470136007EFE09D2190141").is_err());
+        Ok(())
+    }
+
+    #[test]
+    fn test_srec() -> Result<()> {
+        let mut buf = vec![0x0a, 0x0a, 0x0d];
+        buf.resize(16, 0);
+        let res = srec(&buf, 0x7af0)?;
+        assert_eq!(
+            res,
+            "S0030000FC\nS1137AF00A0A0D0000000000000000000000000061\nS9030000FC"
+        );
+        assert_eq!(from_str(&res)?, buf);
+        Ok(())
+    }
+
+    #[test]
+    fn test_sparse_image() -> Result<()> {
+        // The erased record between the two halves of the image is omitted.
+        let mut buf = TEST_STR.as_bytes()[..16].to_vec();
+        buf.extend([0xFF; 16]);
+        buf.extend(b"tail");
+        let res = intel_hex(&buf, 0x0800_0000)?;
+        assert_eq!(
+            res,
+            "\
+:020000040800F2
+:1000000054686520717569636B2062726F776E202A
+:040020007461696C32
+:00000001FF"
+        );
+        assert_eq!(from_str(&res)?, buf);
+        let res = srec(&buf, 0x0800_0000)?;
+        assert_eq!(res.lines().count(), 4);
+        assert!(res.lines().nth(1).unwrap().starts_with("S31508000000"));
+        assert_eq!(from_str(&res)?, buf);
+        Ok(())
+    }
+
+    #[test]
+    fn test_image_errors() -> Result<()> {
+        // An image which doesn't fit below 4 GiB.
+        let res = intel_hex(&[0; 32], 0xFFFF_FFF0);
+        assert_eq!(
+            res.unwrap_err().to_string(),
+            "Hexdump error: an image of 32 bytes at 0xfffffff0 exceeds the 32-bit address space"
+        );
+        assert!(srec(&[0; 32], 0xFFFF_FFF0).is_err());
+
+        // A record which runs past the end of the address space.
+        let res = from_str(":02000004FFFFFC\n:02FFFF00000000\n:00000001FF");
+        assert_eq!(
+            res.unwrap_err().to_string(),
+            "Hexdump error: record at 0xffffffff exceeds the 32-bit address space"
+        );
+
+        // Records at distant addresses.
+        let low = srec(&[0], 0)?;
+        for text in [
+            format!(":0100000000FF\n{}", intel_hex(&[0], 0xFFFF_0000)?),
+            format!(
+                "{}\n{}",
+                low.lines().nth(1).unwrap(),
+                srec(&[0], 0xFFFF_0000)?
+            ),
+        ] {
+            assert_eq!(
+                from_str(&text).unwrap_err().to_string(),
+                "Hexdump error: image spans 4294901761 bytes, more than the limit of 67108864 bytes"
+            );
+        }
+        Ok(())
+    }
 }
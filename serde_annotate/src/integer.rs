@@ -67,8 +67,8 @@ impl IntValue {
             let bits = std::mem::size_of_val(&v) * 8;
             width = match base {
                 Base::Bin => bits,
-                Base::Oct => (bits + 2) / 3,
-                Base::Hex => (bits + 3) / 4,
+                Base::Oct => bits.div_ceil(3),
+                Base::Hex => bits.div_ceil(4),
                 Base::Dec => unreachable!(),
             };
         }
@@ -121,6 +121,33 @@ impl IntValue {
         }
     }
 
+    pub fn is_negative(&self) -> bool {
+        match self {
+            IntValue::I8(v) => *v < 0,
+            IntValue::I16(v) => *v < 0,
+            IntValue::I32(v) => *v < 0,
+            IntValue::I64(v) => *v < 0,
+            IntValue::I128(v) => *v < 0,
+            _ => false,
+        }
+    }
+
+    // Returns the sign and magnitude of the integer.
+    fn sign_magnitude(&self) -> (bool, u128) {
+        match *self {
+            IntValue::U8(v) => (false, v as u128),
+            IntValue::U16(v) => (false, v as u128),
+            IntValue::U32(v) => (false, v as u128),
+            IntValue::U64(v) => (false, v as u128),
+            IntValue::U128(v) => (false, v),
+            IntValue::I8(v) => (v < 0, v.unsigned_abs() as u128),
+            IntValue::I16(v) => (v < 0, v.unsigned_abs() as u128),
+            IntValue::I32(v) => (v < 0, v.unsigned_abs() as u128),
+            IntValue::I64(v) => (v < 0, v.unsigned_abs() as u128),
+            IntValue::I128(v) => (v < 0, v.unsigned_abs()),
+        }
+    }
+
     pub fn negate(self) -> Self {
         match self {
             IntValue::U8(v) => IntValue::I16(-(v as i16)),
@@ -137,11 +164,43 @@ impl IntValue {
     }
 }
 
+/// Integer values compare by numeric value, regardless of their width.
+impl PartialEq for IntValue {
+    fn eq(&self, other: &Self) -> bool {
+        match (self.sign_magnitude(), other.sign_magnitude()) {
+            ((_, 0), (_, 0)) => true,
+            (a, b) => a == b,
+        }
+    }
+}
+
+impl Eq for IntValue {}
+
+/// Integer values are ordered by numeric value, regardless of their width.
+impl PartialOrd for IntValue {
+    fn partial_cmp(&self, other: &Self) -> Option<std::cmp::Ordering> {
+        Some(self.cmp(other))
+    }
+}
+
+impl Ord for IntValue {
+    fn cmp(&self, other: &Self) -> std::cmp::Ordering {
+        match (self.sign_magnitude(), other.sign_magnitude()) {
+            ((_, 0), (_, 0)) => std::cmp::Ordering::Equal,
+            ((true, a), (true, b)) => b.cmp(&a),
+            ((true, _), _) => std::cmp::Ordering::Less,
+            (_, (true, _)) => std::cmp::Ordering::Greater,
+            ((false, a), (false, b)) => a.cmp(&b),
+        }
+    }
+}
+
 #[derive(Clone, Debug)]
 pub struct Int {
     value: IntValue,
     base: Base,
     width: usize,
+    group: usize,
 }
 
 impl Int {
@@ -152,6 +211,7 @@ impl Int {
             value: v.into(),
             base,
             width,
+            group: 0,
         }
     }
     /// Creates an `Int` that will display with no zero padding.
@@ -162,6 +222,13 @@ impl Int {
     pub fn new_padded<T: Into<IntValue>>(v: T, base: Base) -> Int {
         Self::new_with_padding(v, base, usize::MAX)
     }
+    /// Separates every `group` digits with an underscore (e.g. `0x1234_5678`)
+    /// when the integer is displayed in its preferred base.  A `group` of zero
+    /// disables the separators.
+    pub fn with_group(mut self, group: usize) -> Int {
+        self.group = group;
+        self
+    }
 
     /// Returns whether the integer is within the legal range of json integers.
     pub fn is_legal_json(&self) -> bool {
@@ -174,14 +241,74 @@ impl Int {
         }
     }
 
+    /// Returns the value of this integer.
+    pub fn value(&self) -> &IntValue {
+        &self.value
+    }
+
     /// Returns the preferred base for expressing this integer.
     pub fn base(&self) -> Base {
         self.base
     }
 
+    /// Returns the minimum number of digits for expressing this integer.
+    pub fn width(&self) -> usize {
+        self.width
+    }
+
+    /// Returns the number of digits between underscore separators, if any.
+    pub fn group(&self) -> usize {
+        self.group
+    }
+
+    /// Returns whether the integer is less than zero.
+    pub fn is_negative(&self) -> bool {
+        self.value.is_negative()
+    }
+
     /// Formats the integer in the requested base, defaulting to the preferred base.
     pub fn format(&self, base: Option<&Base>) -> String {
-        self.value.format(*base.unwrap_or(&Base::Dec), self.width)
+        let base = *base.unwrap_or(&Base::Dec);
+        let text = self.value.format(base, self.width);
+        if base == self.base && self.group > 0 {
+            Self::group_digits(&text, base, self.group)
+        } else {
+            text
+        }
+    }
+
+    // Inserts an underscore between every `group` digits, counting from the
+    // least significant digit.
+    fn group_digits(text: &str, base: Base, group: usize) -> String {
+        let prefix = match base {
+            Base::Dec => usize::from(text.starts_with('-')),
+            _ => 2,
+        };
+        let (prefix, digits) = text.split_at(prefix);
+        let mut result = String::from(prefix);
+        for (i, ch) in digits.chars().enumerate() {
+            if i > 0 && (digits.len() - i) % group == 0 {
+                result.push('_');
+            }
+            result.push(ch);
+        }
+        result
+    }
+
+    // Removes the underscores which separate groups of digits, returning the
+    // digits and the size of the groups if all groups are the same size.
+    fn strip_separators(text: &str) -> Option<(String, usize)> {
+        if !text.contains('_') {
+            return Some((text.to_string(), 0));
+        }
+        let groups = text.split('_').collect::<Vec<_>>();
+        if groups.iter().any(|g| g.is_empty()) {
+            // Leading, trailing or repeated separators are not permitted.
+            return None;
+        }
+        let size = groups[groups.len() - 1].len();
+        let uniform = groups[1..].iter().all(|g| g.len() == size) && groups[0].len() <= size;
+        Some((groups.concat(), if uniform { size } else { 0 }))
     }
 
     fn strip_numeric_prefix(src: &str, ch: u8) -> &str {
@@ -214,6 +341,9 @@ impl Int {
     /// - If `radix` is `0`, the base is inferred from the common integer
     ///   prefixes `0x`, `0b` and `0o`.  If there is no prefix, the base defaults
     ///   to base 10.
+    ///
+    /// The digits may be separated by single underscores (e.g. `0x1234_5678`).
+    /// Evenly sized groups of digits are preserved when the integer is displayed.
     pub fn from_str_radix(src: &str, radix: u32) -> Result<Int, ParseIntError> {
         let (negative, src) = if let Some(s) = src.strip_prefix('-') {
             (true, s)
@@ -229,9 +359,14 @@ impl Int {
             10 => (Base::Dec, src),
             _ => Self::detect_numeric_prefix(src),
         };
-        let value = IntValue::U128(u128::from_str_radix(text, base as u32)?);
+        let (digits, group) = match Self::strip_separators(text) {
+            Some(d) => d,
+            // Let the standard library report the malformed digits.
+            None => return Err(u128::from_str_radix(text, base as u32).unwrap_err()),
+        };
+        let value = IntValue::U128(u128::from_str_radix(&digits, base as u32)?);
         let value = if negative { value.negate() } else { value };
-        Ok(Self::new_with_padding(value, base, text.len()))
+        Ok(Self::new_with_padding(value, base, digits.len()).with_group(group))
     }
 }
 
@@ -323,6 +458,22 @@ mod tests {
         assert_eq!(Int::new(-16i8, Base::Hex).to_string(), "0xF0");
     }
 
+    #[test]
+    fn value_equality() -> Result<()> {
+        assert_eq!(IntValue::from(16u8), IntValue::from(16i64));
+        assert_eq!(
+            Int::from_str_radix("0x10", 0)?.value(),
+            Int::new(16u32, Base::Dec).value()
+        );
+        assert_eq!(IntValue::from(-1i8), IntValue::from(-1i128));
+        assert_ne!(IntValue::from(-1i8), IntValue::from(1u8));
+        assert_ne!(IntValue::from(255u8), IntValue::from(-1i8));
+        assert!(IntValue::from(-2i8) < IntValue::from(-1i64));
+        assert!(IntValue::from(-1i8) < IntValue::from(0u8));
+        assert!(IntValue::from(u128::MAX) > IntValue::from(i128::MAX));
+        Ok(())
+    }
+
     #[test]
     fn basic_parse() -> Result<()> {
         assert_eq!(u8::from(Int::from_str_radix("0b10", 0)?), 2);
@@ -384,4 +535,46 @@ mod tests {
             "0x10000"
         );
     }
+
+    #[test]
+    fn digit_grouping() -> Result<()> {
+        assert_eq!(
+            Int::new_padded(0x12345678u32, Base::Hex)
+                .with_group(4)
+                .to_string(),
+            "0x1234_5678"
+        );
+        assert_eq!(
+            Int::new_padded(0xA0u8, Base::Bin).with_group(4).to_string(),
+            "0b1010_0000"
+        );
+        assert_eq!(
+            Int::new(-1234567i32, Base::Dec).with_group(3).to_string(),
+            "-1_234_567"
+        );
+        assert_eq!(
+            Int::new_with_padding(0x1234u16, Base::Hex, 6)
+                .with_group(4)
+                .to_string(),
+            "0x00_1234"
+        );
+        // Grouping only applies to the preferred base.
+        assert_eq!(
+            Int::new(0x10000u32, Base::Hex)
+                .with_group(4)
+                .format(Some(&Base::Dec)),
+            "65536"
+        );
+
+        let i = Int::from_str_radix("0x00_1234_5678", 0)?;
+        assert_eq!(u32::from(&i), 0x12345678);
+        assert_eq!((i.width(), i.group()), (10, 4));
+        assert_eq!(i.to_string(), "0x00_1234_5678");
+        // Irregular groups are accepted but not preserved.
+        assert_eq!(Int::from_str_radix("1_00_0", 0)?.to_string(), "1000");
+        assert!(Int::from_str_radix("0x_1234", 0).is_err());
+        assert!(Int::from_str_radix("1234_", 0).is_err());
+        assert!(Int::from_str_radix("12__34", 0).is_err());
+        Ok(())
+    }
 }
@@ -1,7 +1,9 @@
 use crate::color::{ColorProfile, PaintExt};
 use crate::document::{CommentFormat, Document, StrFormat};
 use crate::error::Error;
+use crate::hexdump;
 use crate::integer::{Base, Int};
+use anstyle::Style;
 use once_cell::sync::OnceCell;
 use std::collections::HashSet;
 use std::fmt::{self, Display};
@@ -99,9 +101,10 @@ impl Json {
     }
 }
 
-impl fmt::Display for Json {
-    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
-        let mut emitter = JsonEmitter {
+impl Json {
+    // Returns an emitter configured with the formatting properties.
+    pub(crate) fn emitter(&self) -> JsonEmitter {
+        JsonEmitter {
             level: 0,
             indent: self.indent,
             color: self.color,
@@ -113,8 +116,15 @@ impl fmt::Display for Json {
             multiline: self.multiline,
             bare_keys: self.bare_keys,
             compact: self.compact,
-        };
-        emitter.emit_node(f, &self.document).map_err(|_| fmt::Error)
+        }
+    }
+}
+
+impl fmt::Display for Json {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        self.emitter()
+            .emit_node(f, &self.document)
+            .map_err(|_| fmt::Error)
     }
 }
 
@@ -163,10 +173,10 @@ impl Document {
     }
 }
 
-struct JsonEmitter {
-    level: usize,
+pub(crate) struct JsonEmitter {
+    pub(crate) level: usize,
     indent: usize,
-    color: ColorProfile,
+    pub(crate) color: ColorProfile,
     comment: HashSet<CommentFormat>,
     standard_comment: CommentFormat,
     bases: HashSet<Base>,
@@ -174,7 +184,7 @@ struct JsonEmitter {
     strict_numeric_limits: bool,
     multiline: Multiline,
     bare_keys: bool,
-    compact: bool,
+    pub(crate) compact: bool,
 }
 
 impl Default for JsonEmitter {
@@ -196,7 +206,7 @@ impl Default for JsonEmitter {
 }
 
 impl JsonEmitter {
-    fn emit_node<W: fmt::Write>(&mut self, w: &mut W, node: &Document) -> Result<()> {
+    pub(crate) fn emit_node<W: fmt::Write>(&mut self, w: &mut W, node: &Document) -> Result<()> {
         match node {
             Document::Comment(c, f) => self.emit_comment_newline(w, c, f),
             Document::String(v, f) => self.emit_string(w, v.as_str(), *f),
@@ -273,8 +283,11 @@ impl JsonEmitter {
             }
             if let Document::Fragment(nodes) = value {
                 let mut val_done = false;
-                for node in nodes {
+                for (j, node) in nodes.iter().enumerate() {
                     if let Some((c, f)) = node.comment() {
+                        if !self.comments_enabled() {
+                            continue;
+                        }
                         if val_done && need_eol {
                             write!(w, " ")?;
                         }
@@ -287,8 +300,9 @@ impl JsonEmitter {
                         continue;
                     }
                     if !val_done {
-                        self.emit_node(w, node)?;
-                        if i != last {
+                        let trailing = self.has_comment(nodes[j + 1..].iter());
+                        let eol = self.emit_value(w, node, trailing)?;
+                        if i != last && !eol {
                             write!(w, "{}", self.color.punctuation.paint(","))?;
                         }
                         val_done = true;
@@ -298,8 +312,8 @@ impl JsonEmitter {
                     }
                 }
             } else {
-                self.emit_node(w, value)?;
-                if i != last {
+                let eol = self.emit_value(w, value, false)?;
+                if i != last && !eol {
                     write!(w, "{}", self.color.punctuation.paint(","))?;
                 }
                 need_eol = true;
@@ -314,6 +328,46 @@ impl JsonEmitter {
         Ok(())
     }
 
+    pub(crate) fn emit_key_node<W: fmt::Write>(
+        &mut self,
+        w: &mut W,
+        node: &Document,
+    ) -> Result<()> {
+        match node {
+            Document::String(s, _) => self.emit_key(w, s.as_str())?,
+            Document::StaticStr(s, _) => self.emit_key(w, s)?,
+            Document::Boolean(v) => write!(
+                w,
+                "{}{}{}",
+                self.color.punctuation.paint("\""),
+                self.color.key.paint(v),
+                self.color.punctuation.paint("\"")
+            )?,
+            Document::Int(v) => write!(
+                w,
+                "{}{}{}",
+                self.color.punctuation.paint("\""),
+                self.color.key.paint(v),
+                self.color.punctuation.paint("\"")
+            )?,
+            Document::Float(v) => write!(
+                w,
+                "{}{}{}",
+                self.color.punctuation.paint("\""),
+                self.color.key.paint(v),
+                self.color.punctuation.paint("\"")
+            )?,
+            Document::Comment(_, _) => return Err(Error::KeyTypeError("comment")),
+            Document::Mapping(_) => return Err(Error::KeyTypeError("mapping")),
+            Document::Sequence(_) => return Err(Error::KeyTypeError("sequence")),
+            Document::Bytes(_) => return Err(Error::KeyTypeError("bytes")),
+            Document::Compact(_) => return Err(Error::KeyTypeError("compact")),
+            Document::Fragment(_) => return Err(Error::KeyTypeError("fragment")),
+            Document::Null => return Err(Error::KeyTypeError("null")),
+        }
+        Ok(())
+    }
+
     fn emit_key<W: fmt::Write>(&mut self, w: &mut W, s: &str) -> Result<()> {
         if self.bare_keys && is_legal_bareword(s) {
             write!(w, "{}", self.color.key.paint(s))?
@@ -339,7 +393,7 @@ impl JsonEmitter {
         let last = Document::last_value_index(mapping);
         let mut need_eol = false;
         for (i, frag) in mapping.iter().enumerate() {
-            let nodes = frag.fragments()?;
+            let nodes = frag.entry_nodes()?;
             if i > 0 && need_eol {
                 write!(w, "{}", if self.compact { " " } else { "\n" })?;
                 if i <= last || !self.comment.is_empty() {
@@ -349,8 +403,11 @@ impl JsonEmitter {
             }
             let mut key_done = i > last;
             let mut val_done = i > last;
-            for node in nodes {
+            for (j, node) in nodes.iter().enumerate() {
                 if let Some((c, f)) = node.comment() {
+                    if !self.comments_enabled() {
+                        continue;
+                    }
                     if val_done && need_eol {
                         write!(w, " ")?;
                     }
@@ -363,43 +420,13 @@ impl JsonEmitter {
                     continue;
                 }
                 if !key_done {
-                    match node {
-                        Document::String(s, _) => self.emit_key(w, s.as_str())?,
-                        Document::StaticStr(s, _) => self.emit_key(w, s)?,
-                        Document::Boolean(v) => write!(
-                            w,
-                            "{}{}{}",
-                            self.color.punctuation.paint("\""),
-                            self.color.key.paint(v),
-                            self.color.punctuation.paint("\"")
-                        )?,
-                        Document::Int(v) => write!(
-                            w,
-                            "{}{}{}",
-                            self.color.punctuation.paint("\""),
-                            self.color.key.paint(v),
-                            self.color.punctuation.paint("\"")
-                        )?,
-                        Document::Float(v) => write!(
-                            w,
-                            "{}{}{}",
-                            self.color.punctuation.paint("\""),
-                            self.color.key.paint(v),
-                            self.color.punctuation.paint("\"")
-                        )?,
-                        Document::Comment(_, _) => return Err(Error::KeyTypeError("comment")),
-                        Document::Mapping(_) => return Err(Error::KeyTypeError("mapping")),
-                        Document::Sequence(_) => return Err(Error::KeyTypeError("sequence")),
-                        Document::Bytes(_) => return Err(Error::KeyTypeError("bytes")),
-                        Document::Compact(_) => return Err(Error::KeyTypeError("compact")),
-                        Document::Fragment(_) => return Err(Error::KeyTypeError("fragment")),
-                        Document::Null => return Err(Error::KeyTypeError("null")),
-                    };
+                    self.emit_key_node(w, node)?;
                     write!(w, "{}", self.color.punctuation.paint(": "))?;
                     key_done = true;
                 } else if !val_done {
-                    self.emit_node(w, node)?;
-                    if i != last {
+                    let trailing = self.has_comment(nodes[j + 1..].iter().copied());
+                    let eol = self.emit_value(w, node, trailing)?;
+                    if i != last && !eol {
                         write!(w, "{}", self.color.punctuation.paint(","))?;
                     }
                     val_done = true;
@@ -416,6 +443,44 @@ impl JsonEmitter {
         Ok(())
     }
 
+    // Emits the value of a mapping entry or sequence element and returns
+    // whether it extends to the end of the line.  An HJSON quoteless string
+    // takes the rest of its line, so it is only emitted when no comma or
+    // comment (`trailing`) follows it on the same line.
+    fn emit_value<W: fmt::Write>(
+        &mut self,
+        w: &mut W,
+        node: &Document,
+        trailing: bool,
+    ) -> Result<bool> {
+        let quoteless = match node {
+            Document::String(s, StrFormat::Unquoted) => Some(s.as_str()),
+            Document::StaticStr(s, StrFormat::Unquoted) => Some(*s),
+            _ => None,
+        }
+        .filter(|s| {
+            self.multiline == Multiline::Hjson
+                && !self.compact
+                && !trailing
+                && is_legal_quoteless(s)
+        });
+        match quoteless {
+            Some(s) => {
+                write!(w, "{}", self.color.string.paint(s))?;
+                Ok(true)
+            }
+            None => {
+                self.emit_node(w, node)?;
+                Ok(false)
+            }
+        }
+    }
+
+    // Returns whether any of `nodes` is a comment which will be emitted.
+    fn has_comment<'d>(&self, mut nodes: impl Iterator<Item = &'d Document>) -> bool {
+        self.comments_enabled() && nodes.any(|n| n.comment().is_some())
+    }
+
     fn emit_comment_newline<W: fmt::Write>(
         &mut self,
         w: &mut W,
@@ -429,23 +494,56 @@ impl JsonEmitter {
         Ok(())
     }
 
-    fn emit_comment<W: fmt::Write>(
+    // Emits comments following a value on the same line.
+    pub(crate) fn emit_trailing<W: fmt::Write>(
+        &mut self,
+        w: &mut W,
+        comments: &[Document],
+    ) -> Result<()> {
+        for (c, f) in comments.iter().filter_map(Document::comment) {
+            if self.comments_enabled() {
+                write!(w, " ")?;
+                self.emit_comment(w, c, f)?;
+            }
+        }
+        Ok(())
+    }
+
+    // Returns whether comments are emitted.
+    fn comments_enabled(&self) -> bool {
+        !self.compact && !self.comment.is_empty()
+    }
+
+    pub(crate) fn emit_comment<W: fmt::Write>(
         &mut self,
         w: &mut W,
         comment: &str,
         format: &CommentFormat,
     ) -> Result<bool> {
-        if self.compact || self.comment.is_empty() {
+        if !self.comments_enabled() {
             return Ok(false);
         }
-        let format = *self.comment.get(format).unwrap_or(&self.standard_comment);
+        let mut format = *self.comment.get(format).unwrap_or(&self.standard_comment);
+        // A block comment can't contain its terminator, so use line comments.
+        if format == CommentFormat::Block && comment.contains("*/") {
+            format = match self.standard_comment {
+                CommentFormat::Block => CommentFormat::SlashSlash,
+                f => f,
+            };
+        }
         let leader = match format {
             CommentFormat::SlashSlash | CommentFormat::Standard => "//",
             CommentFormat::Hash => "#",
+            CommentFormat::Block if !comment.contains('\n') => {
+                let comment = format_args!("/* {} */", comment);
+                write!(w, "{}", self.color.comment.paint(comment))?;
+                return Ok(true);
+            }
             CommentFormat::Block => " *",
         };
         if format == CommentFormat::Block {
-            writeln!(w, "/*")?;
+            write!(w, "{}", self.color.comment.paint("/*"))?;
+            writeln!(w)?;
             self.emit_indent(w)?;
         }
         for (i, line) in comment.split('\n').enumerate() {
@@ -466,8 +564,9 @@ impl JsonEmitter {
             }
         }
         if format == CommentFormat::Block {
-            writeln!(w, "*/")?;
+            writeln!(w)?;
             self.emit_indent(w)?;
+            write!(w, "{}", self.color.comment.paint(" */"))?;
         }
         Ok(true)
     }
@@ -482,6 +581,13 @@ impl JsonEmitter {
 
     fn emit_string_strict<W: fmt::Write>(&mut self, w: &mut W, value: &str) -> Result<()> {
         write!(w, "{}", self.color.punctuation.paint("\""))?;
+        self.emit_escaped(w, value, self.color.string)?;
+        write!(w, "{}", self.color.punctuation.paint("\""))?;
+        Ok(())
+    }
+
+    // Emits the characters of a string in `style`, escaping as needed.
+    fn emit_escaped<W: fmt::Write>(&mut self, w: &mut W, value: &str, style: Style) -> Result<()> {
         let bytes = value.as_bytes();
         let mut start = 0;
         for (i, &byte) in bytes.iter().enumerate() {
@@ -490,7 +596,7 @@ impl JsonEmitter {
                 continue;
             }
             if start < i {
-                write!(w, "{}", self.color.string.paint(&value[start..i]))?;
+                write!(w, "{}", style.paint(&value[start..i]))?;
             }
             match escape {
                 UU => write!(
@@ -509,9 +615,8 @@ impl JsonEmitter {
             start = i + 1;
         }
         if start != bytes.len() {
-            write!(w, "{}", self.color.string.paint(&value[start..]))?;
+            write!(w, "{}", style.paint(&value[start..]))?;
         }
-        write!(w, "{}", self.color.punctuation.paint("\""))?;
         Ok(())
     }
 
@@ -525,48 +630,27 @@ impl JsonEmitter {
         } else {
             write!(w, "{}", self.color.punctuation.paint("\""))?;
         }
-        let bytes = value.as_bytes();
-        let mut start = 0;
-        for (i, &byte) in bytes.iter().enumerate() {
-            let escape = ESCAPE[byte as usize];
-            if escape == 0 {
-                continue;
-            }
-            if start < i {
-                write!(w, "{}", self.color.string.paint(&value[start..i]))?;
-            }
-            match escape {
-                UU => write!(
-                    w,
-                    "{}",
-                    self.color.escape.paint(format_args!("\\u{:04x}", byte))
-                )?,
-                NN => match self.multiline {
-                    Multiline::None => write!(
-                        w,
-                        "{}",
-                        self.color
-                            .escape
-                            .paint(format_args!("\\{}", escape as char))
-                    )?,
+        // Hexdumps are styled piecewise, if the color profile styles them.
+        let dump = hexdump::colorize(value, &self.color);
+        for (i, line) in value.split('\n').enumerate() {
+            if i > 0 {
+                match self.multiline {
+                    Multiline::None => write!(w, "{}", self.color.escape.paint("\\n"))?,
                     Multiline::Json5 => writeln!(w, "{}", self.color.escape.paint("\\"))?,
                     Multiline::Hjson => {
                         writeln!(w)?;
                         self.emit_indent(w)?;
                     }
-                },
-                _ => write!(
-                    w,
-                    "{}",
-                    self.color
-                        .escape
-                        .paint(format_args!("\\{}", escape as char))
-                )?,
-            };
-            start = i + 1;
-        }
-        if start != bytes.len() {
-            write!(w, "{}", self.color.string.paint(&value[start..]))?;
+                }
+            }
+            match &dump {
+                Some(dump) => {
+                    for (style, text) in &dump[i] {
+                        self.emit_escaped(w, text, *style)?;
+                    }
+                }
+                None => self.emit_escaped(w, line, self.color.string)?,
+            }
         }
         if self.multiline == Multiline::Hjson {
             writeln!(w)?;
@@ -591,8 +675,10 @@ impl JsonEmitter {
     fn emit_int<W: fmt::Write>(&mut self, w: &mut W, i: &Int) -> Result<()> {
         let b = i.base();
         let s = i.format(self.bases.get(&b));
+        // JSON numbers have no digit separators, so grouped digits are quoted.
         if self.strict_numeric_limits && !i.is_legal_json()
-            || self.bases.get(&b).is_some() && self.literals.get(&b).is_none()
+            || self.bases.contains(&b) && !self.literals.contains(&b)
+            || s.contains('_')
         {
             write!(
                 w,
@@ -617,7 +703,7 @@ impl JsonEmitter {
         Ok(())
     }
 
-    fn emit_indent<W: fmt::Write>(&mut self, w: &mut W) -> Result<()> {
+    pub(crate) fn emit_indent<W: fmt::Write>(&mut self, w: &mut W) -> Result<()> {
         if self.compact {
             return Ok(());
         }
@@ -630,7 +716,7 @@ impl JsonEmitter {
         Ok(())
     }
 
-    fn writeln<W: fmt::Write>(&mut self, w: &mut W, s: impl Display) -> Result<()> {
+    pub(crate) fn writeln<W: fmt::Write>(&mut self, w: &mut W, s: impl Display) -> Result<()> {
         if self.compact {
             write!(w, "{}", s)?;
         } else {
@@ -735,6 +821,28 @@ fn is_reserved_word(word: &str) -> bool {
     words.get(word).is_some()
 }
 
+// Returns whether `s` can be an HJSON quoteless string: it must not be
+// mistaken for another value, a comment or punctuation, and it must survive
+// the trimming of whitespace at either end.
+fn is_legal_quoteless(s: &str) -> bool {
+    let Some(first) = s.chars().next() else {
+        return false;
+    };
+    !first.is_whitespace()
+        && !s.ends_with(char::is_whitespace)
+        && !first.is_ascii_digit()
+        && !matches!(
+            first,
+            '{' | '}' | '[' | ']' | ',' | ':' | '"' | '\'' | '#' | '+' | '-' | '.'
+        )
+        && !s.starts_with("//")
+        && !s.starts_with("/*")
+        && !["true", "false", "null", "Infinity", "NaN"]
+            .iter()
+            .any(|word| s.starts_with(word))
+        && !s.contains(|c: char| c.is_control() || matches!(c, '\u{2028}' | '\u{2029}'))
+}
+
 fn is_legal_bareword(word: &str) -> bool {
     if word.is_empty() {
         return false;
@@ -789,6 +897,7 @@ mod tests {
     }
 
     #[test]
+    #[allow(clippy::approx_constant)]
     fn basic_document() {
         let c = comment("woohoo!").to_json();
         assert_eq!(c.to_string(), "");
@@ -984,4 +1093,57 @@ No \\n's!",
         println!("{}", map);
         assert_eq!(map.to_string(), expect);
     }
+    #[test]
+    fn quoteless_hjson() -> Result<()> {
+        let unquoted = |v: &str| Document::String(v.to_string(), StrFormat::Unquoted);
+        let map = Document::Mapping(vec![
+            kv("url", unquoted("http://example.com/a, b # c")),
+            kv("word", unquoted("trueish")),
+            kv("number", unquoted("1 apple")),
+            kv("brace", unquoted("{x}")),
+            Document::Fragment(vec![
+                string("commented"),
+                unquoted("value"),
+                comment("note"),
+            ]),
+            kv(
+                "list",
+                Document::Sequence(vec![unquoted("a: b"), unquoted(" padded"), unquoted("c")]),
+            ),
+            kv("compact", Document::Compact(unquoted("d").into())),
+            kv("last", unquoted("e")),
+        ]);
+        let text = map.clone().to_hjson().to_string();
+        assert_eq!(
+            text,
+            r#"{
+  url: http://example.com/a, b # c
+  word: "trueish",
+  number: "1 apple",
+  brace: "{x}",
+  commented: "value", # note
+  list: [
+    a: b
+    " padded",
+    c
+  ],
+  compact: "d",
+  last: e
+}"#
+        );
+        let doc = Document::from_hjson(&text)?;
+        let Document::Mapping(kvs) = &doc else {
+            panic!("expected a mapping: {doc:?}");
+        };
+        let Document::Mapping(expect) = &map else {
+            unreachable!()
+        };
+        for (parsed, original) in kvs.iter().zip(expect) {
+            let (_, v) = parsed.as_kv()?;
+            let (_, e) = original.as_kv()?;
+            let json = |d: &Document| d.clone().to_json().to_string();
+            assert_eq!(json(v.as_value()?), json(e.as_value()?));
+        }
+        Ok(())
+    }
 }
@@ -1,24 +1,43 @@
 pub mod annotate;
+mod autoschema;
 mod color;
 mod de;
+mod diff;
 mod doc_iter;
 mod document;
 mod error;
 mod hexdump;
 mod integer;
 mod json;
+mod merge;
+mod patch;
+mod query;
 mod relax;
+pub mod schema;
 mod ser;
+mod span;
+mod stream;
+mod toml;
+mod toml_parser;
+mod validate;
 mod yaml;
+mod yaml_parser;
 
 pub use annotate::Annotate;
+pub use autoschema::{AutoSchema, Shape};
 pub use color::ColorProfile;
 pub use de::{from_str, Deserialize, Deserializer};
+pub use diff::{Change, Difference};
 pub use doc_iter::DocPath;
-pub use document::{BytesFormat, CommentFormat, Document, StrFormat};
+pub use document::{BytesFormat, CommentFormat, Document, DumpLayout, StrFormat};
 pub use error::Error;
 pub use integer::{Base, Int, IntValue};
 pub use json::Json;
+pub use schema::Schema;
 pub use ser::{serialize, AnnotatedSerializer};
 pub use serde_annotate_derive::*;
+pub use span::{Segment, Span, SpanMap};
+pub use stream::{serialize_to_writer, Compound, StreamFormat, StreamSerializer};
+pub use toml::Toml;
+pub use validate::Violation;
 pub use yaml::Yaml;
@@ -0,0 +1,310 @@
+// Merging of updated documents into previously parsed documents.
+use std::collections::{HashMap, HashSet};
+
+use crate::document::Document;
+use crate::integer::Int;
+
+impl Document {
+    /// Merges `updated` into this document and returns the result.
+    ///
+    /// This is meant for round-trip editing: `self` is a document parsed
+    /// from text written by a human, and `updated` is a newly serialized
+    /// document holding the desired values.  The result holds the values of
+    /// `updated`, but wherever a node of `updated` corresponds to a node of
+    /// `self`, the presentation of the original node is kept:
+    ///
+    /// - Comments attached to mapping entries and sequence elements of
+    ///   `self` are kept and the comments of `updated` are discarded.
+    /// - Mapping keys keep the order of `self`.  Keys only present in
+    ///   `updated` are inserted after the key which precedes them in
+    ///   `updated`.  Keys not present in `updated` are removed.
+    /// - Sequence elements correspond by position.  Comments preceding a
+    ///   removed element are removed with it.
+    /// - Integers keep the base and width of `self`, strings keep their
+    ///   `StrFormat` and compact nodes remain compact.
+    ///
+    /// Nodes which have no correspondence in `self` are taken from
+    /// `updated` as-is.
+    pub fn merge(&self, updated: &Document) -> Document {
+        merge_value(self, updated)
+    }
+}
+
+// Returns the value of a node, looking through fragments and compact hints.
+fn strip(doc: &Document) -> &Document {
+    match doc {
+        Document::Compact(c) => strip(c),
+        Document::Fragment(_) => doc.as_value().map(strip).unwrap_or(doc),
+        _ => doc,
+    }
+}
+
+// Merges `updated` into `orig`.  The value of `updated` is only looked at
+// to match it with `orig`: where they don't correspond, `updated` is taken
+// with its comments and compact hints.
+fn merge_value(orig: &Document, updated: &Document) -> Document {
+    let new = strip(updated);
+    match orig {
+        Document::Compact(c) => Document::Compact(Box::new(merge_value(c, updated))),
+        Document::Fragment(f) => match f.iter().position(Document::has_value) {
+            Some(i) if orig.as_value().is_ok() => {
+                let mut f = f.clone();
+                f[i] = merge_value(&f[i], updated);
+                Document::Fragment(f)
+            }
+            _ => updated.clone(),
+        },
+        Document::Mapping(o) => match new {
+            Document::Mapping(n) => Document::Mapping(merge_mapping(o, n)),
+            _ => updated.clone(),
+        },
+        Document::Sequence(o) => match new {
+            Document::Sequence(n) => Document::Sequence(merge_sequence(o, n)),
+            _ => updated.clone(),
+        },
+        Document::Int(a) => match new {
+            Document::Int(b) if a.value() == b.value() => orig.clone(),
+            Document::Int(b) => Document::Int(
+                Int::new_with_padding(b.value().clone(), a.base(), a.width()).with_group(a.group()),
+            ),
+            _ => updated.clone(),
+        },
+        Document::String(_, f) | Document::StaticStr(_, f) => match new {
+            Document::String(s, _) => Document::String(s.clone(), *f),
+            Document::StaticStr(s, _) => Document::StaticStr(s, *f),
+            _ => updated.clone(),
+        },
+        _ => updated.clone(),
+    }
+}
+
+// Returns a string used to match the keys of mapping entries.
+fn key_of(entry: &Document) -> Option<String> {
+    let (k, _) = entry.as_kv().ok()?;
+    match strip(k) {
+        Document::String(s, _) => Some(s.clone()),
+        Document::StaticStr(s, _) => Some(s.to_string()),
+        Document::Int(i) => Some(i.format(None)),
+        Document::Boolean(b) => Some(b.to_string()),
+        _ => None,
+    }
+}
+
+// Replaces the value of the mapping entry `orig` with its merge with
+// the value of `new`.
+fn merge_entry(orig: &Document, new: &Document) -> Document {
+    let (Document::Fragment(f), Ok((_, nv))) = (orig, new.as_kv()) else {
+        return new.clone();
+    };
+    let mut f = f.clone();
+    if let Some(i) = f.iter().rposition(Document::has_value) {
+        f[i] = merge_value(&f[i], nv);
+    }
+    Document::Fragment(f)
+}
+
+fn merge_mapping(orig: &[Document], new: &[Document]) -> Vec<Document> {
+    let okeys = orig.iter().filter_map(key_of).collect::<HashSet<_>>();
+    let mut updated = HashMap::new();
+    // New entries which don't exist in the original, grouped by the
+    // original key they follow.
+    let mut inserts = HashMap::<Option<String>, Vec<&Document>>::new();
+    let mut prev = None;
+    for entry in new.iter().filter(|e| e.has_value()) {
+        match key_of(entry) {
+            Some(k) if okeys.contains(&k) => {
+                updated.insert(k.clone(), entry);
+                prev = Some(k);
+            }
+            _ => inserts.entry(prev.clone()).or_default().push(entry),
+        }
+    }
+
+    let mut result = Vec::new();
+    let mut leading = inserts.remove(&None);
+    for entry in orig {
+        if !entry.has_value() {
+            result.push(entry.clone());
+            continue;
+        }
+        if let Some(leading) = leading.take() {
+            result.extend(leading.into_iter().cloned());
+        }
+        let Some(k) = key_of(entry) else {
+            continue;
+        };
+        if let Some(n) = updated.get(&k) {
+            result.push(merge_entry(entry, n));
+        }
+        if let Some(after) = inserts.remove(&Some(k)) {
+            result.extend(after.into_iter().cloned());
+        }
+    }
+    if let Some(leading) = leading {
+        result.extend(leading.into_iter().cloned());
+    }
+    result
+}
+
+fn merge_sequence(orig: &[Document], new: &[Document]) -> Vec<Document> {
+    let mut new = new.iter().filter(|e| e.has_value());
+    let mut result = Vec::new();
+    // Comments preceding an element belong to it and are removed with it.
+    let mut comments = Vec::new();
+    for elem in orig {
+        if !elem.has_value() {
+            comments.push(elem.clone());
+        } else if let Some(n) = new.next() {
+            result.append(&mut comments);
+            result.push(merge_value(elem, n));
+        } else {
+            comments.clear();
+        }
+    }
+    result.append(&mut comments);
+    result.extend(new.cloned());
+    result
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::document::CommentFormat;
+    use crate::ser::serialize;
+    use anyhow::Result;
+    use serde::{Deserialize, Serialize};
+
+    #[derive(Debug, Serialize, Deserialize)]
+    struct Reg {
+        name: String,
+        addr: u32,
+    }
+
+    #[derive(Debug, Serialize, Deserialize)]
+    struct Config {
+        regs: Vec<Reg>,
+        version: u32,
+        #[serde(skip_serializing_if = "Option::is_none")]
+        debug: Option<bool>,
+        #[serde(skip_serializing_if = "Option::is_none")]
+        label: Option<String>,
+    }
+
+    const ORIGINAL: &str = r#"{
+  // The regs come first.
+  regs: [
+    // The boot ROM.
+    {
+      addr: 0x0000,
+      name: 'rom'
+    },
+    {
+      addr: 0x1000,
+      name: 'ram' // Main memory.
+    }
+  ],
+  version: 0x02,
+  /* Remove this when done. */
+  debug: true
+}"#;
+
+    #[test]
+    fn test_merge_keeps_presentation() -> Result<()> {
+        let orig = Document::parse(ORIGINAL)?;
+        let mut config = crate::from_str::<Config>(ORIGINAL)?;
+        config.version = 3;
+        config.regs[1].addr = 0x2000;
+        config.regs.push(Reg {
+            name: "mmio".into(),
+            addr: 0x4000,
+        });
+        config.debug = None;
+        config.label = Some("soc".into());
+
+        let doc = orig.merge(&serialize(&config)?);
+        let text = doc.to_json5().to_string();
+        assert_eq!(
+            text,
+            r#"{
+  // The regs come first.
+  regs: [
+    // The boot ROM.
+    {
+      addr: 0x0000,
+      name: "rom"
+    },
+    {
+      addr: 0x2000,
+      name: "ram" // Main memory.
+    },
+    {
+      name: "mmio",
+      addr: 16384
+    }
+  ],
+  version: 0x03,
+  label: "soc"
+}"#
+        );
+        Ok(())
+    }
+
+    #[test]
+    fn test_merge_unchanged() -> Result<()> {
+        let orig = Document::parse(ORIGINAL)?;
+        let config = crate::from_str::<Config>(ORIGINAL)?;
+        let doc = orig.merge(&serialize(&config)?);
+        assert_eq!(doc.to_json5().to_string(), orig.to_json5().to_string());
+        Ok(())
+    }
+
+    #[test]
+    fn test_merge_leading_insert() -> Result<()> {
+        let orig = Document::parse("{b: 1}")?;
+        let new = Document::parse("{a: 0, b: 0x2}")?;
+        let doc = orig.merge(&new);
+        assert_eq!(doc.to_json5().to_string(), "{\n  a: 0,\n  b: 2\n}");
+        Ok(())
+    }
+    #[test]
+    fn test_merge_removed_elements() -> Result<()> {
+        let comment = |c: &str| Document::Comment(c.into(), CommentFormat::Standard);
+        let orig = Document::Sequence(vec![
+            Document::from("a"),
+            comment("About b."),
+            Document::from("b"),
+            comment("About c."),
+            Document::from("c"),
+        ]);
+        let new = Document::Sequence(vec![Document::from("x"), Document::from("y")]);
+        let expect = Document::Sequence(vec![
+            Document::from("x"),
+            comment("About b."),
+            Document::from("y"),
+        ]);
+        let doc = orig.merge(&new);
+        assert_eq!(format!("{:?}", doc), format!("{:?}", expect));
+        Ok(())
+    }
+
+    #[test]
+    fn test_merge_keeps_new_compact() -> Result<()> {
+        let orig = Document::parse("{a: 1, b: 'x'}")?;
+        let new = Document::Mapping(vec![
+            Document::Fragment(vec![
+                Document::from("a"),
+                Document::Compact(Box::new(Document::parse("[1, 2]")?)),
+            ]),
+            Document::Fragment(vec![
+                Document::from("b"),
+                Document::Compact(Box::new(Document::parse("{c: 3}")?)),
+            ]),
+        ]);
+        let doc = orig.merge(&new);
+        assert_eq!(
+            doc.to_json5().to_string(),
+            "{\n  a: [1, 2],\n  b: {c: 3}\n}"
+        );
+        Ok(())
+    }
+}
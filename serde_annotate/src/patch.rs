@@ -0,0 +1,458 @@
+// JSON Merge Patch (RFC 7386) and JSON Patch (RFC 6902) on documents.
+use crate::document::{Document, StrFormat};
+use crate::error::Error;
+
+type Result<T> = std::result::Result<T, Error>;
+
+fn patch_error(msg: impl Into<String>) -> Error {
+    Error::PatchError(msg.into())
+}
+
+// Returns the comments before and after the value nodes of an entry.
+fn split_comments(entry: &Document) -> (Vec<Document>, Vec<Document>) {
+    let Document::Fragment(f) = entry else {
+        return (Vec::new(), Vec::new());
+    };
+    let first = f.iter().position(Document::has_value).unwrap_or(f.len());
+    let last = f
+        .iter()
+        .rposition(Document::has_value)
+        .map_or(first, |i| i + 1);
+    let comments = |nodes: &[Document]| {
+        nodes
+            .iter()
+            .filter(|n| n.comment().is_some())
+            .cloned()
+            .collect::<Vec<_>>()
+    };
+    (comments(&f[..first]), comments(&f[last..]))
+}
+
+// Attaches the `leading` and `trailing` comments to a mapping entry or
+// sequence element, skipping comments the entry already carries.
+fn add_comments(entry: &mut Document, leading: Vec<Document>, trailing: Vec<Document>) {
+    if leading.is_empty() && trailing.is_empty() {
+        return;
+    }
+    if !matches!(entry, Document::Fragment(_)) {
+        let value = std::mem::replace(entry, Document::Null);
+        *entry = Document::Fragment(vec![value]);
+    }
+    let Document::Fragment(f) = entry else {
+        unreachable!()
+    };
+    let present = |f: &[Document], c: &Document| f.iter().any(|n| n.comment() == c.comment());
+    let mut position = f.iter().position(Document::has_value).unwrap_or(f.len());
+    for c in leading {
+        if !present(f, &c) {
+            f.insert(position, c);
+            position += 1;
+        }
+    }
+    for c in trailing {
+        if !present(f, &c) {
+            f.push(c);
+        }
+    }
+}
+
+// Returns an entry holding `value` and the given comments.
+fn with_comments(value: Document, leading: Vec<Document>, trailing: Vec<Document>) -> Document {
+    if leading.is_empty() && trailing.is_empty() {
+        return value;
+    }
+    Document::Fragment(leading.into_iter().chain([value]).chain(trailing).collect())
+}
+
+fn find_key(mapping: &[Document], key: &Document) -> Option<usize> {
+    mapping
+        .iter()
+        .position(|e| e.as_kv().is_ok_and(|(k, _)| k.value_eq(key)))
+}
+
+impl Document {
+    /// Applies a JSON Merge Patch (RFC 7386) to this document.
+    ///
+    /// Mapping entries of `patch` are merged into this document by key and
+    /// a `null` value removes the key.  Any other value replaces the target
+    /// node.  Untouched nodes keep their comments and presentation, and
+    /// comments attached to the entries of `patch` are carried over to the
+    /// corresponding entries of this document.
+    pub fn merge_patch(&mut self, patch: &Document) {
+        let Ok(patch) = patch.as_value() else {
+            return;
+        };
+        let target = match self {
+            Document::Fragment(_) | Document::Compact(_) if self.has_value() => {
+                // Unwrap is ok: the node has a value.
+                self.as_value_mut().unwrap()
+            }
+            _ => self,
+        };
+        let Document::Mapping(entries) = patch else {
+            *target = patch.clone();
+            return;
+        };
+        if !matches!(target, Document::Mapping(_)) {
+            *target = Document::Mapping(Vec::new());
+        }
+        let Document::Mapping(mapping) = target else {
+            unreachable!()
+        };
+        for entry in entries {
+            let Ok((k, v)) = entry.as_kv() else {
+                continue;
+            };
+            let (leading, trailing) = split_comments(entry);
+            let position = find_key(mapping, k);
+            match (position, v.as_value()) {
+                (Some(i), Ok(Document::Null)) => {
+                    mapping.remove(i);
+                }
+                (None, Ok(Document::Null)) => {}
+                (Some(i), _) => {
+                    // Unwrap is ok: `find_key` only matches kvpairs.
+                    mapping[i].as_kv_mut().unwrap().1.merge_patch(v);
+                    add_comments(&mut mapping[i], leading, trailing);
+                }
+                (None, _) => {
+                    let mut value = Document::Null;
+                    value.merge_patch(v);
+                    mapping.push(Document::Fragment(
+                        leading
+                            .into_iter()
+                            .chain([k.clone(), value])
+                            .chain(trailing)
+                            .collect(),
+                    ));
+                }
+            }
+        }
+    }
+
+    /// Applies a JSON Patch (RFC 6902) to this document.
+    ///
+    /// The patch is a sequence of `add`, `remove`, `replace`, `move`,
+    /// `copy` and `test` operations.  The operations are applied atomically:
+    /// if any of them fails, the document is left unchanged.  Comments
+    /// attached to an operation (or to its `value`) are carried over to the
+    /// entry it adds or replaces.
+    pub fn patch(&mut self, patch: &Document) -> Result<()> {
+        let Document::Sequence(ops) = patch.as_value()? else {
+            return Err(patch_error("a patch must be a sequence of operations"));
+        };
+        let mut doc = self.clone();
+        for op in ops.iter().filter(|op| op.has_value()) {
+            doc.apply(op)?;
+        }
+        *self = doc;
+        Ok(())
+    }
+
+    fn apply(&mut self, op: &Document) -> Result<()> {
+        let (mut leading, mut trailing) = split_comments(op);
+        let Document::Mapping(fields) = op.as_value()? else {
+            return Err(patch_error("an operation must be a mapping"));
+        };
+        let field = |name: &str| {
+            fields.iter().find_map(|e| match e.as_kv() {
+                Ok((k, v)) if k.as_str().ok() == Some(name) => Some((e, v)),
+                _ => None,
+            })
+        };
+        let member = |name: &str| {
+            field(name).ok_or_else(|| patch_error(format!("operation is missing `{}`", name)))
+        };
+        let name = member("op")?.1.as_str()?;
+        let path = member("path")?.1.as_str()?;
+        let mut value = || -> Result<Document> {
+            let (entry, value) = member("value")?;
+            let (l, t) = split_comments(entry);
+            leading.extend(l);
+            trailing.extend(t);
+            Ok(value.as_value()?.clone())
+        };
+        match name {
+            "add" => {
+                let value = value()?;
+                self.add(path, value, leading, trailing, false)
+            }
+            "remove" => self.remove(path).map(|_| ()),
+            "replace" => {
+                let value = value()?;
+                if self.pointer(path).is_none() {
+                    return Err(patch_error(format!("no value at `{}`", path)));
+                }
+                self.add(path, value, leading, trailing, true)
+            }
+            "move" => {
+                let from = member("from")?.1.as_str()?;
+                if path.starts_with(from) && path[from.len()..].starts_with('/') {
+                    return Err(patch_error(format!(
+                        "cannot move `{}` into its own child `{}`",
+                        from, path
+                    )));
+                }
+                let (entry, value) = self.remove(from)?;
+                let (l, t) = split_comments(&entry);
+                self.add(path, value, l, t, false)
+            }
+            "copy" => {
+                let from = member("from")?.1.as_str()?;
+                let value = self
+                    .pointer(from)
+                    .ok_or_else(|| patch_error(format!("no value at `{}`", from)))?
+                    .clone();
+                self.add(path, value, leading, trailing, false)
+            }
+            "test" => {
+                let value = value()?;
+                match self.pointer(path) {
+                    Some(v) if v.value_eq(&value) => Ok(()),
+                    _ => Err(patch_error(format!("test of `{}` failed", path))),
+                }
+            }
+            _ => Err(patch_error(format!("unknown operation `{}`", name))),
+        }
+    }
+
+    // Splits a JSON Pointer into the container node it refers into and the
+    // unescaped final reference token.
+    fn parent_mut(&mut self, path: &str) -> Result<(&mut Document, String)> {
+        let (parent, token) = path
+            .rsplit_once('/')
+            .ok_or_else(|| patch_error(format!("invalid path `{}`", path)))?;
+        let container = self
+            .pointer_mut(parent)
+            .ok_or_else(|| patch_error(format!("no value at `{}`", parent)))?;
+        Ok((container, token.replace("~1", "/").replace("~0", "~")))
+    }
+
+    // Adds `value` at `path`, replacing the value of an existing mapping
+    // entry.  Sequence elements are inserted, or replaced if `replace` is
+    // set.
+    fn add(
+        &mut self,
+        path: &str,
+        value: Document,
+        leading: Vec<Document>,
+        trailing: Vec<Document>,
+        replace: bool,
+    ) -> Result<()> {
+        if path.is_empty() {
+            *self = with_comments(value, leading, trailing);
+            return Ok(());
+        }
+        let (container, token) = self.parent_mut(path)?;
+        match container {
+            Document::Mapping(m) => {
+                let key = Document::String(token, StrFormat::Standard);
+                match find_key(m, &key) {
+                    Some(i) => {
+                        // Unwrap is ok: `find_key` only matches kvpairs.
+                        *m[i].as_kv_mut().unwrap().1 = value;
+                        add_comments(&mut m[i], leading, trailing);
+                    }
+                    None => m.push(Document::Fragment(
+                        leading
+                            .into_iter()
+                            .chain([key, value])
+                            .chain(trailing)
+                            .collect(),
+                    )),
+                }
+            }
+            Document::Sequence(s) => {
+                let len = s.iter().filter(|v| v.has_value()).count();
+                let index = match token.as_str() {
+                    "-" => len,
+                    t => index(t)
+                        .filter(|i| *i <= len)
+                        .ok_or_else(|| patch_error(format!("index out of bounds at `{}`", path)))?,
+                };
+                let position = position(s, index);
+                if replace {
+                    *s[position].as_value_mut()? = value;
+                    add_comments(&mut s[position], leading, trailing);
+                } else {
+                    s.insert(position, with_comments(value, leading, trailing));
+                }
+            }
+            _ => return Err(patch_error(format!("no container at `{}`", path))),
+        }
+        Ok(())
+    }
+
+    // Removes the node at `path`, returning its entry and its value.
+    fn remove(&mut self, path: &str) -> Result<(Document, Document)> {
+        if path.is_empty() {
+            return Err(patch_error("cannot remove the root"));
+        }
+        let missing = || patch_error(format!("no value at `{}`", path));
+        let (container, token) = self.parent_mut(path)?;
+        match container {
+            Document::Mapping(m) => {
+                let key = Document::String(token, StrFormat::Standard);
+                let entry = m.remove(find_key(m, &key).ok_or_else(missing)?);
+                // Unwrap is ok: `find_key` only matches kvpairs.
+                let value = entry.as_kv().unwrap().1.as_value()?.clone();
+                Ok((entry, value))
+            }
+            Document::Sequence(s) => {
+                let len = s.iter().filter(|v| v.has_value()).count();
+                let index = index(&token).filter(|i| *i < len).ok_or_else(missing)?;
+                let entry = s.remove(position(s, index));
+                let value = entry.as_value()?.clone();
+                Ok((entry, value))
+            }
+            _ => Err(missing()),
+        }
+    }
+}
+
+// Parses a sequence index reference token.
+fn index(token: &str) -> Option<usize> {
+    if token.is_empty()
+        || !token.bytes().all(|b| b.is_ascii_digit())
+        || (token.len() > 1 && token.starts_with('0'))
+    {
+        return None;
+    }
+    token.parse().ok()
+}
+
+// Returns the position in `sequence` of the value element at `index`.
+fn position(sequence: &[Document], index: usize) -> usize {
+    sequence
+        .iter()
+        .enumerate()
+        .filter(|(_, v)| v.has_value())
+        .nth(index)
+        .map_or(sequence.len(), |(i, _)| i)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use anyhow::Result;
+
+    const BASE: &str = r#"{
+  // Clock configuration.
+  clock: {
+    freq: 0x1000,
+    // Divider for the peripheral bus.
+    div: 2,
+  },
+  regs: [
+    "ctrl", // Control register.
+    "status",
+  ],
+  debug: true,
+}"#;
+
+    #[test]
+    fn test_merge_patch() -> Result<()> {
+        let mut doc = Document::parse(BASE)?;
+        let patch = Document::parse(
+            r#"{
+  clock: {
+    // The fast board.
+    freq: 0x4000,
+    div: null,
+  },
+  debug: null,
+  // Board revision.
+  rev: {major: 2, minor: null},
+}"#,
+        )?;
+        doc.merge_patch(&patch);
+        assert_eq!(
+            doc.to_json5().to_string(),
+            r#"{
+  // Clock configuration.
+  clock: {
+    // The fast board.
+    freq: 0x4000
+  },
+  regs: [
+    "ctrl", // Control register.
+    "status"
+  ],
+  // Board revision.
+  rev: {
+    major: 2
+  }
+}"#
+        );
+
+        let mut doc = Document::parse("{a: 1}")?;
+        doc.merge_patch(&Document::parse("[1, 2]")?);
+        assert_eq!(doc.to_json().compact(true).to_string(), "[1, 2]");
+        Ok(())
+    }
+
+    #[test]
+    fn test_patch() -> Result<()> {
+        let mut doc = Document::parse(BASE)?;
+        let patch = Document::parse(
+            r#"[
+  {op: "test", path: "/clock/div", value: 2},
+  // The fast board.
+  {op: "replace", path: "/clock/freq", value: 0x4000},
+  {op: "add", path: "/regs/1", value: "mode"},
+  {op: "replace", path: "/regs/0", value: "CTRL"},
+  {op: "add", path: "/regs/-", value: 0x10}, // Scratch register.
+  {op: "remove", path: "/debug"},
+  {op: "move", from: "/clock/div", path: "/div"},
+  {op: "copy", from: "/regs/0", path: "/first"},
+]"#,
+        )?;
+        doc.patch(&patch)?;
+        assert_eq!(
+            doc.to_json5().to_string(),
+            r#"{
+  // Clock configuration.
+  clock: {
+    // The fast board.
+    freq: 0x4000
+  },
+  regs: [
+    "CTRL", // Control register.
+    "mode",
+    "status",
+    0x10 // Scratch register.
+  ],
+  // Divider for the peripheral bus.
+  div: 2,
+  first: "CTRL"
+}"#
+        );
+        Ok(())
+    }
+
+    #[test]
+    fn test_patch_atomic() -> Result<()> {
+        let mut doc = Document::parse(BASE)?;
+        let before = doc.clone().to_json5().to_string();
+        let patch = Document::parse(
+            r#"[
+  {op: "remove", path: "/debug"},
+  {op: "test", path: "/clock/div", value: 3},
+]"#,
+        )?;
+        let err = doc.patch(&patch).unwrap_err();
+        assert_eq!(err.to_string(), "patch error: test of `/clock/div` failed");
+        assert_eq!(doc.clone().to_json5().to_string(), before);
+
+        for op in [
+            r#"[{op: "remove", path: "/regs/2"}]"#,
+            r#"[{op: "add", path: "/regs/01", value: 1}]"#,
+            r#"[{op: "add", path: "/nope/x", value: 1}]"#,
+            r#"[{op: "move", from: "/clock", path: "/clock/inner"}]"#,
+            r#"[{op: "frob", path: "/debug"}]"#,
+        ] {
+            assert!(doc.patch(&Document::parse(op)?).is_err(), "{}", op);
+        }
+        Ok(())
+    }
+}
@@ -0,0 +1,390 @@
+// JSON Pointer and JSONPath queries over documents.
+use crate::document::Document;
+use crate::error::Error;
+
+// One step of a JSONPath query.
+#[derive(Clone, Debug)]
+enum Step {
+    // A mapping key (`.name` or `['name']`).
+    Name(String),
+    // A sequence index (`[3]`), negative indices count from the end.
+    Index(isize),
+    // All children (`.*` or `[*]`).
+    Wildcard,
+    // A step applied at any depth (`..name`, `..*`, `..[3]`).
+    Descendant(Box<Step>),
+}
+
+fn query_error(path: &str, msg: &str) -> Error {
+    Error::QueryError(format!("`{}`: {}", path, msg))
+}
+
+fn parse_bracket(path: &str, text: &str) -> Result<(Step, usize), Error> {
+    let close = text
+        .find(']')
+        .ok_or_else(|| query_error(path, "unterminated `[`"))?;
+    let inner = text[1..close].trim();
+    let step = if inner == "*" {
+        Step::Wildcard
+    } else if let Some(name) = inner
+        .strip_prefix('\'')
+        .and_then(|s| s.strip_suffix('\''))
+        .or_else(|| inner.strip_prefix('"').and_then(|s| s.strip_suffix('"')))
+    {
+        Step::Name(name.to_string())
+    } else {
+        let index = inner
+            .parse::<isize>()
+            .map_err(|_| query_error(path, "expected an index, a quoted name or `*`"))?;
+        Step::Index(index)
+    };
+    Ok((step, close + 1))
+}
+
+fn parse_name(path: &str, text: &str) -> Result<(Step, usize), Error> {
+    let end = text.find(['.', '[']).unwrap_or(text.len());
+    match &text[..end] {
+        "" => Err(query_error(path, "expected a name")),
+        "*" => Ok((Step::Wildcard, end)),
+        name => Ok((Step::Name(name.to_string()), end)),
+    }
+}
+
+fn parse_path(path: &str) -> Result<Vec<Step>, Error> {
+    let mut text = path
+        .trim()
+        .strip_prefix('$')
+        .ok_or_else(|| query_error(path, "a path must start with `$`"))?;
+    let mut steps = Vec::new();
+    while !text.is_empty() {
+        let (step, len) = if let Some(rest) = text.strip_prefix("..") {
+            let (step, len) = if rest.starts_with('[') {
+                parse_bracket(path, rest)?
+            } else {
+                parse_name(path, rest)?
+            };
+            (Step::Descendant(Box::new(step)), len + 2)
+        } else if let Some(rest) = text.strip_prefix('.') {
+            let (step, len) = parse_name(path, rest)?;
+            (step, len + 1)
+        } else if text.starts_with('[') {
+            parse_bracket(path, text)?
+        } else {
+            return Err(query_error(path, "expected `.` or `[`"));
+        };
+        steps.push(step);
+        text = &text[len..];
+    }
+    Ok(steps)
+}
+
+// Returns the position, key and value of each child of a container node.
+// The position is the index of the child in the container's node list, which
+// may also hold comments.
+fn children(doc: &Document) -> Vec<(usize, Option<&str>, &Document)> {
+    match doc {
+        Document::Mapping(m) => m
+            .iter()
+            .enumerate()
+            .filter_map(|(i, kv)| {
+                let (k, v) = kv.as_kv().ok()?;
+                Some((i, k.as_str().ok(), v.as_value().ok()?))
+            })
+            .collect(),
+        Document::Sequence(s) => s
+            .iter()
+            .enumerate()
+            .filter_map(|(i, v)| Some((i, None, v.as_value().ok()?)))
+            .collect(),
+        _ => Vec::new(),
+    }
+}
+
+// Returns the positions of the children of `doc` selected by `step`.
+fn select_step(doc: &Document, step: &Step) -> Vec<usize> {
+    let children = children(doc);
+    match step {
+        Step::Name(n) => children
+            .iter()
+            .filter(|(_, k, _)| *k == Some(n.as_str()))
+            .map(|(i, _, _)| *i)
+            .collect(),
+        Step::Index(i) if matches!(doc, Document::Sequence(_)) => {
+            let i = if *i < 0 {
+                children.len() as isize + i
+            } else {
+                *i
+            };
+            usize::try_from(i)
+                .ok()
+                .and_then(|i| children.get(i))
+                .map(|(i, _, _)| vec![*i])
+                .unwrap_or_default()
+        }
+        Step::Index(_) => Vec::new(),
+        Step::Wildcard => children.iter().map(|(i, _, _)| *i).collect(),
+        Step::Descendant(_) => unreachable!(),
+    }
+}
+
+// Returns the value of the child at position `i` of a container node.
+fn child(doc: &Document, i: usize) -> Option<&Document> {
+    match doc {
+        Document::Mapping(m) => m[i].as_kv().ok()?.1.as_value().ok(),
+        Document::Sequence(s) => s[i].as_value().ok(),
+        _ => None,
+    }
+}
+
+// Appends the positional paths of the nodes selected by `steps` to `out`.
+fn select_paths(
+    doc: &Document,
+    steps: &[Step],
+    prefix: &mut Vec<usize>,
+    out: &mut Vec<Vec<usize>>,
+) {
+    let Some((step, rest)) = steps.split_first() else {
+        out.push(prefix.clone());
+        return;
+    };
+    let (step, recurse) = match step {
+        Step::Descendant(step) => (step.as_ref(), true),
+        _ => (step, false),
+    };
+    for i in select_step(doc, step) {
+        prefix.push(i);
+        select_paths(child(doc, i).unwrap(), rest, prefix, out);
+        prefix.pop();
+    }
+    if recurse {
+        for (i, _, v) in children(doc) {
+            prefix.push(i);
+            select_paths(v, steps, prefix, out);
+            prefix.pop();
+        }
+    }
+}
+
+// Collects mutable references to the nodes at the sorted, non-overlapping
+// positional `paths` relative to `doc`.
+fn resolve_mut<'a>(doc: &'a mut Document, paths: &[&[usize]], out: &mut Vec<&'a mut Document>) {
+    if paths.first().is_some_and(|p| p.is_empty()) {
+        out.push(doc);
+        return;
+    }
+    let (items, mapping) = match doc {
+        Document::Mapping(m) => (m, true),
+        Document::Sequence(s) => (s, false),
+        _ => return,
+    };
+    let mut paths = paths;
+    for (i, item) in items.iter_mut().enumerate() {
+        let n = paths.iter().take_while(|p| p[0] == i).count();
+        if n == 0 {
+            continue;
+        }
+        let sub = paths[..n].iter().map(|p| &p[1..]).collect::<Vec<_>>();
+        paths = &paths[n..];
+        let item = if mapping {
+            item.as_kv_mut().ok().map(|(_, v)| v)
+        } else {
+            Some(item)
+        };
+        if let Some(v) = item.and_then(|d| d.as_value_mut().ok()) {
+            resolve_mut(v, &sub, out);
+        }
+    }
+}
+
+fn pointer_tokens(pointer: &str) -> Option<Vec<String>> {
+    if pointer.is_empty() {
+        return Some(Vec::new());
+    }
+    let pointer = pointer.strip_prefix('/')?;
+    Some(
+        pointer
+            .split('/')
+            .map(|t| t.replace("~1", "/").replace("~0", "~"))
+            .collect(),
+    )
+}
+
+// Returns the position of the child of `doc` named by a pointer token.
+fn pointer_step(doc: &Document, token: &str) -> Option<usize> {
+    let step = match doc {
+        Document::Sequence(_) => {
+            // Array indices are plain decimal numbers without leading zeros.
+            if !token.bytes().all(|b| b.is_ascii_digit())
+                || token.len() > 1 && token.starts_with('0')
+            {
+                return None;
+            }
+            Step::Index(token.parse().ok()?)
+        }
+        _ => Step::Name(token.to_string()),
+    };
+    select_step(doc, &step).first().copied()
+}
+
+impl Document {
+    /// Looks up a value node by a JSON Pointer (RFC 6901), such as
+    /// `/regs/3/addr`.
+    ///
+    /// Comments are skipped when indexing into sequences and the result is
+    /// unwrapped from any `Fragment` or `Compact` nodes.  Returns `None` if
+    /// the pointer is malformed or there is no node at the pointer.
+    pub fn pointer(&self, pointer: &str) -> Option<&Document> {
+        let mut node = self.as_value().ok()?;
+        for token in pointer_tokens(pointer)? {
+            let i = pointer_step(node, &token)?;
+            node = child(node, i)?;
+        }
+        Some(node)
+    }
+
+    /// Looks up a mutable value node by a JSON Pointer (RFC 6901).
+    pub fn pointer_mut(&mut self, pointer: &str) -> Option<&mut Document> {
+        let mut path = Vec::new();
+        let mut node = self.as_value().ok()?;
+        for token in pointer_tokens(pointer)? {
+            let i = pointer_step(node, &token)?;
+            path.push(i);
+            node = child(node, i)?;
+        }
+        let mut out = Vec::new();
+        resolve_mut(self.as_value_mut().ok()?, &[&path], &mut out);
+        out.pop()
+    }
+
+    /// Selects the value nodes matching a JSONPath-like query, such as
+    /// `$.regs[*].addr`.
+    ///
+    /// The query starts with `$` (the root) followed by any number of
+    /// `.name` or `['name']` (a mapping key), `[3]` or `[-1]` (a sequence
+    /// index), `.*` or `[*]` (every child) and `..` (recursive descent)
+    /// segments.  The matches are returned in document order.
+    pub fn select(&self, query: &str) -> Result<Vec<&Document>, Error> {
+        let steps = parse_path(query)?;
+        let mut paths = Vec::new();
+        if let Ok(root) = self.as_value() {
+            select_paths(root, &steps, &mut Vec::new(), &mut paths);
+        }
+        paths.sort();
+        paths.dedup();
+        Ok(paths
+            .iter()
+            .filter_map(|path| {
+                path.iter()
+                    .try_fold(self.as_value().ok()?, |node, &i| child(node, i))
+            })
+            .collect())
+    }
+
+    /// Selects mutable value nodes matching a JSONPath-like query.
+    ///
+    /// Returns an error if one match is nested within another, since both
+    /// cannot be borrowed mutably at the same time.
+    pub fn select_mut(&mut self, query: &str) -> Result<Vec<&mut Document>, Error> {
+        let steps = parse_path(query)?;
+        let mut paths = Vec::new();
+        if let Ok(root) = self.as_value() {
+            select_paths(root, &steps, &mut Vec::new(), &mut paths);
+        }
+        paths.sort();
+        paths.dedup();
+        if paths.windows(2).any(|w| w[1].starts_with(&w[0])) {
+            return Err(query_error(query, "matches overlap"));
+        }
+        let mut out = Vec::new();
+        if let Ok(root) = self.as_value_mut() {
+            let paths = paths.iter().map(Vec::as_slice).collect::<Vec<_>>();
+            resolve_mut(root, &paths, &mut out);
+        }
+        Ok(out)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use anyhow::Result;
+
+    const DOC: &str = r#"{
+  // The register map.
+  regs: [
+    // The boot ROM.
+    {name: "rom", addr: 0x0000},
+    {name: "ram", addr: 0x1000},
+    {name: "a/b", addr: 0x2000, sub: {addr: 0x2004}},
+  ],
+  "a~b": true,
+}"#;
+
+    fn ints(docs: &[&Document]) -> Vec<u64> {
+        docs.iter().map(|d| u64::try_from(*d).unwrap()).collect()
+    }
+
+    #[test]
+    fn test_pointer() -> Result<()> {
+        let doc = Document::parse(DOC)?;
+        assert!(matches!(doc.pointer(""), Some(Document::Mapping(_))));
+        assert_eq!(
+            doc.pointer("/regs/1/name").map(|d| d.as_str().ok()),
+            Some(Some("ram"))
+        );
+        assert_eq!(
+            ints(&[doc.pointer("/regs/2/sub/addr").unwrap()]),
+            vec![0x2004]
+        );
+        assert!(doc.pointer("/a~0b").is_some());
+        assert!(doc.pointer("/regs/3").is_none());
+        assert!(doc.pointer("/regs/01").is_none());
+        assert!(doc.pointer("regs").is_none());
+        Ok(())
+    }
+
+    #[test]
+    fn test_pointer_mut() -> Result<()> {
+        let mut doc = Document::parse(DOC)?;
+        *doc.pointer_mut("/regs/0/addr").unwrap() = Document::Int(0x8000u32.into());
+        assert_eq!(
+            doc.select("$.regs[0].addr").map(|v| ints(&v))?,
+            vec![0x8000]
+        );
+        // The comment above the element is retained.
+        assert!(doc.to_json5().to_string().contains("// The boot ROM."));
+        Ok(())
+    }
+
+    #[test]
+    fn test_select() -> Result<()> {
+        let doc = Document::parse(DOC)?;
+        assert_eq!(
+            ints(&doc.select("$.regs[*].addr")?),
+            vec![0, 0x1000, 0x2000]
+        );
+        assert_eq!(ints(&doc.select("$['regs'][-1].addr")?), vec![0x2000]);
+        assert_eq!(
+            ints(&doc.select("$..addr")?),
+            vec![0, 0x1000, 0x2000, 0x2004]
+        );
+        assert_eq!(doc.select("$.regs.*")?.len(), 3);
+        assert_eq!(doc.select("$.regs.name")?.len(), 0);
+        assert!(doc.select("regs").is_err());
+        assert!(doc.select("$.regs[").is_err());
+        assert!(doc.select("$.regs[?(@.addr)]").is_err());
+        Ok(())
+    }
+
+    #[test]
+    fn test_select_mut() -> Result<()> {
+        let mut doc = Document::parse(DOC)?;
+        for name in doc.select_mut("$.regs[*].name")? {
+            *name = Document::from("reg");
+        }
+        let names = doc.select("$.regs[*].name")?;
+        assert!(names.iter().all(|n| n.as_str().ok() == Some("reg")));
+        assert!(doc.select_mut("$..*").is_err());
+        Ok(())
+    }
+}
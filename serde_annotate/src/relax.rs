@@ -4,14 +4,32 @@ use pest::Parser as P;
 use pest::Position;
 use pest_derive::Parser;
 use std::cell::RefCell;
+use std::ops::Range;
 
 use crate::document::{CommentFormat, Document, StrFormat};
 use crate::error::Error;
 use crate::integer::Int;
+use crate::span::{PathTree, Segment, Span, SpanMap};
+
+// The locations in the source text of the parsed strings which appear
+// there verbatim, by the object-path of the string or of the mapping entry
+// it is the key of.  A deserializer uses these to lend strings from the
+// source text.
+pub(crate) type Verbatim = PathTree<Range<usize>>;
 
 #[derive(Default)]
 struct Inner {
     lines: Vec<usize>,
+    // The nodes of the enclosing values of the value currently being
+    // parsed.  At most one of `spans` and `verbatim` is recorded, so the
+    // nodes are those of the one being recorded.
+    path: Vec<usize>,
+    // The locations of the parsed values, if requested.
+    spans: Option<SpanMap>,
+    // The locations of the verbatim strings, if requested.
+    verbatim: Option<Verbatim>,
+    // The location of the most recently parsed verbatim string.
+    last: Option<Range<usize>>,
 }
 
 /// `Relax` is a permissive JSON parser that permits many common extensions to
@@ -31,6 +49,7 @@ pub struct Relax {
     pub number_oct: bool,
     pub number_plus: bool,
     pub number_lax_dec_point: bool,
+    pub number_underscore: bool,
     pub string_single_quote: bool,
     pub string_unquoted: bool,
     pub string_ident: bool,
@@ -55,6 +74,7 @@ impl Default for Relax {
             number_oct: true,
             number_plus: true,
             number_lax_dec_point: true,
+            number_underscore: true,
             string_single_quote: true,
             string_unquoted: true,
             string_ident: true,
@@ -78,6 +98,7 @@ impl Relax {
             number_oct: false,
             number_plus: false,
             number_lax_dec_point: false,
+            number_underscore: false,
             string_single_quote: false,
             string_unquoted: false,
             string_ident: false,
@@ -99,6 +120,7 @@ impl Relax {
             comment_hash: false,
             number_bin: false,
             number_oct: false,
+            number_underscore: false,
             ..Self::default()
         }
     }
@@ -112,17 +134,48 @@ impl Relax {
             number_oct: false,
             number_plus: false,
             number_lax_dec_point: false,
+            number_underscore: false,
             ..Self::default()
         }
     }
 
     /// Parses a string into a `Document`.
+    #[allow(clippy::wrong_self_convention)]
     pub fn from_str(&self, text: &str) -> Result<Document, Error> {
+        self.parse_text(text, Inner::default())
+    }
+
+    /// Parses a string into a `Document`, also returning the location
+    /// of every value node in the document.
+    #[allow(clippy::wrong_self_convention)]
+    pub fn from_str_with_spans(&self, text: &str) -> Result<(Document, SpanMap), Error> {
+        let inner = Inner {
+            spans: Some(SpanMap::default()),
+            ..Default::default()
+        };
+        let doc = self.parse_text(text, inner)?;
+        let spans = self.inner.borrow_mut().spans.take().unwrap_or_default();
+        Ok((doc, spans))
+    }
+
+    // Parses a string into a `Document`, also returning the locations of
+    // the strings which appear verbatim in `text`.
+    #[allow(clippy::wrong_self_convention)]
+    pub(crate) fn from_str_verbatim(&self, text: &str) -> Result<(Document, Verbatim), Error> {
+        let inner = Inner {
+            verbatim: Some(Verbatim::default()),
+            ..Default::default()
+        };
+        let doc = self.parse_text(text, inner)?;
+        let verbatim = self.inner.borrow_mut().verbatim.take().unwrap_or_default();
+        Ok((doc, verbatim))
+    }
+
+    fn parse_text(&self, text: &str, mut inner: Inner) -> Result<Document, Error> {
         // Iterate over the input text and remember the line breaks. Since we use
         // positioning information to infer which comments belong with which json
         // items, caching the line-number information speeds up parsing
         // quite a bit.
-        let mut inner = Inner::default();
         inner.lines.push(0);
         for (i, ch) in text.char_indices() {
             if ch == '\n' {
@@ -145,6 +198,106 @@ impl Relax {
         (line, col)
     }
 
+    // Returns the location of `pair` in the source text.
+    fn span(&self, pair: &Pair<Rule>) -> Span {
+        let span = pair.as_span();
+        let (line, col) = self.line_col(span.start());
+        // `line_col` reports the byte offset of the position from the
+        // preceding newline; the column is reported in characters.
+        let offset = if line == 0 { col } else { col - 1 };
+        let column = span.start_pos().line_of()[..offset].chars().count() + 1;
+        Span {
+            start: span.start(),
+            end: span.end(),
+            line: line + 1,
+            column,
+        }
+    }
+
+    // Records the location of a mapping key named `name` and, if the key
+    // appears verbatim in the source text, its text under `index`, the
+    // position of the entry in the mapping.
+    fn record_key(&self, pair: &Pair<Rule>, name: &str, index: usize) {
+        let span = self.inner.borrow().spans.is_some().then(|| self.span(pair));
+        let mut inner = self.inner.borrow_mut();
+        let inner = &mut *inner;
+        let parent = inner.path.last().copied().unwrap_or(Verbatim::ROOT);
+        if let (Some(spans), Some(span)) = (inner.spans.as_mut(), span) {
+            let node = spans.tree.child(parent, Segment::Name(name.to_string()));
+            spans.tree.insert_key(node, span);
+        }
+        if let (Some(verbatim), Some(text)) = (inner.verbatim.as_mut(), inner.last.take()) {
+            let node = verbatim.child(parent, Segment::Index(index));
+            verbatim.insert_key(node, text);
+        }
+    }
+
+    // Handles a value node.  `child` is the segment naming the value in
+    // its parent and its position among the parent's values, or `None` for
+    // the root value.  Records the location of the value under the segment
+    // if spans were requested.  If verbatim strings were requested and the
+    // value is one, records its text under the position, as the entries of
+    // a mapping with duplicate keys share a segment.
+    fn handle_value(
+        &self,
+        pair: Pair<Rule>,
+        child: Option<(Segment, usize)>,
+    ) -> Result<Document, Error> {
+        let (spans, verbatim) = {
+            let inner = self.inner.borrow();
+            (inner.spans.is_some(), inner.verbatim.is_some())
+        };
+        if !spans && !verbatim {
+            return self.handle_pair(pair);
+        }
+        let span = spans.then(|| self.span(&pair));
+        {
+            let mut inner = self.inner.borrow_mut();
+            let inner = &mut *inner;
+            let parent = inner.path.last().copied().unwrap_or(Verbatim::ROOT);
+            let node = match child {
+                None => parent,
+                Some((segment, index)) => match &mut inner.spans {
+                    Some(spans) => spans.tree.child(parent, segment),
+                    // Unwrap is ok: verbatim strings are recorded if spans
+                    // aren't.
+                    None => inner
+                        .verbatim
+                        .as_mut()
+                        .unwrap()
+                        .child(parent, Segment::Index(index)),
+                },
+            };
+            if let (Some(spans), Some(span)) = (&mut inner.spans, span) {
+                spans.tree.insert(node, span);
+            }
+            inner.path.push(node);
+            inner.last = None;
+        }
+        let result = self.handle_pair(pair);
+        let mut inner = self.inner.borrow_mut();
+        let inner = &mut *inner;
+        let node = inner.path.pop().unwrap();
+        if let (Ok(Document::String(..)), Some(verbatim), Some(text)) =
+            (&result, inner.verbatim.as_mut(), inner.last.take())
+        {
+            verbatim.insert(node, text);
+        }
+        result
+    }
+
+    // Records that the string `doc` appears verbatim in the source text at
+    // offset `start`, if verbatim strings were requested.
+    fn verbatim(&self, doc: Document, start: usize) -> Document {
+        if let Document::String(s, _) = &doc {
+            let mut inner = self.inner.borrow_mut();
+            if inner.verbatim.is_some() {
+                inner.last = Some(start..start + s.len());
+            }
+        }
+        doc
+    }
+
     fn unhex(ch: char) -> u32 {
         match ch {
             '0'..='9' => (ch as u8 - b'0') as u32,
@@ -212,6 +365,11 @@ impl Relax {
         } else {
             text
         };
+        Self::syntax_error(
+            !self.number_underscore && t.contains('_'),
+            "digit separator",
+            pair.as_span().start_pos(),
+        )?;
         if t.starts_with("0x") || t.starts_with("0X") {
             // Hexadecimal integer.
             Self::syntax_error(
@@ -227,7 +385,7 @@ impl Relax {
                 "binary literal",
                 pair.as_span().start_pos(),
             )?;
-            return Self::from_str_radix(text, 2);
+            Self::from_str_radix(text, 2)
         } else if t.starts_with("0o") || t.starts_with("0O") {
             // Octal integer.
             Self::syntax_error(
@@ -235,7 +393,7 @@ impl Relax {
                 "octal literal",
                 pair.as_span().start_pos(),
             )?;
-            return Self::from_str_radix(text, 8);
+            Self::from_str_radix(text, 8)
         } else if t.contains('.')
             || t.contains('e')
             || t.contains('E')
@@ -248,16 +406,21 @@ impl Relax {
                 "bad float literal",
                 pair.as_span().start_pos(),
             )?;
-            return Ok(Document::Float(text.parse().unwrap()));
+            Ok(Document::Float(text.replace('_', "").parse().unwrap()))
         } else {
             // Decimal integer.
-            return Self::from_str_radix(text, 10);
+            Self::from_str_radix(text, 10)
         }
     }
 
-    fn handle_kvpair(&self, pairs: &mut Pairs<Rule>) -> Result<(Document, bool), Error> {
+    fn handle_kvpair(
+        &self,
+        pairs: &mut Pairs<Rule>,
+        index: usize,
+    ) -> Result<(Document, bool), Error> {
         let mut k = usize::MAX;
         let mut v = usize::MAX;
+        let mut name = None;
         let mut kv = vec![];
         let mut comma = false;
         while let Some(pair) = pairs.peek() {
@@ -287,11 +450,26 @@ impl Relax {
                 // the key, then it must be the key.
                 // Keep it.
                 k = line;
+                self.inner.borrow_mut().last = None;
+                let key = self.handle_pair(pair.clone())?;
+                let key_name = match key.as_str() {
+                    Ok(s) => s.to_string(),
+                    Err(_) => pair.as_str().to_string(),
+                };
+                self.record_key(&pair, &key_name, index);
+                name = Some(key_name);
+                kv.push(key);
+                let _ = pairs.next();
+                continue;
             } else if v == usize::MAX {
                 // If the pair isn't a comment or comma, and we haven't seen
                 // the value, then it must be the value.
                 // Keep it.
                 v = line;
+                let segment = Segment::Name(name.take().unwrap_or_default());
+                kv.push(self.handle_value(pair, Some((segment, index)))?);
+                let _ = pairs.next();
+                continue;
             } else {
                 // If the pair is a not a comment or comma and we've seen both
                 // the key and value, it must be part of the next kvpair.
@@ -305,7 +483,11 @@ impl Relax {
         Ok((Document::Fragment(kv), comma))
     }
 
-    fn handle_array_elem(&self, pairs: &mut Pairs<Rule>) -> Result<(Document, bool), Error> {
+    fn handle_array_elem(
+        &self,
+        pairs: &mut Pairs<Rule>,
+        index: usize,
+    ) -> Result<(Document, bool), Error> {
         let mut i = usize::MAX;
         let mut item = vec![];
         let mut comma = false;
@@ -337,6 +519,9 @@ impl Relax {
                 // Keep the value.
                 i = line;
                 saw_value = true;
+                item.push(self.handle_value(pair, Some((Segment::Index(index), index)))?);
+                let _ = pairs.next();
+                continue;
             } else {
                 // If the pair is a value, but we've already seen a value,
                 // its the next value.  Exit the loop.
@@ -352,7 +537,7 @@ impl Relax {
         }
     }
 
-    fn strip_leading_prefix<'a>(lines: &[&'a str], prefix: char) -> Vec<&'a str> {
+    pub(crate) fn strip_leading_prefix<'a>(lines: &[&'a str], prefix: char) -> Vec<&'a str> {
         let plen = lines.iter().fold(usize::MAX, |acc, s| {
             if s.is_empty() {
                 acc
@@ -485,14 +670,21 @@ impl Relax {
             } else {
                 StrFormat::Standard
             };
-            Ok(Document::String(Self::unescape(s)?, format))
+            let doc = Document::String(Self::unescape(s)?, format);
+            if s.contains('\\') {
+                Ok(doc)
+            } else {
+                Ok(self.verbatim(doc, pair.as_span().start() + 1))
+            }
         } else {
             Self::syntax_error(
                 !self.string_unquoted,
                 "missing quotes",
                 pair.as_span().start_pos(),
             )?;
-            Ok(Document::String(s.trim().into(), StrFormat::Unquoted))
+            let start = pair.as_span().start() + s.len() - s.trim_start().len();
+            let doc = Document::String(s.trim().into(), StrFormat::Unquoted);
+            Ok(self.verbatim(doc, start))
         }
     }
 
@@ -507,7 +699,8 @@ impl Relax {
                     "missing quotes",
                     pair.as_span().start_pos(),
                 )?;
-                Ok(Document::String(pair.as_str().into(), StrFormat::Unquoted))
+                let doc = Document::String(pair.as_str().into(), StrFormat::Unquoted);
+                Ok(self.verbatim(doc, pair.as_span().start()))
             }
             Rule::identifier => {
                 Self::syntax_error(
@@ -516,7 +709,8 @@ impl Relax {
                     pair.as_span().start_pos(),
                 )?;
                 // TODO: add StrFormat::Unquoted
-                Ok(Document::String(pair.as_str().into(), StrFormat::Unquoted))
+                let doc = Document::String(pair.as_str().into(), StrFormat::Unquoted);
+                Ok(self.verbatim(doc, pair.as_span().start()))
             }
             Rule::number => self.handle_number(pair),
             Rule::object => {
@@ -534,16 +728,16 @@ impl Relax {
                         )?;
                     }
                     npair = pairs.peek();
-                    let (node, comma) = self.handle_kvpair(&mut pairs)?;
+                    let (node, comma) = self.handle_kvpair(&mut pairs, kvs.len())?;
                     kvs.push(node);
                     saw_comma = comma;
                     need_comma = true;
                 }
-                if npair.is_some() {
+                if let Some(npair) = npair {
                     Self::syntax_error(
                         !self.comma_trailing && saw_comma,
                         "no comma expected",
-                        npair.unwrap().as_span().end_pos(),
+                        npair.as_span().end_pos(),
                     )?;
                 }
                 Ok(Document::Mapping(kvs))
@@ -564,16 +758,16 @@ impl Relax {
                     }
 
                     npair = pairs.peek();
-                    let (node, comma) = self.handle_array_elem(&mut pairs)?;
+                    let (node, comma) = self.handle_array_elem(&mut pairs, values.len())?;
                     values.push(node);
                     saw_comma = comma;
                     need_comma = true;
                 }
-                if npair.is_some() {
+                if let Some(npair) = npair {
                     Self::syntax_error(
                         !self.comma_trailing && saw_comma,
                         "no comma expected",
-                        npair.unwrap().as_span().end_pos(),
+                        npair.as_span().end_pos(),
                     )?;
                 }
 
@@ -584,7 +778,10 @@ impl Relax {
             Rule::text => {
                 let mut doc = pair
                     .into_inner()
-                    .map(|p| self.handle_pair(p))
+                    .map(|p| match p.as_rule() {
+                        Rule::COMMENT | Rule::EOI => self.handle_pair(p),
+                        _ => self.handle_value(p, None),
+                    })
                     .collect::<Result<Vec<_>, _>>()?;
                 // Since we explicitly handled EOI, remove the dummy Null node
                 // from the end of the vector.
@@ -693,6 +890,21 @@ mod tests {
         assert_eq!(i, -64);
         Ok(())
     }
+    #[test]
+    fn test_number_underscore() -> Result<()> {
+        let relax = Relax::default();
+        let i = parse_integer(&relax, "0x1234_5678")?;
+        assert_eq!(i, 0x12345678);
+        let i = parse_integer(&relax, "0b1010_0000")?;
+        assert_eq!(i, 0xA0);
+        let i = parse_integer(&relax, "-1_000_000")?;
+        assert_eq!(i, -1000000);
+        let f = parse_float(&relax, "1_000.5")?;
+        assert_eq!(f, 1000.5);
+        let relax = Relax::json5();
+        assert!(parse_integer(&relax, "0x1234_5678").is_err());
+        Ok(())
+    }
 
     #[test]
     fn test_number_dec() -> Result<()> {
@@ -0,0 +1,672 @@
+//! JSON Schema generation.
+//!
+//! Types implementing [`Schema`] can describe the documents they serialize
+//! to with a [JSON Schema](https://json-schema.org).  The schema is itself
+//! expressed as a [`Document`], so it can be rendered by any of the
+//! emitters (e.g. `schema_for::<T>().to_json5()`).
+//!
+//! `#[derive(Schema)]` implements the trait for structs and enums.  The
+//! static text of `#[annotate(comment = "...")]` attributes becomes the
+//! `description` of the member and `#[annotate(format = ...)]` attributes
+//! become the custom keyword `x-format` (e.g. `"x-format": "hex"`).
+//!
+//! The schema of a type which contains itself (e.g. a tree node with a
+//! `Vec` of children) is placed under the `$defs` of the root schema and
+//! referred to with `$ref`.
+use std::cell::RefCell;
+use std::collections::{BTreeMap, BTreeSet, HashMap, HashSet, VecDeque};
+
+use crate::annotate::Format;
+use crate::document::{Document, StrFormat};
+use crate::integer::{Base, Int};
+
+/// The JSON Schema dialect of generated schemas.
+pub const DIALECT: &str = "https://json-schema.org/draft/2020-12/schema";
+
+/// The custom keyword used to express `#[annotate(format = ...)]`.
+pub const FORMAT_KEYWORD: &str = "x-format";
+
+/// Trait implemented by types which can describe themselves with a
+/// JSON Schema.
+pub trait Schema {
+    /// Returns the JSON Schema of this type.
+    fn schema() -> Document;
+
+    /// Returns whether a struct field of this type must be present.
+    fn required() -> bool {
+        true
+    }
+}
+
+/// Returns the JSON Schema of `T` as a standalone schema document.
+pub fn schema_for<T: Schema + ?Sized>() -> Document {
+    let mut schema = with_defs(T::schema);
+    if let Document::Mapping(m) = &mut schema {
+        m.insert(0, entry("$schema", Document::from(DIALECT)));
+    }
+    schema
+}
+
+/// Returns the name used for a `Format` in the `x-format` keyword.
+pub fn format_name(format: &Format) -> &'static str {
+    match format {
+        Format::Block => "block",
+        Format::Binary => "bin",
+        Format::Decimal => "dec",
+        Format::Hex => "hex",
+        Format::Octal => "oct",
+        Format::Int { base, .. } => match base {
+            Base::Bin => "bin",
+            Base::Oct => "oct",
+            Base::Dec => "dec",
+            Base::Hex => "hex",
+        },
+        Format::Compact => "compact",
+        Format::HexStr => "hexstr",
+        Format::Hexdump | Format::HexdumpLayout(_) => "hexdump",
+        Format::Xxd | Format::XxdLayout(_) => "xxd",
+        Format::Base64 => "base64",
+        Format::Base64Url => "base64url",
+        Format::Base32 => "base32",
+        Format::CArray => "c_array",
+        Format::XxdInclude => "xxd_i",
+        Format::IntelHex { .. } => "ihex",
+        Format::Srec { .. } => "srec",
+        Format::Quoted => "quoted",
+        Format::Unquoted => "unquoted",
+    }
+}
+
+pub(crate) fn entry(key: &'static str, value: Document) -> Document {
+    Document::Fragment(vec![Document::from(key), value])
+}
+
+pub(crate) fn string(s: &str) -> Document {
+    Document::String(s.to_string(), StrFormat::Standard)
+}
+
+pub(crate) fn object(entries: Vec<Document>) -> Document {
+    Document::Mapping(entries)
+}
+
+pub(crate) fn type_of(name: &'static str) -> Document {
+    object(vec![entry("type", Document::from(name))])
+}
+
+fn with_entry(mut schema: Document, key: &'static str, value: Document) -> Document {
+    match &mut schema {
+        Document::Mapping(m) => m.push(entry(key, value)),
+        _ => {
+            schema = object(vec![
+                entry("allOf", Document::Sequence(vec![schema])),
+                entry(key, value),
+            ])
+        }
+    }
+    schema
+}
+
+/// Returns the schema of a struct field or enum variant member of type `T`,
+/// annotated with its description and format.
+pub fn member<T: Schema + ?Sized>(description: Option<&str>, format: Option<Format>) -> Document {
+    annotated(T::schema(), description, format)
+}
+
+// The definitions of the recursive types of the schema being generated.
+#[derive(Default)]
+struct Defs {
+    // The definition names of the named types, by Rust type name.
+    names: HashMap<&'static str, String>,
+    // The named types whose schemas are being generated.
+    pending: HashSet<&'static str>,
+    // The named types which contain themselves.
+    recursive: HashSet<&'static str>,
+    // The schemas of the recursive types.
+    schemas: Vec<(String, Document)>,
+}
+
+thread_local! {
+    static DEFS: RefCell<Option<Defs>> = const { RefCell::new(None) };
+}
+
+// Calls `f` to generate a schema and adds the definitions of the recursive
+// types it contains to the schema.  Nested calls share the definitions of
+// the outermost call.
+fn with_defs(f: impl FnOnce() -> Document) -> Document {
+    if DEFS.with(|d| d.borrow().is_some()) {
+        return f();
+    }
+    DEFS.with(|d| *d.borrow_mut() = Some(Defs::default()));
+    let schema = f();
+    // Unwrap is ok: the definitions were created above.
+    let defs = DEFS.with(|d| d.borrow_mut().take()).unwrap();
+    if defs.schemas.is_empty() {
+        return schema;
+    }
+    let defs = defs
+        .schemas
+        .into_iter()
+        .map(|(name, s)| Document::Fragment(vec![string(&name), s]))
+        .collect();
+    with_entry(schema, "$defs", object(defs))
+}
+
+fn reference(name: &str) -> Document {
+    object(vec![entry("$ref", string(&format!("#/$defs/{}", name)))])
+}
+
+/// Returns the schema of the named type `T`, generating it with `schema`.
+///
+/// If `T` contains itself, its schema is defined under `$defs` with the
+/// given `name` (or a variant of it, when several types share the name)
+/// and the returned schema refers to the definition.
+pub fn named<T: ?Sized>(name: &str, schema: impl FnOnce() -> Document) -> Document {
+    let id = std::any::type_name::<T>();
+    with_defs(|| {
+        let pending = DEFS.with(|d| {
+            // Unwrap is ok: `with_defs` created the definitions.
+            let mut d = d.borrow_mut();
+            let d = d.as_mut().unwrap();
+            if !d.names.contains_key(id) {
+                let mut unique = name.to_string();
+                let mut n = 1;
+                while d.names.values().any(|v| *v == unique) {
+                    n += 1;
+                    unique = format!("{}{}", name, n);
+                }
+                d.names.insert(id, unique);
+            }
+            if d.pending.contains(id) || d.recursive.contains(id) {
+                d.recursive.insert(id);
+                Some(reference(&d.names[id]))
+            } else {
+                d.pending.insert(id);
+                None
+            }
+        });
+        if let Some(r) = pending {
+            return r;
+        }
+        let schema = schema();
+        DEFS.with(|d| {
+            // Unwrap is ok: `with_defs` created the definitions.
+            let mut d = d.borrow_mut();
+            let d = d.as_mut().unwrap();
+            d.pending.remove(id);
+            if d.recursive.contains(id) {
+                let name = d.names[id].clone();
+                d.schemas.push((name.clone(), schema));
+                reference(&name)
+            } else {
+                schema
+            }
+        })
+    })
+}
+
+fn annotated(mut schema: Document, description: Option<&str>, format: Option<Format>) -> Document {
+    if let Some(d) = description {
+        schema = with_entry(schema, "description", string(d));
+    }
+    if let Some(f) = format {
+        schema = with_entry(schema, FORMAT_KEYWORD, Document::from(format_name(&f)));
+    }
+    schema
+}
+
+/// Returns the schema of a struct or enum, annotated with the description
+/// and format of the type itself.
+pub fn container(schema: Document, description: Option<&str>, format: Option<Format>) -> Document {
+    annotated(schema, description, format)
+}
+
+/// Returns the schema of an object with the given `(name, schema)`
+/// properties and list of required property names.
+pub fn structure(
+    properties: Vec<(&'static str, Document)>,
+    required: Vec<&'static str>,
+) -> Document {
+    let mut schema = vec![entry("type", Document::from("object"))];
+    schema.push(entry(
+        "properties",
+        object(properties.into_iter().map(|(k, v)| entry(k, v)).collect()),
+    ));
+    if !required.is_empty() {
+        schema.push(entry(
+            "required",
+            Document::Sequence(required.into_iter().map(Document::from).collect()),
+        ));
+    }
+    object(schema)
+}
+
+/// Returns the schema of a fixed-length array whose elements have the
+/// given schemas.
+pub fn tuple(items: Vec<Document>) -> Document {
+    let len = items.len() as u64;
+    object(vec![
+        entry("type", Document::from("array")),
+        entry("prefixItems", Document::Sequence(items)),
+        entry("minItems", Document::Int(len.into())),
+        entry("maxItems", Document::Int(len.into())),
+    ])
+}
+
+/// Returns the schema of a unit enum variant, which serializes as the
+/// variant name.
+pub fn unit_variant(name: &'static str, description: Option<&str>) -> Document {
+    let mut schema = vec![entry("const", Document::from(name))];
+    if let Some(d) = description {
+        schema.push(entry("description", string(d)));
+    }
+    object(schema)
+}
+
+/// Returns the schema of a non-unit enum variant, which serializes as a
+/// mapping from the variant name to `value`.
+pub fn variant(name: &'static str, description: Option<&str>, value: Document) -> Document {
+    let mut schema = structure(vec![(name, value)], vec![name]);
+    if let Document::Mapping(m) = &mut schema {
+        m.push(entry("additionalProperties", Document::Boolean(false)));
+        if let Some(d) = description {
+            m.push(entry("description", string(d)));
+        }
+    }
+    schema
+}
+
+/// Returns the schema of an internally tagged enum variant, which
+/// serializes as `value` with an added `tag` property naming the variant.
+/// A unit variant has no `value`.
+pub fn tagged_variant(
+    tag: &'static str,
+    name: &'static str,
+    description: Option<&str>,
+    value: Option<Document>,
+) -> Document {
+    let mut schema = structure(vec![(tag, unit_variant(name, None))], vec![tag]);
+    if let Some(mut value) = value {
+        // Merge the tag into the variant's own properties if it has them.
+        if let Some(Document::Mapping(p)) = lookup_mut(&mut value, "properties") {
+            p.insert(0, entry(tag, unit_variant(name, None)));
+            match lookup_mut(&mut value, "required") {
+                Some(Document::Sequence(r)) => r.insert(0, Document::from(tag)),
+                _ => value = with_entry(value, "required", Document::Sequence(vec![tag.into()])),
+            }
+            schema = value;
+        } else {
+            schema = object(vec![entry(
+                "allOf",
+                Document::Sequence(vec![schema, value]),
+            )]);
+        }
+    }
+    match description {
+        Some(d) => with_entry(schema, "description", string(d)),
+        None => schema,
+    }
+}
+
+/// Returns the schema of an adjacently tagged enum variant, which
+/// serializes as a mapping from `tag` to the variant name and from
+/// `content` to `value`.  A unit variant has no `value`.
+pub fn adjacent_variant(
+    tag: &'static str,
+    content: &'static str,
+    name: &'static str,
+    description: Option<&str>,
+    value: Option<Document>,
+) -> Document {
+    let mut properties = vec![(tag, unit_variant(name, None))];
+    let mut required = vec![tag];
+    if let Some(value) = value {
+        properties.push((content, value));
+        required.push(content);
+    }
+    let schema = deny_unknown_fields(structure(properties, required));
+    match description {
+        Some(d) => with_entry(schema, "description", string(d)),
+        None => schema,
+    }
+}
+
+/// Returns the schema of an untagged enum variant, which serializes as
+/// `value`.
+pub fn untagged_variant(description: Option<&str>, value: Document) -> Document {
+    match description {
+        Some(d) => with_entry(value, "description", string(d)),
+        None => value,
+    }
+}
+
+/// Returns the schema of a struct with the members of a `#[serde(flatten)]`
+/// field of schema `inner` merged into the struct's `schema`.
+pub fn flatten(mut schema: Document, inner: Document) -> Document {
+    const MERGED: [&str; 4] = ["type", "properties", "required", "additionalProperties"];
+    let inner = match inner {
+        Document::Mapping(m)
+            if m.iter().all(|e| {
+                e.as_kv()
+                    .ok()
+                    .and_then(|(k, _)| k.as_str().ok())
+                    .is_some_and(|k| MERGED.contains(&k))
+            }) =>
+        {
+            m
+        }
+        // Other schemas (e.g. enums) can only be combined with the struct.
+        inner => {
+            return object(vec![entry(
+                "allOf",
+                Document::Sequence(vec![schema, inner]),
+            )])
+        }
+    };
+    for e in inner {
+        let Document::Fragment(mut kv) = e else {
+            continue;
+        };
+        // Unwrap is ok: entries of a mergeable schema are key-value pairs.
+        let value = kv.pop().unwrap();
+        let key = kv.pop().unwrap();
+        match (key.as_str().unwrap_or_default(), value) {
+            ("properties", Document::Mapping(p)) => {
+                if let Some(Document::Mapping(m)) = lookup_mut(&mut schema, "properties") {
+                    m.extend(p);
+                }
+            }
+            ("required", Document::Sequence(r)) => match lookup_mut(&mut schema, "required") {
+                Some(Document::Sequence(m)) => m.extend(r),
+                _ => schema = with_entry(schema, "required", Document::Sequence(r)),
+            },
+            ("additionalProperties", value) => {
+                schema = with_entry(schema, "additionalProperties", value);
+            }
+            _ => {}
+        }
+    }
+    schema
+}
+
+/// Returns an object `schema` which forbids properties it doesn't describe.
+pub fn deny_unknown_fields(schema: Document) -> Document {
+    with_entry(schema, "additionalProperties", Document::Boolean(false))
+}
+
+// Returns the value of the `key` keyword of a schema.
+fn lookup_mut<'a>(schema: &'a mut Document, key: &str) -> Option<&'a mut Document> {
+    let Document::Mapping(m) = schema else {
+        return None;
+    };
+    m.iter_mut()
+        .filter_map(|e| e.as_kv_mut().ok())
+        .find(|(k, _)| k.as_str().ok() == Some(key))
+        .map(|(_, v)| v)
+}
+
+/// Returns the schema of an enum with the given variant schemas.
+///
+/// An enum consisting only of undocumented unit variants is described
+/// with the `enum` keyword; other enums are described with `oneOf`.
+pub fn one_of(variants: Vec<Document>) -> Document {
+    let names = variants
+        .iter()
+        .map(|v| match v {
+            Document::Mapping(m) if m.len() == 1 => m[0]
+                .as_kv()
+                .ok()
+                .filter(|(k, _)| k.as_str().ok() == Some("const"))
+                .map(|(_, v)| v.clone()),
+            _ => None,
+        })
+        .collect::<Option<Vec<_>>>();
+    match names {
+        Some(names) => object(vec![
+            entry("type", Document::from("string")),
+            entry("enum", Document::Sequence(names)),
+        ]),
+        None => object(vec![entry("oneOf", Document::Sequence(variants))]),
+    }
+}
+
+/// Returns the schema of an untagged enum with the given variant schemas.
+/// The first matching variant is chosen, so the variants may overlap.
+pub fn any_of(variants: Vec<Document>) -> Document {
+    object(vec![entry("anyOf", Document::Sequence(variants))])
+}
+
+impl Schema for bool {
+    fn schema() -> Document {
+        type_of("boolean")
+    }
+}
+
+macro_rules! impl_int_schema {
+    ($t:ty) => {
+        impl Schema for $t {
+            fn schema() -> Document {
+                object(vec![
+                    entry("type", Document::from("integer")),
+                    entry("minimum", Document::Int(Int::new(<$t>::MIN, Base::Dec))),
+                    entry("maximum", Document::Int(Int::new(<$t>::MAX, Base::Dec))),
+                ])
+            }
+        }
+    };
+}
+
+impl_int_schema!(u8);
+impl_int_schema!(u16);
+impl_int_schema!(u32);
+impl_int_schema!(u64);
+impl_int_schema!(u128);
+impl_int_schema!(i8);
+impl_int_schema!(i16);
+impl_int_schema!(i32);
+impl_int_schema!(i64);
+impl_int_schema!(i128);
+
+impl Schema for usize {
+    fn schema() -> Document {
+        u64::schema()
+    }
+}
+
+impl Schema for isize {
+    fn schema() -> Document {
+        i64::schema()
+    }
+}
+
+impl Schema for f32 {
+    fn schema() -> Document {
+        type_of("number")
+    }
+}
+
+impl Schema for f64 {
+    fn schema() -> Document {
+        type_of("number")
+    }
+}
+
+impl Schema for char {
+    fn schema() -> Document {
+        object(vec![
+            entry("type", Document::from("string")),
+            entry("minLength", Document::Int(1u8.into())),
+            entry("maxLength", Document::Int(1u8.into())),
+        ])
+    }
+}
+
+impl Schema for str {
+    fn schema() -> Document {
+        type_of("string")
+    }
+}
+
+impl Schema for String {
+    fn schema() -> Document {
+        type_of("string")
+    }
+}
+
+impl Schema for () {
+    fn schema() -> Document {
+        type_of("null")
+    }
+}
+
+impl<T: Schema + ?Sized> Schema for &T {
+    fn schema() -> Document {
+        T::schema()
+    }
+    fn required() -> bool {
+        T::required()
+    }
+}
+
+impl<T: Schema + ?Sized> Schema for Box<T> {
+    fn schema() -> Document {
+        T::schema()
+    }
+    fn required() -> bool {
+        T::required()
+    }
+}
+
+impl<T: Schema> Schema for Option<T> {
+    fn schema() -> Document {
+        object(vec![entry(
+            "anyOf",
+            Document::Sequence(vec![T::schema(), type_of("null")]),
+        )])
+    }
+    fn required() -> bool {
+        false
+    }
+}
+
+fn array_of(items: Document) -> Document {
+    object(vec![
+        entry("type", Document::from("array")),
+        entry("items", items),
+    ])
+}
+
+macro_rules! impl_seq_schema {
+    ($($t:tt)*) => {
+        impl<T: Schema> Schema for $($t)* {
+            fn schema() -> Document {
+                array_of(T::schema())
+            }
+        }
+    };
+}
+
+impl_seq_schema!([T]);
+impl_seq_schema!(Vec<T>);
+impl_seq_schema!(VecDeque<T>);
+impl_seq_schema!(BTreeSet<T>);
+impl_seq_schema!(HashSet<T>);
+
+impl<T: Schema, const N: usize> Schema for [T; N] {
+    fn schema() -> Document {
+        with_entry(
+            with_entry(
+                array_of(T::schema()),
+                "minItems",
+                Document::Int((N as u64).into()),
+            ),
+            "maxItems",
+            Document::Int((N as u64).into()),
+        )
+    }
+}
+
+macro_rules! impl_map_schema {
+    ($t:ident) => {
+        impl<K, V: Schema> Schema for $t<K, V> {
+            fn schema() -> Document {
+                object(vec![
+                    entry("type", Document::from("object")),
+                    entry("additionalProperties", V::schema()),
+                ])
+            }
+        }
+    };
+}
+
+impl_map_schema!(BTreeMap);
+impl_map_schema!(HashMap);
+
+macro_rules! impl_tuple_schema {
+    ($($t:ident),*) => {
+        impl<$($t: Schema),*> Schema for ($($t,)*) {
+            fn schema() -> Document {
+                tuple(vec![$($t::schema()),*])
+            }
+        }
+    };
+}
+
+impl_tuple_schema!(A);
+impl_tuple_schema!(A, B);
+impl_tuple_schema!(A, B, C);
+impl_tuple_schema!(A, B, C, D);
+impl_tuple_schema!(A, B, C, D, E);
+impl_tuple_schema!(A, B, C, D, E, F);
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use anyhow::Result;
+
+    #[test]
+    fn test_primitives() -> Result<()> {
+        assert_eq!(
+            u8::schema().to_json().to_string(),
+            r#"{
+  "type": "integer",
+  "minimum": 0,
+  "maximum": 255
+}"#
+        );
+        assert_eq!(
+            Option::<bool>::schema()
+                .to_json5()
+                .compact(true)
+                .to_string(),
+            r#"{anyOf: [{type: "boolean"}, {type: "null"}]}"#
+        );
+        assert!(!Option::<bool>::required());
+        assert_eq!(
+            <[u16; 2]>::schema().to_json5().compact(true).to_string(),
+            r#"{type: "array", items: {type: "integer", minimum: 0, maximum: 65535}, minItems: 2, maxItems: 2}"#
+        );
+        Ok(())
+    }
+
+    #[test]
+    fn test_enums() -> Result<()> {
+        let plain = one_of(vec![unit_variant("A", None), unit_variant("B", None)]);
+        assert_eq!(
+            plain.to_json5().compact(true).to_string(),
+            r#"{type: "string", "enum": ["A", "B"]}"#
+        );
+        let mixed = one_of(vec![
+            unit_variant("A", Some("The A")),
+            variant("B", None, u8::schema()),
+        ]);
+        assert_eq!(
+            mixed.to_json5().compact(true).to_string(),
+            concat!(
+                r#"{oneOf: [{"const": "A", description: "The A"}, "#,
+                r#"{type: "object", properties: {B: {type: "integer", minimum: 0, maximum: 255}}, "#,
+                r#"required: ["B"], additionalProperties: false}]}"#
+            )
+        );
+        Ok(())
+    }
+}
@@ -2,11 +2,10 @@ use std::cell::Cell;
 
 use serde::ser;
 
-use crate::annotate::{Annotate, Format, MemberId};
+use crate::annotate::{Annotate, Format, MemberId, Region};
 use crate::document::{BytesFormat, CommentFormat, Document, StrFormat};
 use crate::error::Error;
-use crate::hexdump;
-use crate::integer::{Base, Int};
+use crate::integer::{Base, Int, IntValue};
 
 pub fn serialize<T>(value: &T) -> Result<Document, Error>
 where
@@ -19,21 +18,33 @@ where
 #[derive(Clone)]
 pub struct AnnotatedSerializer {
     base: Base,
+    width: usize,
+    group: usize,
     strformat: StrFormat,
     bytesformat: BytesFormat,
-    compact: bool,
+    regions: Vec<Region>,
+    pub(crate) compact: bool,
 }
 
 thread_local! {
     static ANNOTATE: Cell<Option<&'static dyn Annotate>> = const { Cell::new(None) };
 }
 
+impl Default for AnnotatedSerializer {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
 impl AnnotatedSerializer {
     pub fn new() -> Self {
         AnnotatedSerializer {
             base: Base::Dec,
+            width: 0,
+            group: 0,
             strformat: StrFormat::Standard,
             bytesformat: BytesFormat::Standard,
+            regions: Vec::new(),
             compact: false,
         }
     }
@@ -44,7 +55,9 @@ impl AnnotatedSerializer {
             // SAFETY: use `transmute` to erase the lifetime. This is sound as we use a scope guard
             // to ensure that the value is restored when the function returns, so we do not extend
             // the lifetime beyond this function.
-            let old = annotate.replace(unsafe { std::mem::transmute(value) });
+            let old = annotate.replace(unsafe {
+                std::mem::transmute::<Option<&dyn Annotate>, Option<&'static dyn Annotate>>(value)
+            });
             scopeguard::defer! {
                 annotate.set(old);
             }
@@ -53,11 +66,21 @@ impl AnnotatedSerializer {
     }
 
     fn with_base(&self, b: Base) -> Self {
+        self.with_int(b, 0, 0)
+    }
+
+    fn with_int(&self, b: Base, width: usize, group: usize) -> Self {
         let mut x = self.clone();
         x.base = b;
+        x.width = width;
+        x.group = group;
         x
     }
 
+    fn int<T: Into<IntValue>>(&self, v: T) -> Document {
+        Document::Int(Int::new_with_padding(v, self.base, self.width).with_group(self.group))
+    }
+
     fn with_bytesformat(&self, b: BytesFormat) -> Self {
         let mut x = self.clone();
         x.bytesformat = b;
@@ -76,33 +99,97 @@ impl AnnotatedSerializer {
         x
     }
 
-    fn annotate<T>(&self, variant: Option<&str>, field: &MemberId, f: impl FnOnce(Self) -> T) -> T {
+    pub(crate) fn annotate<T>(
+        &self,
+        variant: Option<&str>,
+        field: &MemberId,
+        f: impl FnOnce(Self) -> T,
+    ) -> T {
         Self::with(None, |annotator| {
-            let annotator = match annotator.and_then(|a| a.format(variant, field)) {
+            let mut ser = match annotator.and_then(|a| a.format(variant, field)) {
                 Some(Format::Block) => self.with_strformat(StrFormat::Multiline),
                 Some(Format::Binary) => self.with_base(Base::Bin),
                 Some(Format::Decimal) => self.with_base(Base::Dec),
                 Some(Format::Hex) => self.with_base(Base::Hex),
                 Some(Format::Octal) => self.with_base(Base::Oct),
+                Some(Format::Int { base, width, group }) => self.with_int(base, width, group),
                 Some(Format::Compact) => self.with_compact(true),
                 Some(Format::HexStr) => self.with_bytesformat(BytesFormat::HexStr),
                 Some(Format::Hexdump) => self.with_bytesformat(BytesFormat::Hexdump),
                 Some(Format::Xxd) => self.with_bytesformat(BytesFormat::Xxd),
+                Some(Format::HexdumpLayout(layout)) => {
+                    self.with_bytesformat(BytesFormat::HexdumpLayout(layout))
+                }
+                Some(Format::XxdLayout(layout)) => {
+                    self.with_bytesformat(BytesFormat::XxdLayout(layout))
+                }
+                Some(Format::Base64) => self.with_bytesformat(BytesFormat::Base64),
+                Some(Format::Base64Url) => self.with_bytesformat(BytesFormat::Base64Url),
+                Some(Format::Base32) => self.with_bytesformat(BytesFormat::Base32),
+                Some(Format::CArray) => self.with_bytesformat(BytesFormat::CArray),
+                Some(Format::XxdInclude) => self.with_bytesformat(BytesFormat::XxdInclude),
+                Some(Format::IntelHex { base }) => {
+                    self.with_bytesformat(BytesFormat::IntelHex { base })
+                }
+                Some(Format::Srec { base }) => self.with_bytesformat(BytesFormat::Srec { base }),
+                Some(Format::Quoted) => self.with_strformat(StrFormat::Quoted),
+                Some(Format::Unquoted) => self.with_strformat(StrFormat::Unquoted),
                 None => self.clone(),
             };
-            f(annotator)
+            // Regions describe a single bytes member, so they aren't
+            // inherited by the members of its value.
+            ser.regions = annotator
+                .and_then(|a| a.regions(variant, field))
+                .unwrap_or_default();
+            f(ser)
         })
     }
 
-    fn comment(&self, variant: Option<&str>, field: &MemberId) -> Option<Document> {
+    /// Returns the container-level annotations of the object being serialized.
+    pub(crate) fn container(&self) -> Container {
+        Container {
+            compact: Self::with(None, |annotator| {
+                matches!(
+                    annotator.and_then(|a| a.format(None, &MemberId::Container)),
+                    Some(Format::Compact)
+                )
+            }),
+            comment: self.comment(None, &MemberId::Container),
+        }
+    }
+
+    pub(crate) fn comment(&self, variant: Option<&str>, field: &MemberId) -> Option<Document> {
         Self::with(None, |annotator| {
-            annotator
-                .and_then(|a| a.comment(variant, field))
-                .map(|c| Document::Comment(c, CommentFormat::Standard))
+            let annotator = annotator?;
+            let comment = annotator.comment(variant, field)?;
+            let style = annotator
+                .comment_style(variant, field)
+                .unwrap_or(CommentFormat::Standard);
+            Some(Document::Comment(comment, style))
         })
     }
 }
 
+/// The annotations of a struct or enum which apply to the whole object.
+pub(crate) struct Container {
+    pub(crate) compact: bool,
+    pub(crate) comment: Option<Document>,
+}
+
+impl Container {
+    fn wrap(self, node: Document) -> Document {
+        let node = if self.compact && node.is_aggregate() {
+            Document::Compact(node.into())
+        } else {
+            node
+        };
+        match self.comment {
+            Some(c) => Document::Fragment(vec![c, node]),
+            None => node,
+        }
+    }
+}
+
 impl<'s> ser::Serializer for &'s mut AnnotatedSerializer {
     type Ok = Document;
     type Error = Error;
@@ -120,43 +207,43 @@ impl<'s> ser::Serializer for &'s mut AnnotatedSerializer {
     }
 
     fn serialize_i8(self, v: i8) -> Result<Self::Ok, Self::Error> {
-        Ok(Document::Int(Int::new(v, self.base)))
+        Ok(self.int(v))
     }
 
     fn serialize_i16(self, v: i16) -> Result<Self::Ok, Self::Error> {
-        Ok(Document::Int(Int::new(v, self.base)))
+        Ok(self.int(v))
     }
 
     fn serialize_i32(self, v: i32) -> Result<Self::Ok, Self::Error> {
-        Ok(Document::Int(Int::new(v, self.base)))
+        Ok(self.int(v))
     }
 
     fn serialize_i64(self, v: i64) -> Result<Self::Ok, Self::Error> {
-        Ok(Document::Int(Int::new(v, self.base)))
+        Ok(self.int(v))
     }
 
     fn serialize_i128(self, v: i128) -> Result<Self::Ok, Self::Error> {
-        Ok(Document::Int(Int::new(v, self.base)))
+        Ok(self.int(v))
     }
 
     fn serialize_u8(self, v: u8) -> Result<Self::Ok, Self::Error> {
-        Ok(Document::Int(Int::new(v, self.base)))
+        Ok(self.int(v))
     }
 
     fn serialize_u16(self, v: u16) -> Result<Self::Ok, Self::Error> {
-        Ok(Document::Int(Int::new(v, self.base)))
+        Ok(self.int(v))
     }
 
     fn serialize_u32(self, v: u32) -> Result<Self::Ok, Self::Error> {
-        Ok(Document::Int(Int::new(v, self.base)))
+        Ok(self.int(v))
     }
 
     fn serialize_u64(self, v: u64) -> Result<Self::Ok, Self::Error> {
-        Ok(Document::Int(Int::new(v, self.base)))
+        Ok(self.int(v))
     }
 
     fn serialize_u128(self, v: u128) -> Result<Self::Ok, Self::Error> {
-        Ok(Document::Int(Int::new(v, self.base)))
+        Ok(self.int(v))
     }
 
     fn serialize_f32(self, v: f32) -> Result<Self::Ok, Self::Error> {
@@ -176,18 +263,7 @@ impl<'s> ser::Serializer for &'s mut AnnotatedSerializer {
     }
 
     fn serialize_bytes(self, v: &[u8]) -> Result<Self::Ok, Self::Error> {
-        if let Some(string) = hexdump::to_string(v, self.bytesformat) {
-            Ok(Document::String(
-                string,
-                if self.bytesformat == BytesFormat::HexStr {
-                    StrFormat::Standard
-                } else {
-                    StrFormat::Multiline
-                },
-            ))
-        } else {
-            Ok(Document::Bytes(v.to_vec()))
-        }
+        Document::from_bytes(v, self.bytesformat, &self.regions)
     }
 
     fn serialize_none(self) -> Result<Self::Ok, Self::Error> {
@@ -215,16 +291,14 @@ impl<'s> ser::Serializer for &'s mut AnnotatedSerializer {
         _variant_index: u32,
         variant: &'static str,
     ) -> Result<Self::Ok, Self::Error> {
+        let container = self.container();
         let node = self.serialize_str(variant)?;
-        // TODO(serde-annotate#6): currently, placing a comment on a unit variant results in
-        // ugly (json) or bad (yaml) documents.  For now, omit comments on
-        // unit variants until we refactor comment emitting.
-        //if let Some(c) = self.comment(Some(variant), &MemberId::Variant) {
-        //    Ok(Document::Fragment(vec![c, node]))
-        //} else {
-        //    Ok(node)
-        //}
-        Ok(node)
+        let node = if let Some(c) = self.comment(Some(variant), &MemberId::Variant) {
+            Document::Fragment(vec![c, node])
+        } else {
+            node
+        };
+        Ok(container.wrap(node))
     }
 
     fn serialize_newtype_struct<T>(
@@ -258,6 +332,7 @@ impl<'s> ser::Serializer for &'s mut AnnotatedSerializer {
     where
         T: ?Sized + ser::Serialize,
     {
+        let container = self.container();
         let v = self.annotate(Some(variant), &MemberId::Variant, |mut ser| {
             let v = value.serialize(&mut ser)?;
             Ok::<_, Self::Error>(if ser.compact {
@@ -273,7 +348,7 @@ impl<'s> ser::Serializer for &'s mut AnnotatedSerializer {
         nodes.push(Document::from(variant));
         nodes.push(v);
 
-        Ok(Document::Mapping(vec![Document::Fragment(nodes)]))
+        Ok(container.wrap(Document::Mapping(vec![Document::Fragment(nodes)])))
     }
 
     fn serialize_seq(self, _len: Option<usize>) -> Result<Self::SerializeSeq, Self::Error> {
@@ -389,6 +464,7 @@ impl<'s> ser::SerializeTuple for SerializeTuple<'s> {
 
 pub struct SerializeTupleStruct<'s> {
     serializer: &'s mut AnnotatedSerializer,
+    container: Container,
     index: u32,
     sequence: Vec<Document>,
 }
@@ -396,6 +472,7 @@ pub struct SerializeTupleStruct<'s> {
 impl<'s> SerializeTupleStruct<'s> {
     fn new(s: &'s mut AnnotatedSerializer) -> Self {
         SerializeTupleStruct {
+            container: s.container(),
             serializer: s,
             index: 0,
             sequence: Vec::new(),
@@ -425,12 +502,13 @@ impl<'s> ser::SerializeTupleStruct for SerializeTupleStruct<'s> {
     }
 
     fn end(self) -> Result<Self::Ok, Self::Error> {
-        Ok(Document::Sequence(self.sequence))
+        Ok(self.container.wrap(Document::Sequence(self.sequence)))
     }
 }
 
 pub struct SerializeTupleVariant<'s> {
     serializer: &'s mut AnnotatedSerializer,
+    container: Container,
     variant: &'static str,
     index: u32,
     sequence: Vec<Document>,
@@ -439,6 +517,7 @@ pub struct SerializeTupleVariant<'s> {
 impl<'s> SerializeTupleVariant<'s> {
     fn new(s: &'s mut AnnotatedSerializer, v: &'static str) -> Self {
         SerializeTupleVariant {
+            container: s.container(),
             serializer: s,
             variant: v,
             index: 0,
@@ -490,7 +569,9 @@ impl<'s> ser::SerializeTupleVariant for SerializeTupleVariant<'s> {
         }
         nodes.push(Document::from(self.variant));
         nodes.push(sequence);
-        Ok(Document::Mapping(vec![Document::Fragment(nodes)]))
+        Ok(self
+            .container
+            .wrap(Document::Mapping(vec![Document::Fragment(nodes)])))
     }
 }
 
@@ -557,12 +638,14 @@ impl<'s> ser::SerializeMap for SerializeMap<'s> {
 
 pub struct SerializeStruct<'s> {
     serializer: &'s mut AnnotatedSerializer,
+    container: Container,
     mapping: Vec<Document>,
 }
 
 impl<'s> SerializeStruct<'s> {
     fn new(s: &'s mut AnnotatedSerializer) -> Self {
         SerializeStruct {
+            container: s.container(),
             serializer: s,
             mapping: Vec::new(),
         }
@@ -574,7 +657,7 @@ impl<'s> ser::SerializeStruct for SerializeStruct<'s> {
     type Error = Error;
 
     fn end(self) -> Result<Self::Ok, Self::Error> {
-        Ok(Document::Mapping(self.mapping))
+        Ok(self.container.wrap(Document::Mapping(self.mapping)))
     }
 
     fn serialize_field<T>(&mut self, key: &'static str, value: &T) -> Result<(), Self::Error>
@@ -598,6 +681,7 @@ impl<'s> ser::SerializeStruct for SerializeStruct<'s> {
 
 pub struct SerializeStructVariant<'s> {
     serializer: &'s mut AnnotatedSerializer,
+    container: Container,
     variant: &'static str,
     mapping: Vec<Document>,
 }
@@ -605,6 +689,7 @@ pub struct SerializeStructVariant<'s> {
 impl<'s> SerializeStructVariant<'s> {
     fn new(s: &'s mut AnnotatedSerializer, v: &'static str) -> Self {
         SerializeStructVariant {
+            container: s.container(),
             serializer: s,
             variant: v,
             mapping: Vec::new(),
@@ -635,7 +720,9 @@ impl<'s> ser::SerializeStructVariant for SerializeStructVariant<'s> {
         }
         nodes.push(Document::from(self.variant));
         nodes.push(mapping);
-        Ok(Document::Mapping(vec![Document::Fragment(nodes)]))
+        Ok(self
+            .container
+            .wrap(Document::Mapping(vec![Document::Fragment(nodes)])))
     }
 
     fn serialize_field<T>(&mut self, key: &'static str, value: &T) -> Result<(), Self::Error>
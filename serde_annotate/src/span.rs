@@ -0,0 +1,236 @@
+// Source locations of parsed document nodes.
+use std::collections::HashMap;
+use std::fmt;
+
+use crate::doc_iter::DocPath;
+
+/// The location of a node in the text it was parsed from.
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub struct Span {
+    /// The byte offset of the start of the node.
+    pub start: usize,
+    /// The byte offset just past the end of the node.
+    pub end: usize,
+    /// The line number (starting at 1) of the start of the node.
+    pub line: usize,
+    /// The column number (starting at 1) of the start of the node.
+    pub column: usize,
+}
+
+impl fmt::Display for Span {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(f, "{}:{}", self.line, self.column)
+    }
+}
+
+/// An owned element of a `DocPath`, as carried by `Error::PathError`.
+#[derive(Clone, Debug, PartialEq, Eq, Hash)]
+pub enum Segment {
+    /// The key of a mapping entry or the name of an enum variant.
+    Name(String),
+    /// The index of a sequence element.
+    Index(usize),
+}
+
+impl Segment {
+    /// Returns this segment as a borrowed `DocPath`.
+    pub fn as_path(&self) -> DocPath<'_> {
+        match self {
+            Segment::Name(n) => DocPath::Name(n),
+            Segment::Index(i) => DocPath::Index(*i),
+        }
+    }
+}
+
+impl From<&DocPath<'_>> for Segment {
+    fn from(path: &DocPath<'_>) -> Self {
+        match path {
+            DocPath::Name(n) => Segment::Name(n.to_string()),
+            DocPath::Index(i) => Segment::Index(*i),
+        }
+    }
+}
+
+// A node of the document in a `PathTree`, with the nodes it contains.
+#[derive(Clone, Debug)]
+struct Node<T> {
+    value: Option<T>,
+    key: Option<T>,
+    names: HashMap<String, usize>,
+    indices: HashMap<usize, usize>,
+}
+
+impl<T> Default for Node<T> {
+    fn default() -> Self {
+        Node {
+            value: None,
+            key: None,
+            names: HashMap::new(),
+            indices: HashMap::new(),
+        }
+    }
+}
+
+// Records a `T` for the nodes and mapping keys of a document by their
+// object-path.  The nodes are stored in a vector and children refer to
+// their nodes by index, so lookups walk the path in place.  The root is
+// the first node.
+#[derive(Clone, Debug)]
+pub(crate) struct PathTree<T> {
+    nodes: Vec<Node<T>>,
+}
+
+impl<T> Default for PathTree<T> {
+    fn default() -> Self {
+        PathTree {
+            nodes: vec![Node::default()],
+        }
+    }
+}
+
+impl<T> PathTree<T> {
+    // The root node of the document.
+    pub(crate) const ROOT: usize = 0;
+
+    // Returns the node named by `segment` within `parent`, creating it if
+    // needed.
+    pub(crate) fn child(&mut self, parent: usize, segment: Segment) -> usize {
+        let next = self.nodes.len();
+        let node = &mut self.nodes[parent];
+        let id = match segment {
+            Segment::Name(n) => *node.names.entry(n).or_insert(next),
+            Segment::Index(i) => *node.indices.entry(i).or_insert(next),
+        };
+        if id == next {
+            self.nodes.push(Node::default());
+        }
+        id
+    }
+
+    // Returns the node named by `path` within `parent`, if it exists.
+    pub(crate) fn get_child(&self, parent: usize, path: &DocPath) -> Option<usize> {
+        let node = &self.nodes[parent];
+        match path {
+            DocPath::Name(n) => node.names.get(*n),
+            DocPath::Index(i) => node.indices.get(i),
+        }
+        .copied()
+    }
+
+    pub(crate) fn insert(&mut self, node: usize, value: T) {
+        self.nodes[node].value = Some(value);
+    }
+
+    pub(crate) fn insert_key(&mut self, node: usize, key: T) {
+        self.nodes[node].key = Some(key);
+    }
+
+    pub(crate) fn value(&self, node: usize) -> Option<&T> {
+        self.nodes[node].value.as_ref()
+    }
+
+    pub(crate) fn key(&self, node: usize) -> Option<&T> {
+        self.nodes[node].key.as_ref()
+    }
+
+    fn find(&self, path: &[DocPath]) -> Option<usize> {
+        path.iter()
+            .try_fold(Self::ROOT, |node, p| self.get_child(node, p))
+    }
+}
+
+/// A `SpanMap` records the location of every value node and mapping key
+/// in a parsed document.  Locations are looked up by the object-path of
+/// the node, as yielded by `Document::iter_path`.
+#[derive(Clone, Debug, Default)]
+pub struct SpanMap {
+    pub(crate) tree: PathTree<Span>,
+}
+
+impl SpanMap {
+    /// Returns the location of the node at `path`.
+    pub fn get(&self, path: &[DocPath]) -> Option<&Span> {
+        self.tree.value(self.tree.find(path)?)
+    }
+
+    /// Returns the location of the key of the mapping entry at `path`.
+    pub fn key(&self, path: &[DocPath]) -> Option<&Span> {
+        self.tree.key(self.tree.find(path)?)
+    }
+
+    /// Returns an iterator over the object-paths and locations of all
+    /// recorded nodes.  The iteration order is unspecified.
+    pub fn iter(&self) -> impl Iterator<Item = (Vec<DocPath<'_>>, &Span)> {
+        let nodes = &self.tree.nodes;
+        let mut found = Vec::new();
+        let mut stack = vec![(Vec::new(), &nodes[PathTree::<Span>::ROOT])];
+        while let Some((path, node)) = stack.pop() {
+            for (name, id) in &node.names {
+                let mut p = path.clone();
+                p.push(DocPath::Name(name.as_str()));
+                stack.push((p, &nodes[*id]));
+            }
+            for (index, id) in &node.indices {
+                let mut p = path.clone();
+                p.push(DocPath::Index(*index));
+                stack.push((p, &nodes[*id]));
+            }
+            if let Some(span) = &node.value {
+                found.push((path, span));
+            }
+        }
+        found.into_iter()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::document::Document;
+    use anyhow::Result;
+
+    const SAMPLE: &str = r#"{
+  a: 1,
+  // Comment
+  c: {
+    k: 0x10,
+  },
+  d: [100, "two"],
+}"#;
+
+    #[test]
+    fn test_spans() -> Result<()> {
+        let (_, spans) = Document::parse_with_spans(SAMPLE)?;
+        let root = spans.get(&[]).unwrap();
+        assert_eq!((root.start, root.end), (0, SAMPLE.len()));
+        let a = spans.get(&[DocPath::Name("a")]).unwrap();
+        assert_eq!((a.line, a.column), (2, 6));
+        assert_eq!(&SAMPLE[a.start..a.end], "1");
+        let k = spans
+            .get(&[DocPath::Name("c"), DocPath::Name("k")])
+            .unwrap();
+        assert_eq!(k.to_string(), "5:8");
+        assert_eq!(&SAMPLE[k.start..k.end], "0x10");
+        let c = spans.get(&[DocPath::Name("c")]).unwrap();
+        assert_eq!((c.line, c.column), (4, 6));
+        let two = spans.get(&[DocPath::Name("d"), DocPath::Index(1)]).unwrap();
+        assert_eq!(&SAMPLE[two.start..two.end], "\"two\"");
+        assert!(spans.get(&[DocPath::Name("e")]).is_none());
+        let key = spans
+            .key(&[DocPath::Name("c"), DocPath::Name("k")])
+            .unwrap();
+        assert_eq!(&SAMPLE[key.start..key.end], "k");
+        assert_eq!(key.to_string(), "5:5");
+        assert_eq!(spans.iter().count(), 7);
+        Ok(())
+    }
+
+    #[test]
+    fn test_paths_match_iter_path() -> Result<()> {
+        let (doc, spans) = Document::parse_with_spans(SAMPLE)?;
+        for (path, _) in doc.iter_path() {
+            assert!(spans.get(&path).is_some(), "no span for {:?}", path);
+        }
+        Ok(())
+    }
+}
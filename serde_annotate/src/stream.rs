@@ -0,0 +1,748 @@
+// Streaming serialization directly to an output writer.
+use std::io;
+
+use serde::ser;
+
+use crate::annotate::MemberId;
+use crate::color::{ColorProfile, PaintExt};
+use crate::document::Document;
+use crate::error::Error;
+use crate::json::JsonEmitter;
+use crate::ser::AnnotatedSerializer;
+use crate::yaml::YamlEmitter;
+
+type Result<T> = std::result::Result<T, Error>;
+
+// Output is buffered and written out in chunks of about this size.
+const BUFFER_SIZE: usize = 8192;
+
+/// The document formats supported by the `StreamSerializer`.
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum StreamFormat {
+    Json,
+    Json5,
+    Yaml,
+}
+
+enum Emitter {
+    Json(JsonEmitter),
+    Yaml(YamlEmitter),
+}
+
+fn emitter(format: StreamFormat, color: ColorProfile) -> Emitter {
+    match format {
+        StreamFormat::Json => Emitter::Json(Document::Null.to_json().color(color).emitter()),
+        StreamFormat::Json5 => Emitter::Json(Document::Null.to_json5().color(color).emitter()),
+        StreamFormat::Yaml => Emitter::Yaml(Document::Null.to_yaml().color(color).emitter()),
+    }
+}
+
+// An open mapping or sequence.
+struct Frame {
+    mapping: bool,
+    compact: bool,
+    count: usize,
+    // Comments on the value of the current entry, which follow the entry.
+    trailing: Vec<Document>,
+}
+
+/// Serializer which writes the document to an `io::Write` as the value is
+/// serialized, without first building a `Document` in memory.
+///
+/// Comments and formats from `Annotate` are honored and the output is the
+/// same as that of serializing to a `Document` and rendering it with
+/// `to_json`, `to_json5` or `to_yaml`.  Variants annotated as compact are
+/// built as `Document`s before being written.
+pub struct StreamSerializer<W: io::Write> {
+    writer: W,
+    buffer: String,
+    format: StreamFormat,
+    emitter: Emitter,
+    annotator: AnnotatedSerializer,
+    frames: Vec<Frame>,
+    // The YAML prefix (`:` or `-`) waiting for the value which follows it.
+    pending: Option<&'static str>,
+    // The key of the mapping entry waiting for the value which follows it.
+    key: Option<Document>,
+}
+
+impl AnnotatedSerializer {
+    /// Creates a streaming serializer which writes to `writer` in the given
+    /// `format`, starting from the formatting options of this serializer.
+    pub fn stream<W: io::Write>(self, writer: W, format: StreamFormat) -> StreamSerializer<W> {
+        StreamSerializer::with_annotator(writer, format, self)
+    }
+}
+
+/// Serializes `value` to `writer` in the given `format`.
+pub fn serialize_to_writer<W, T>(writer: W, format: StreamFormat, value: &T) -> Result<()>
+where
+    W: io::Write,
+    T: ?Sized + ser::Serialize,
+{
+    let mut serializer = StreamSerializer::new(writer, format);
+    value.serialize(&mut serializer)?;
+    serializer.flush()
+}
+
+impl<W: io::Write> StreamSerializer<W> {
+    pub fn new(writer: W, format: StreamFormat) -> Self {
+        Self::with_annotator(writer, format, AnnotatedSerializer::new())
+    }
+
+    /// Set the color profile to use in the document.
+    pub fn color(mut self, c: ColorProfile) -> Self {
+        self.emitter = emitter(self.format, c);
+        self
+    }
+
+    fn with_annotator(writer: W, format: StreamFormat, annotator: AnnotatedSerializer) -> Self {
+        StreamSerializer {
+            writer,
+            buffer: match format {
+                StreamFormat::Yaml => "---\n".into(),
+                _ => String::new(),
+            },
+            format,
+            emitter: emitter(format, ColorProfile::default()),
+            annotator,
+            frames: Vec::new(),
+            pending: None,
+            key: None,
+        }
+    }
+
+    /// Writes out all buffered output and flushes the writer.
+    pub fn flush(&mut self) -> Result<()> {
+        self.writer.write_all(self.buffer.as_bytes())?;
+        self.buffer.clear();
+        self.writer.flush()?;
+        Ok(())
+    }
+
+    /// Returns the underlying writer.  Any buffered output is written first.
+    pub fn into_inner(mut self) -> Result<W> {
+        self.flush()?;
+        Ok(self.writer)
+    }
+
+    fn drain(&mut self) -> Result<()> {
+        if self.buffer.len() >= BUFFER_SIZE {
+            self.writer.write_all(self.buffer.as_bytes())?;
+            self.buffer.clear();
+        }
+        Ok(())
+    }
+
+    fn compact(&self) -> bool {
+        self.frames.last().is_some_and(|f| f.compact)
+    }
+
+    // Writes the pending YAML prefix for a value on the same line.
+    fn prefix_inline(&mut self) -> Result<()> {
+        if let (Some(prefix), Emitter::Yaml(y)) = (self.pending.take(), &mut self.emitter) {
+            let prefix = y.color.punctuation.paint(prefix);
+            put(&mut self.buffer, format_args!("{} ", prefix))?;
+        }
+        Ok(())
+    }
+
+    // Starts a mapping or sequence, preceded by an optional comment.
+    fn begin(&mut self, mapping: bool, compact: bool, comment: Option<Document>) -> Result<()> {
+        self.comments(comment.as_slice())?;
+        self.write_key()?;
+        let compact = compact || self.compact();
+        self.frames.push(Frame {
+            mapping,
+            compact,
+            count: 0,
+            trailing: Vec::new(),
+        });
+        let open = if mapping { "{" } else { "[" };
+        match &mut self.emitter {
+            Emitter::Json(j) => {
+                j.compact = compact;
+                j.level += 1;
+                j.writeln(&mut self.buffer, j.color.aggregate.paint(open))?;
+            }
+            Emitter::Yaml(_) if compact => {
+                self.prefix_inline()?;
+                let Emitter::Yaml(y) = &mut self.emitter else {
+                    unreachable!()
+                };
+                y.compact = true;
+                put(&mut self.buffer, y.color.aggregate.paint(open))?;
+            }
+            Emitter::Yaml(_) => {}
+        }
+        Ok(())
+    }
+
+    // Starts an entry of a mapping or an element of a sequence, preceded by
+    // an optional comment.
+    fn item(&mut self, comment: Option<Document>) -> Result<()> {
+        // Unwrap is ok: items are only started within a container.
+        let frame = self.frames.last_mut().unwrap();
+        let (first, mapping, compact) = (frame.count == 0, frame.mapping, frame.compact);
+        frame.count += 1;
+        let trailing = std::mem::take(&mut frame.trailing);
+        let w = &mut self.buffer;
+        match &mut self.emitter {
+            Emitter::Json(j) => {
+                if !first {
+                    put(w, j.color.punctuation.paint(","))?;
+                    j.emit_trailing(w, &trailing)?;
+                    put(w, if compact { " " } else { "\n" })?;
+                }
+                j.emit_indent(w)?;
+                if let Some((c, f)) = comment.as_ref().and_then(Document::comment) {
+                    if j.emit_comment(w, c, f)? {
+                        put(w, "\n")?;
+                        j.emit_indent(w)?;
+                    }
+                }
+            }
+            Emitter::Yaml(y) if compact => {
+                if !first {
+                    if mapping {
+                        put(w, ", ")?;
+                    } else {
+                        put(w, y.color.punctuation.paint(", "))?;
+                    }
+                }
+            }
+            Emitter::Yaml(y) => {
+                if first {
+                    if let Some(prefix) = self.pending.take() {
+                        put(w, format_args!("{}\n", y.color.punctuation.paint(prefix)))?;
+                        y.emit_indent_extra(w, 1)?;
+                    }
+                    y.level += 1;
+                } else {
+                    y.emit_trailing(w, &trailing)?;
+                    y.writeln(w, "")?;
+                    y.emit_indent(w)?;
+                }
+                if let Some((c, f)) = comment.as_ref().and_then(Document::comment) {
+                    if y.emit_comment(w, c, f)? {
+                        y.writeln(w, "")?;
+                        y.emit_indent(w)?;
+                    }
+                }
+                if !mapping {
+                    self.pending = Some("-");
+                }
+            }
+        }
+        Ok(())
+    }
+
+    // Writes comments on the lines preceding a sequence element.
+    fn comments(&mut self, comments: &[Document]) -> Result<()> {
+        let w = &mut self.buffer;
+        for (c, f) in comments.iter().filter_map(Document::comment) {
+            match &mut self.emitter {
+                Emitter::Json(j) => {
+                    if j.emit_comment(w, c, f)? {
+                        put(w, "\n")?;
+                        j.emit_indent(w)?;
+                    }
+                }
+                Emitter::Yaml(y) => {
+                    if y.emit_comment(w, c, f)? {
+                        y.writeln(w, "")?;
+                        y.emit_indent(w)?;
+                    }
+                }
+            }
+        }
+        Ok(())
+    }
+
+    // Sets the key of a mapping entry.  The key is written when its value
+    // starts, after any comments which precede the entry.
+    fn key(&mut self, key: Document) {
+        self.key = Some(key);
+    }
+
+    fn write_key(&mut self) -> Result<()> {
+        let Some(key) = self.key.take() else {
+            return Ok(());
+        };
+        let w = &mut self.buffer;
+        match &mut self.emitter {
+            Emitter::Json(j) => {
+                j.emit_key_node(w, &key)?;
+                put(w, j.color.punctuation.paint(": "))?;
+            }
+            Emitter::Yaml(y) => {
+                y.is_key = true;
+                let result = y.emit_node(w, &key);
+                y.is_key = false;
+                result?;
+                self.pending = Some(":");
+            }
+        }
+        Ok(())
+    }
+
+    // Writes a value which has been built as a `Document`.
+    fn value(&mut self, value: Document) -> Result<()> {
+        // The comments directly on a scalar, such as a unit variant, follow
+        // a mapping entry.  Otherwise, comments precede the entry or element.
+        let (mut leading, mut trailing) = (Vec::new(), Vec::new());
+        let value = split_comments(value, &mut leading, &mut trailing);
+        match self.frames.last_mut() {
+            Some(frame) if frame.mapping => frame.trailing = trailing,
+            _ => leading.append(&mut trailing),
+        }
+        self.comments(&leading)?;
+        self.write_key()?;
+        self.prefix_inline()?;
+        match &mut self.emitter {
+            Emitter::Json(j) => j.emit_node(&mut self.buffer, &value)?,
+            Emitter::Yaml(y) => y.emit_node(&mut self.buffer, &value)?,
+        }
+        self.drain()
+    }
+
+    // Ends the innermost mapping or sequence.
+    fn end(&mut self) -> Result<()> {
+        // Unwrap is ok: `end` is always paired with `begin`.
+        let frame = self.frames.pop().unwrap();
+        let parent = self.compact();
+        let close = if frame.mapping { "}" } else { "]" };
+        match &mut self.emitter {
+            Emitter::Json(j) => {
+                if frame.count > 0 {
+                    j.emit_trailing(&mut self.buffer, &frame.trailing)?;
+                    j.writeln(&mut self.buffer, "")?;
+                }
+                j.level -= 1;
+                j.emit_indent(&mut self.buffer)?;
+                put(&mut self.buffer, j.color.aggregate.paint(close))?;
+                j.compact = parent;
+            }
+            Emitter::Yaml(y) if frame.compact => {
+                put(&mut self.buffer, y.color.aggregate.paint(close))?;
+                y.compact = parent;
+            }
+            Emitter::Yaml(y) if frame.count > 0 => {
+                y.emit_trailing(&mut self.buffer, &frame.trailing)?;
+                y.level -= 1;
+            }
+            Emitter::Yaml(_) => {
+                let open = if frame.mapping { "{" } else { "[" };
+                self.prefix_inline()?;
+                let Emitter::Yaml(y) = &mut self.emitter else {
+                    unreachable!()
+                };
+                put(&mut self.buffer, y.color.aggregate.paint(open))?;
+                put(&mut self.buffer, y.color.aggregate.paint(close))?;
+            }
+        }
+        self.drain()
+    }
+
+    // Serializes with the formatting annotations of a member.
+    fn annotated<T>(
+        &mut self,
+        variant: Option<&str>,
+        field: &MemberId,
+        f: impl FnOnce(&mut Self) -> Result<T>,
+    ) -> Result<T> {
+        let annotator = self.annotator.clone();
+        annotator.annotate(variant, field, |annotator| {
+            let saved = std::mem::replace(&mut self.annotator, annotator);
+            let result = f(self);
+            self.annotator = saved;
+            result
+        })
+    }
+
+    // Starts the single-entry mapping holding the payload of an enum variant.
+    fn begin_variant(&mut self, variant: &'static str) -> Result<()> {
+        let container = self.annotator.container();
+        let comment = self.annotator.comment(Some(variant), &MemberId::Variant);
+        self.begin(true, container.compact, container.comment)?;
+        self.item(comment)?;
+        self.key(Document::from(variant));
+        Ok(())
+    }
+
+    // Starts a struct, with the annotations of the struct itself.
+    fn begin_struct(&mut self, mapping: bool) -> Result<()> {
+        let container = self.annotator.container();
+        self.begin(mapping, container.compact, container.comment)
+    }
+
+    fn variant_is_compact(&self, variant: &'static str) -> bool {
+        self.annotator
+            .annotate(Some(variant), &MemberId::Variant, |a| a.compact)
+    }
+}
+
+// Removes the comments from a commented value, leaving the value itself,
+// in the same way as `Document::entry_nodes`.
+fn split_comments(
+    node: Document,
+    leading: &mut Vec<Document>,
+    trailing: &mut Vec<Document>,
+) -> Document {
+    let scalar = node.is_commented_scalar();
+    match node {
+        Document::Fragment(nodes) => {
+            let mut values = Vec::new();
+            for n in nodes {
+                match n {
+                    Document::Comment(..) if scalar => trailing.push(n),
+                    Document::Comment(..) => leading.push(n),
+                    _ => values.push(split_comments(n, leading, trailing)),
+                }
+            }
+            match values.len() {
+                1 => values.remove(0),
+                _ => Document::Fragment(values),
+            }
+        }
+        _ => node,
+    }
+}
+
+fn put(w: &mut String, s: impl std::fmt::Display) -> Result<()> {
+    use std::fmt::Write;
+    write!(w, "{}", s)?;
+    Ok(())
+}
+
+macro_rules! serialize_scalar {
+    ($($method:ident($ty:ty)),* $(,)?) => {
+        $(
+            fn $method(self, v: $ty) -> Result<()> {
+                let value = (&mut self.annotator).$method(v)?;
+                self.value(value)
+            }
+        )*
+    };
+}
+
+impl<'s, W: io::Write> ser::Serializer for &'s mut StreamSerializer<W> {
+    type Ok = ();
+    type Error = Error;
+
+    type SerializeSeq = Compound<'s, W>;
+    type SerializeTuple = Compound<'s, W>;
+    type SerializeTupleStruct = Compound<'s, W>;
+    type SerializeTupleVariant = Compound<'s, W>;
+    type SerializeMap = Compound<'s, W>;
+    type SerializeStruct = Compound<'s, W>;
+    type SerializeStructVariant = Compound<'s, W>;
+
+    serialize_scalar!(
+        serialize_bool(bool),
+        serialize_i8(i8),
+        serialize_i16(i16),
+        serialize_i32(i32),
+        serialize_i64(i64),
+        serialize_i128(i128),
+        serialize_u8(u8),
+        serialize_u16(u16),
+        serialize_u32(u32),
+        serialize_u64(u64),
+        serialize_u128(u128),
+        serialize_f32(f32),
+        serialize_f64(f64),
+        serialize_char(char),
+        serialize_str(&str),
+        serialize_bytes(&[u8]),
+    );
+
+    fn serialize_none(self) -> Result<()> {
+        self.value(Document::Null)
+    }
+
+    fn serialize_some<T>(self, value: &T) -> Result<()>
+    where
+        T: ?Sized + ser::Serialize,
+    {
+        value.serialize(self)
+    }
+
+    fn serialize_unit(self) -> Result<()> {
+        self.value(Document::Null)
+    }
+
+    fn serialize_unit_struct(self, _name: &'static str) -> Result<()> {
+        self.value(Document::Null)
+    }
+
+    fn serialize_unit_variant(
+        self,
+        name: &'static str,
+        variant_index: u32,
+        variant: &'static str,
+    ) -> Result<()> {
+        let value = (&mut self.annotator).serialize_unit_variant(name, variant_index, variant)?;
+        self.value(value)
+    }
+
+    fn serialize_newtype_struct<T>(self, _name: &'static str, value: &T) -> Result<()>
+    where
+        T: ?Sized + ser::Serialize,
+    {
+        self.annotated(None, &MemberId::Index(0), |s| value.serialize(s))
+    }
+
+    fn serialize_newtype_variant<T>(
+        self,
+        _name: &'static str,
+        _variant_index: u32,
+        variant: &'static str,
+        value: &T,
+    ) -> Result<()>
+    where
+        T: ?Sized + ser::Serialize,
+    {
+        self.begin_variant(variant)?;
+        self.annotated(Some(variant), &MemberId::Variant, |s| {
+            if s.annotator.compact {
+                let value = value.serialize(&mut s.annotator.clone())?;
+                s.value(Document::Compact(value.into()))
+            } else {
+                value.serialize(&mut *s)
+            }
+        })?;
+        self.end()
+    }
+
+    fn serialize_seq(self, _len: Option<usize>) -> Result<Compound<'s, W>> {
+        self.begin(false, false, None)?;
+        Ok(Compound::new(self, None))
+    }
+
+    fn serialize_tuple(self, _len: usize) -> Result<Compound<'s, W>> {
+        self.begin(false, false, None)?;
+        Ok(Compound::new(self, None))
+    }
+
+    fn serialize_tuple_struct(self, _name: &'static str, _len: usize) -> Result<Compound<'s, W>> {
+        self.begin_struct(false)?;
+        Ok(Compound::new(self, None))
+    }
+
+    fn serialize_tuple_variant(
+        self,
+        _name: &'static str,
+        _variant_index: u32,
+        variant: &'static str,
+        _len: usize,
+    ) -> Result<Compound<'s, W>> {
+        self.begin_variant(variant)?;
+        let compact = self.variant_is_compact(variant);
+        self.begin(false, compact, None)?;
+        Ok(Compound::new(self, Some(variant)))
+    }
+
+    fn serialize_map(self, _len: Option<usize>) -> Result<Compound<'s, W>> {
+        self.begin(true, false, None)?;
+        Ok(Compound::new(self, None))
+    }
+
+    fn serialize_struct(self, _name: &'static str, _len: usize) -> Result<Compound<'s, W>> {
+        self.begin_struct(true)?;
+        Ok(Compound::new(self, None))
+    }
+
+    fn serialize_struct_variant(
+        self,
+        _name: &'static str,
+        _variant_index: u32,
+        variant: &'static str,
+        _len: usize,
+    ) -> Result<Compound<'s, W>> {
+        self.begin_variant(variant)?;
+        let compact = self.variant_is_compact(variant);
+        self.begin(true, compact, None)?;
+        Ok(Compound::new(self, Some(variant)))
+    }
+}
+
+/// The state of a compound value being streamed by a `StreamSerializer`.
+pub struct Compound<'s, W: io::Write> {
+    serializer: &'s mut StreamSerializer<W>,
+    variant: Option<&'static str>,
+    index: u32,
+}
+
+impl<'s, W: io::Write> Compound<'s, W> {
+    fn new(s: &'s mut StreamSerializer<W>, variant: Option<&'static str>) -> Self {
+        Compound {
+            serializer: s,
+            variant,
+            index: 0,
+        }
+    }
+
+    fn element<T>(&mut self, value: &T) -> Result<()>
+    where
+        T: ?Sized + ser::Serialize,
+    {
+        self.serializer.item(None)?;
+        value.serialize(&mut *self.serializer)
+    }
+
+    fn indexed_field<T>(&mut self, value: &T) -> Result<()>
+    where
+        T: ?Sized + ser::Serialize,
+    {
+        let field = MemberId::Index(self.index);
+        let comment = self.serializer.annotator.comment(self.variant, &field);
+        self.serializer.item(comment)?;
+        self.serializer
+            .annotated(self.variant, &field, |s| value.serialize(s))?;
+        self.index += 1;
+        Ok(())
+    }
+
+    fn named_field<T>(&mut self, key: &'static str, value: &T) -> Result<()>
+    where
+        T: ?Sized + ser::Serialize,
+    {
+        let field = MemberId::Name(key);
+        let comment = self.serializer.annotator.comment(None, &field);
+        self.serializer.item(comment)?;
+        self.serializer.key(Document::from(key));
+        self.serializer
+            .annotated(None, &field, |s| value.serialize(s))
+    }
+
+    fn end(self) -> Result<()> {
+        self.serializer.end()?;
+        if self.variant.is_some() {
+            self.serializer.end()?;
+        }
+        Ok(())
+    }
+}
+
+impl<W: io::Write> ser::SerializeSeq for Compound<'_, W> {
+    type Ok = ();
+    type Error = Error;
+
+    fn serialize_element<T>(&mut self, value: &T) -> Result<()>
+    where
+        T: ?Sized + ser::Serialize,
+    {
+        self.element(value)
+    }
+
+    fn end(self) -> Result<()> {
+        Compound::end(self)
+    }
+}
+
+impl<W: io::Write> ser::SerializeTuple for Compound<'_, W> {
+    type Ok = ();
+    type Error = Error;
+
+    fn serialize_element<T>(&mut self, value: &T) -> Result<()>
+    where
+        T: ?Sized + ser::Serialize,
+    {
+        self.element(value)
+    }
+
+    fn end(self) -> Result<()> {
+        Compound::end(self)
+    }
+}
+
+impl<W: io::Write> ser::SerializeTupleStruct for Compound<'_, W> {
+    type Ok = ();
+    type Error = Error;
+
+    fn serialize_field<T>(&mut self, value: &T) -> Result<()>
+    where
+        T: ?Sized + ser::Serialize,
+    {
+        self.indexed_field(value)
+    }
+
+    fn end(self) -> Result<()> {
+        Compound::end(self)
+    }
+}
+
+impl<W: io::Write> ser::SerializeTupleVariant for Compound<'_, W> {
+    type Ok = ();
+    type Error = Error;
+
+    fn serialize_field<T>(&mut self, value: &T) -> Result<()>
+    where
+        T: ?Sized + ser::Serialize,
+    {
+        self.indexed_field(value)
+    }
+
+    fn end(self) -> Result<()> {
+        Compound::end(self)
+    }
+}
+
+impl<W: io::Write> ser::SerializeMap for Compound<'_, W> {
+    type Ok = ();
+    type Error = Error;
+
+    fn serialize_key<T>(&mut self, key: &T) -> Result<()>
+    where
+        T: ?Sized + ser::Serialize,
+    {
+        self.serializer.item(None)?;
+        let key = key.serialize(&mut self.serializer.annotator.clone())?;
+        self.serializer.key(key);
+        Ok(())
+    }
+
+    fn serialize_value<T>(&mut self, value: &T) -> Result<()>
+    where
+        T: ?Sized + ser::Serialize,
+    {
+        value.serialize(&mut *self.serializer)
+    }
+
+    fn end(self) -> Result<()> {
+        Compound::end(self)
+    }
+}
+
+impl<W: io::Write> ser::SerializeStruct for Compound<'_, W> {
+    type Ok = ();
+    type Error = Error;
+
+    fn serialize_field<T>(&mut self, key: &'static str, value: &T) -> Result<()>
+    where
+        T: ?Sized + ser::Serialize,
+    {
+        self.named_field(key, value)
+    }
+
+    fn end(self) -> Result<()> {
+        Compound::end(self)
+    }
+}
+
+impl<W: io::Write> ser::SerializeStructVariant for Compound<'_, W> {
+    type Ok = ();
+    type Error = Error;
+
+    fn serialize_field<T>(&mut self, key: &'static str, value: &T) -> Result<()>
+    where
+        T: ?Sized + ser::Serialize,
+    {
+        self.named_field(key, value)
+    }
+
+    fn end(self) -> Result<()> {
+        Compound::end(self)
+    }
+}
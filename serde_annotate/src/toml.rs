@@ -0,0 +1,681 @@
+use crate::color::{ColorProfile, PaintExt};
+use crate::document::{Document, StrFormat};
+use crate::error::Error;
+use crate::integer::{Base, Int};
+use std::fmt;
+
+type Result<T> = std::result::Result<T, Error>;
+
+pub struct Toml {
+    document: Document,
+    indent: usize,
+    color: ColorProfile,
+}
+
+impl Toml {
+    pub fn indent(mut self, i: usize) -> Self {
+        self.indent = i;
+        self
+    }
+    pub fn color(mut self, c: ColorProfile) -> Self {
+        self.color = c;
+        self
+    }
+}
+
+impl fmt::Display for Toml {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        let mut emitter = TomlEmitter {
+            level: 0,
+            indent: self.indent,
+            color: self.color,
+            compact: false,
+            started: false,
+        };
+        emitter
+            .emit_document(f, &self.document)
+            .map_err(|_| fmt::Error)
+    }
+}
+
+impl Document {
+    pub fn to_toml(self) -> Toml {
+        Toml {
+            document: self,
+            indent: 2,
+            color: ColorProfile::default(),
+        }
+    }
+}
+
+// How a value is represented in a TOML table.
+#[derive(Clone, Copy, PartialEq)]
+enum Kind {
+    // A `key = value` pair.
+    Value,
+    // A `[table]` section.
+    Table,
+    // A sequence of `[[table]]` sections.
+    TableArray,
+    // A value TOML cannot express (ie: null).
+    Skip,
+}
+
+pub struct TomlEmitter {
+    level: usize,
+    indent: usize,
+    color: ColorProfile,
+    compact: bool,
+    started: bool,
+}
+
+impl TomlEmitter {
+    const SPACE: &'static str = "                                                                                                    ";
+
+    fn kind(value: &Document) -> Result<Kind> {
+        Ok(match value {
+            Document::Mapping(_) => Kind::Table,
+            Document::Sequence(s)
+                if !s.is_empty()
+                    && s.iter().all(|v| {
+                        matches!(v.as_value(), Ok(Document::Mapping(_)))
+                            && !matches!(v, Document::Compact(_))
+                    }) =>
+            {
+                Kind::TableArray
+            }
+            Document::Null => Kind::Skip,
+            Document::Fragment(_) => Self::kind(value.as_value()?)?,
+            _ => Kind::Value,
+        })
+    }
+
+    // Splits a kvpair fragment into its leading comments, key, value and
+    // trailing comments.
+    #[allow(clippy::type_complexity)]
+    fn split_kv(frag: &Document) -> Result<(Vec<&Document>, &Document, &Document, Vec<&Document>)> {
+        let nodes = frag.entry_nodes()?;
+        let values = nodes
+            .iter()
+            .enumerate()
+            .filter(|(_, n)| n.has_value())
+            .map(|(i, _)| i)
+            .collect::<Vec<_>>();
+        let [k, v] = values[..] else {
+            return Err(Error::StructureError("kvpair", "not two elements"));
+        };
+        Ok((
+            nodes[..k].to_vec(),
+            nodes[k],
+            nodes[v],
+            nodes[v + 1..].to_vec(),
+        ))
+    }
+
+    fn emit_document<W: fmt::Write>(&mut self, w: &mut W, node: &Document) -> Result<()> {
+        match node {
+            Document::Mapping(m) => self.emit_table(w, &mut Vec::new(), m),
+            Document::Compact(d) => self.emit_document(w, d),
+            Document::Fragment(ds) => {
+                for d in ds {
+                    if d.comment().is_some() {
+                        self.emit_comments(w, [d])?;
+                    } else {
+                        self.emit_document(w, d)?;
+                    }
+                }
+                Ok(())
+            }
+            _ => Err(Error::StructureError("Mapping", node.variant())),
+        }
+    }
+
+    fn newline<W: fmt::Write>(&mut self, w: &mut W) -> Result<()> {
+        if self.started {
+            writeln!(w)?;
+        }
+        self.started = true;
+        Ok(())
+    }
+
+    fn emit_comments<'d, W: fmt::Write>(
+        &mut self,
+        w: &mut W,
+        nodes: impl IntoIterator<Item = &'d Document>,
+    ) -> Result<()> {
+        for (c, _) in nodes.into_iter().filter_map(Document::comment) {
+            for line in c.split('\n') {
+                self.newline(w)?;
+                self.emit_comment_line(w, line)?;
+            }
+        }
+        Ok(())
+    }
+
+    // Emits comments following a value on the same line.
+    fn emit_trailing<'d, W: fmt::Write>(
+        &mut self,
+        w: &mut W,
+        nodes: impl IntoIterator<Item = &'d Document>,
+    ) -> Result<()> {
+        let mut first = true;
+        for (c, _) in nodes.into_iter().filter_map(Document::comment) {
+            for line in c.split('\n') {
+                if first {
+                    write!(w, " ")?;
+                    first = false;
+                } else {
+                    self.newline(w)?;
+                    self.emit_indent(w)?;
+                }
+                self.emit_comment_line(w, line)?;
+            }
+        }
+        Ok(())
+    }
+
+    fn emit_comment_line<W: fmt::Write>(&mut self, w: &mut W, line: &str) -> Result<()> {
+        if line.is_empty() {
+            write!(w, "{}", self.color.comment.paint("#"))?;
+        } else {
+            write!(
+                w,
+                "{}",
+                self.color.comment.paint(format_args!("# {}", line))
+            )?;
+        }
+        Ok(())
+    }
+
+    fn emit_table<W: fmt::Write>(
+        &mut self,
+        w: &mut W,
+        path: &mut Vec<String>,
+        entries: &[Document],
+    ) -> Result<()> {
+        // TOML requires all of a table's key/value pairs to precede any
+        // sub-tables.
+        for frag in entries {
+            let (pre, key, value, post) = Self::split_kv(frag)?;
+            if Self::kind(value)? != Kind::Value {
+                continue;
+            }
+            self.emit_comments(w, pre)?;
+            self.newline(w)?;
+            self.emit_key(w, key)?;
+            write!(w, " {} ", self.color.punctuation.paint("="))?;
+            self.emit_value(w, value)?;
+            self.emit_trailing(w, post)?;
+        }
+        for frag in entries {
+            let (pre, key, value, post) = Self::split_kv(frag)?;
+            match Self::kind(value)? {
+                Kind::Table => {
+                    path.push(Self::key_text(key)?);
+                    let table = value.as_value()?;
+                    let m = match table {
+                        Document::Mapping(m) => m,
+                        _ => return Err(Error::StructureError("Mapping", table.variant())),
+                    };
+                    // A table holding only other tables can be defined implicitly.
+                    let implicit = pre.is_empty()
+                        && post.is_empty()
+                        && !m.is_empty()
+                        && m.iter().all(|kv| {
+                            Self::split_kv(kv)
+                                .and_then(|(_, _, v, _)| Self::kind(v))
+                                .map(|k| k != Kind::Value)
+                                .unwrap_or(false)
+                        });
+                    if !implicit {
+                        self.emit_header(w, path, false, pre, post)?;
+                    }
+                    self.emit_table(w, path, m)?;
+                    path.pop();
+                }
+                Kind::TableArray => {
+                    path.push(Self::key_text(key)?);
+                    if let Document::Sequence(s) = value.as_value()? {
+                        for (i, elem) in s.iter().enumerate() {
+                            let (epre, epost) = match elem {
+                                Document::Fragment(nodes) => {
+                                    let v = Document::last_value_index(nodes);
+                                    (&nodes[..v], &nodes[v + 1..])
+                                }
+                                _ => (&[][..], &[][..]),
+                            };
+                            // The comments on the sequence itself go with the
+                            // first element.
+                            let (pre, post): (&[&Document], &[&Document]) =
+                                if i == 0 { (&pre, &post) } else { (&[], &[]) };
+                            let pre = pre.iter().copied().chain(epre);
+                            self.emit_header(w, path, true, pre, post.iter().copied())?;
+                            self.emit_trailing(w, epost)?;
+                            if let Document::Mapping(m) = elem.as_value()? {
+                                self.emit_table(w, path, m)?;
+                            }
+                        }
+                    }
+                    path.pop();
+                }
+                _ => {}
+            }
+        }
+        Ok(())
+    }
+
+    fn emit_header<'d, W: fmt::Write>(
+        &mut self,
+        w: &mut W,
+        path: &[String],
+        array: bool,
+        pre: impl IntoIterator<Item = &'d Document>,
+        post: impl IntoIterator<Item = &'d Document>,
+    ) -> Result<()> {
+        if self.started {
+            writeln!(w)?;
+        }
+        self.emit_comments(w, pre)?;
+        self.newline(w)?;
+        let (open, close) = if array { ("[[", "]]") } else { ("[", "]") };
+        write!(w, "{}", self.color.aggregate.paint(open))?;
+        for (i, k) in path.iter().enumerate() {
+            if i > 0 {
+                write!(w, "{}", self.color.punctuation.paint("."))?;
+            }
+            self.emit_key_str(w, k)?;
+        }
+        write!(w, "{}", self.color.aggregate.paint(close))?;
+        self.emit_trailing(w, post)
+    }
+
+    fn key_text(key: &Document) -> Result<String> {
+        match key.as_value()? {
+            Document::String(s, _) => Ok(s.clone()),
+            Document::StaticStr(s, _) => Ok(s.to_string()),
+            Document::Int(i) => Ok(i.to_string()),
+            Document::Boolean(b) => Ok(b.to_string()),
+            _ => Err(Error::KeyTypeError(key.variant())),
+        }
+    }
+
+    fn emit_key<W: fmt::Write>(&mut self, w: &mut W, key: &Document) -> Result<()> {
+        self.emit_key_str(w, &Self::key_text(key)?)
+    }
+
+    fn emit_key_str<W: fmt::Write>(&mut self, w: &mut W, key: &str) -> Result<()> {
+        if !key.is_empty()
+            && key
+                .bytes()
+                .all(|b| b.is_ascii_alphanumeric() || b == b'_' || b == b'-')
+        {
+            write!(w, "{}", self.color.key.paint(key))?;
+        } else {
+            self.escape_str(w, key, false)?;
+        }
+        Ok(())
+    }
+
+    fn emit_value<W: fmt::Write>(&mut self, w: &mut W, node: &Document) -> Result<()> {
+        match node {
+            Document::String(v, f) => self.emit_string(w, v.as_str(), *f),
+            Document::StaticStr(v, f) => self.emit_string(w, v, *f),
+            Document::Boolean(v) => self.emit_boolean(w, *v),
+            Document::Int(v) => self.emit_int(w, v),
+            Document::Float(v) => self.emit_float(w, *v),
+            Document::Mapping(m) => self.emit_inline_table(w, m),
+            Document::Sequence(s) => self.emit_array(w, s),
+            Document::Bytes(v) => self.emit_bytes(w, v),
+            Document::Compact(d) => {
+                let compact = self.compact;
+                self.compact = true;
+                let result = self.emit_value(w, d);
+                self.compact = compact;
+                result
+            }
+            Document::Fragment(_) => self.emit_value(w, node.as_value()?),
+            Document::Null | Document::Comment(_, _) => {
+                Err(Error::StructureError("a value", node.variant()))
+            }
+        }
+    }
+
+    fn emit_inline_table<W: fmt::Write>(&mut self, w: &mut W, mapping: &[Document]) -> Result<()> {
+        let compact = self.compact;
+        self.compact = true;
+        write!(w, "{}", self.color.aggregate.paint("{"))?;
+        let mut first = true;
+        for frag in mapping {
+            let (key, value) = frag.as_kv()?;
+            if Self::kind(value)? == Kind::Skip {
+                continue;
+            }
+            if !first {
+                write!(w, "{}", self.color.punctuation.paint(","))?;
+            }
+            first = false;
+            write!(w, " ")?;
+            self.emit_key(w, key)?;
+            write!(w, " {} ", self.color.punctuation.paint("="))?;
+            self.emit_value(w, value)?;
+        }
+        if !first {
+            write!(w, " ")?;
+        }
+        write!(w, "{}", self.color.aggregate.paint("}"))?;
+        self.compact = compact;
+        Ok(())
+    }
+
+    fn emit_array<W: fmt::Write>(&mut self, w: &mut W, sequence: &[Document]) -> Result<()> {
+        let multiline = !self.compact
+            && sequence.iter().any(
+                |v| matches!(v, Document::Fragment(f) if f.iter().any(|n| n.comment().is_some())),
+            );
+        write!(w, "{}", self.color.aggregate.paint("["))?;
+        if multiline {
+            self.level += 1;
+            for value in sequence {
+                let nodes = match value {
+                    Document::Fragment(f) => f.as_slice(),
+                    _ => std::slice::from_ref(value),
+                };
+                let v = Document::last_value_index(nodes);
+                for (c, _) in nodes[..v].iter().filter_map(Document::comment) {
+                    for line in c.split('\n') {
+                        writeln!(w)?;
+                        self.emit_indent(w)?;
+                        self.emit_comment_line(w, line)?;
+                    }
+                }
+                if v < nodes.len() {
+                    writeln!(w)?;
+                    self.emit_indent(w)?;
+                    self.emit_value(w, &nodes[v])?;
+                    write!(w, "{}", self.color.punctuation.paint(","))?;
+                    self.emit_trailing(w, &nodes[v + 1..])?;
+                }
+            }
+            self.level -= 1;
+            writeln!(w)?;
+            self.emit_indent(w)?;
+        } else {
+            for (i, value) in sequence.iter().enumerate() {
+                if i > 0 {
+                    write!(w, "{} ", self.color.punctuation.paint(","))?;
+                }
+                self.emit_value(w, value)?;
+            }
+        }
+        write!(w, "{}", self.color.aggregate.paint("]"))?;
+        Ok(())
+    }
+
+    fn emit_bytes<W: fmt::Write>(&mut self, w: &mut W, bytes: &[u8]) -> Result<()> {
+        write!(w, "{}", self.color.aggregate.paint("["))?;
+        if self.compact {
+            for (i, b) in bytes.iter().enumerate() {
+                if i > 0 {
+                    write!(w, "{} ", self.color.punctuation.paint(","))?;
+                }
+                write!(
+                    w,
+                    "{}",
+                    self.color.integer.paint(format_args!("0x{:02X}", b))
+                )?;
+            }
+        } else {
+            self.level += 1;
+            for chunk in bytes.chunks(16) {
+                writeln!(w)?;
+                self.emit_indent(w)?;
+                for (i, b) in chunk.iter().enumerate() {
+                    if i > 0 {
+                        write!(w, " ")?;
+                    }
+                    write!(
+                        w,
+                        "{}",
+                        self.color.integer.paint(format_args!("0x{:02X}", b))
+                    )?;
+                    write!(w, "{}", self.color.punctuation.paint(","))?;
+                }
+            }
+            self.level -= 1;
+            writeln!(w)?;
+            self.emit_indent(w)?;
+        }
+        write!(w, "{}", self.color.aggregate.paint("]"))?;
+        Ok(())
+    }
+
+    fn emit_string<W: fmt::Write>(&mut self, w: &mut W, value: &str, f: StrFormat) -> Result<()> {
+        match f {
+            StrFormat::Multiline if !self.compact => self.emit_string_multiline(w, value)?,
+            _ => self.escape_str(w, value, false)?,
+        }
+        Ok(())
+    }
+
+    fn emit_string_multiline<W: fmt::Write>(&mut self, w: &mut W, value: &str) -> Result<()> {
+        // Prefer a literal string since it needs no escaping.
+        let literal = !value.contains("'''")
+            && !value.ends_with('\'')
+            && !value
+                .chars()
+                .any(|c| c != '\n' && c != '\t' && (c.is_ascii_control()));
+        let quote = if literal { "'''" } else { "\"\"\"" };
+        writeln!(w, "{}", self.color.punctuation.paint(quote))?;
+        if literal {
+            write!(w, "{}", self.color.string.paint(value))?;
+        } else {
+            self.escape_str(w, value, true)?;
+        }
+        write!(w, "{}", self.color.punctuation.paint(quote))?;
+        Ok(())
+    }
+
+    fn emit_boolean<W: fmt::Write>(&mut self, w: &mut W, b: bool) -> Result<()> {
+        write!(w, "{}", self.color.boolean.paint(b))?;
+        Ok(())
+    }
+
+    fn emit_int<W: fmt::Write>(&mut self, w: &mut W, i: &Int) -> Result<()> {
+        // TOML only permits non-negative integers in non-decimal bases.
+        if i.is_negative() {
+            write!(
+                w,
+                "{}",
+                self.color.integer.paint(i.format(Some(&Base::Dec)))
+            )?;
+        } else {
+            write!(w, "{}", self.color.integer.paint(i))?;
+        }
+        Ok(())
+    }
+
+    fn emit_float<W: fmt::Write>(&mut self, w: &mut W, f: f64) -> Result<()> {
+        if f.is_nan() {
+            write!(w, "{}", self.color.float.paint("nan"))?;
+        } else if f.is_infinite() {
+            let v = if f.is_sign_negative() { "-inf" } else { "inf" };
+            write!(w, "{}", self.color.float.paint(v))?;
+        } else {
+            // The debug representation always includes a decimal point or exponent.
+            write!(w, "{}", self.color.float.paint(format_args!("{:?}", f)))?;
+        }
+        Ok(())
+    }
+
+    fn emit_indent<W: fmt::Write>(&mut self, w: &mut W) -> Result<()> {
+        let mut len = self.level * self.indent;
+        while len > 0 {
+            let chunk = std::cmp::min(len, Self::SPACE.len());
+            write!(w, "{}", &Self::SPACE[..chunk])?;
+            len -= chunk;
+        }
+        Ok(())
+    }
+
+    // Writes a basic string.  In a multiline string, newlines are not escaped
+    // and the surrounding quotes are written by the caller.
+    fn escape_str<W: fmt::Write>(
+        &self,
+        wr: &mut W,
+        v: &str,
+        multiline: bool,
+    ) -> std::result::Result<(), fmt::Error> {
+        if !multiline {
+            write!(wr, "{}", self.color.punctuation.paint("\""))?;
+        }
+        let mut start = 0;
+        for (i, ch) in v.char_indices() {
+            let escaped = match ch {
+                '"' => "\\\"".to_string(),
+                '\\' => "\\\\".to_string(),
+                '\x08' => "\\b".to_string(),
+                '\t' => "\\t".to_string(),
+                '\n' if multiline => continue,
+                '\n' => "\\n".to_string(),
+                '\x0c' => "\\f".to_string(),
+                '\r' => "\\r".to_string(),
+                '\x00'..='\x1f' | '\x7f' => format!("\\u{:04x}", ch as u32),
+                _ => continue,
+            };
+            if start < i {
+                write!(wr, "{}", self.color.string.paint(&v[start..i]))?;
+            }
+            write!(wr, "{}", self.color.escape.paint(escaped))?;
+            start = i + 1;
+        }
+        if start != v.len() {
+            write!(wr, "{}", self.color.string.paint(&v[start..]))?;
+        }
+        if !multiline {
+            write!(wr, "{}", self.color.punctuation.paint("\""))?;
+        }
+        Ok(())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::document::CommentFormat;
+
+    fn int(v: i32) -> Document {
+        Document::Int(Int::new(v, Base::Dec))
+    }
+    fn hex(v: u32) -> Document {
+        Document::Int(Int::new(v, Base::Hex))
+    }
+    fn string(v: &str) -> Document {
+        Document::String(v.into(), StrFormat::Standard)
+    }
+    fn comment(v: &str) -> Document {
+        Document::Comment(v.into(), CommentFormat::Standard)
+    }
+    fn kv(k: &str, v: Document) -> Document {
+        Document::Fragment(vec![string(k), v])
+    }
+
+    #[test]
+    fn basic_document() {
+        let doc = Document::Fragment(vec![
+            comment("A manifest"),
+            Document::Mapping(vec![
+                Document::Fragment(vec![comment("The name"), string("name"), string("fw")]),
+                kv(
+                    "server",
+                    Document::Mapping(vec![
+                        kv("addr", hex(0x1000)),
+                        Document::Fragment(vec![
+                            string("port"),
+                            int(-1),
+                            comment("Not yet assigned"),
+                        ]),
+                    ]),
+                ),
+                kv("ratio", Document::Float(1.0)),
+                kv("null", Document::Null),
+                kv(
+                    "image",
+                    Document::Sequence(vec![
+                        Document::Mapping(vec![kv("offset", hex(0))]),
+                        Document::Mapping(vec![kv(
+                            "point",
+                            Document::Compact(Document::Mapping(vec![kv("x", int(1))]).into()),
+                        )]),
+                    ]),
+                ),
+                kv("list", Document::Sequence(vec![int(1), string("two")])),
+            ]),
+        ]);
+        let toml = doc.to_toml().to_string();
+        assert_eq!(
+            toml,
+            r#"# A manifest
+# The name
+name = "fw"
+ratio = 1.0
+list = [1, "two"]
+
+[server]
+addr = 0x1000
+port = -1 # Not yet assigned
+
+[[image]]
+offset = 0x0
+
+[[image]]
+point = { x = 1 }"#
+        );
+    }
+
+    #[test]
+    fn strings() {
+        let doc = Document::Mapping(vec![
+            kv("a key", string("quote\" and\ttab")),
+            kv(
+                "lines",
+                Document::String("one\ntwo\n".into(), StrFormat::Multiline),
+            ),
+            kv(
+                "escapes",
+                Document::String("\x01\n".into(), StrFormat::Multiline),
+            ),
+        ]);
+        let toml = doc.to_toml().to_string();
+        assert_eq!(
+            toml,
+            r#""a key" = "quote\" and\ttab"
+lines = '''
+one
+two
+'''
+escapes = """
+\u0001
+""""#
+        );
+    }
+
+    #[test]
+    fn commented_array() {
+        let doc = Document::Mapping(vec![kv(
+            "list",
+            Document::Sequence(vec![
+                Document::Fragment(vec![comment("first"), int(1)]),
+                Document::Fragment(vec![int(2), comment("second")]),
+            ]),
+        )]);
+        let toml = doc.to_toml().to_string();
+        assert_eq!(
+            toml,
+            r#"list = [
+  # first
+  1,
+  2, # second
+]"#
+        );
+    }
+}
@@ -0,0 +1,799 @@
+// Parser for TOML documents.
+use std::borrow::Cow;
+use std::collections::HashSet;
+
+use crate::document::{CommentFormat, Document, StrFormat};
+use crate::error::Error;
+use crate::integer::Int;
+use crate::relax::Relax;
+
+type Result<T> = std::result::Result<T, Error>;
+
+/// `TomlParser` parses TOML documents into a `Document`.
+///
+/// Tables and arrays of tables become nested mappings and sequences of
+/// mappings, inline tables become compact mappings and `#` comments are
+/// attached to the neighboring key/value pairs, table headers and array
+/// elements.  Integers remember the base they were written in.
+///
+/// Date and time values are kept as strings.
+pub struct TomlParser<'a> {
+    text: &'a str,
+    pos: usize,
+}
+
+impl<'a> TomlParser<'a> {
+    /// Parses a string into a `Document`.
+    pub fn parse(text: &str) -> Result<Document> {
+        let text = if text.contains('\r') {
+            Cow::Owned(text.replace("\r\n", "\n"))
+        } else {
+            Cow::Borrowed(text)
+        };
+        let mut parser = TomlParser {
+            text: &text,
+            pos: 0,
+        };
+        parser.parse_document()
+    }
+
+    fn rest(&self) -> &'a str {
+        &self.text[self.pos..]
+    }
+
+    fn peek(&self) -> Option<char> {
+        self.rest().chars().next()
+    }
+
+    fn at_end(&self) -> bool {
+        self.pos >= self.text.len()
+    }
+
+    fn line_end(&self) -> usize {
+        self.rest()
+            .find('\n')
+            .map(|i| self.pos + i)
+            .unwrap_or(self.text.len())
+    }
+
+    fn skip_space(&mut self) {
+        while matches!(self.peek(), Some(' ') | Some('\t')) {
+            self.pos += 1;
+        }
+    }
+
+    fn expect(&mut self, token: &str) -> Result<()> {
+        self.skip_space();
+        if self.rest().starts_with(token) {
+            self.pos += token.len();
+            self.skip_space();
+            Ok(())
+        } else {
+            Err(self.error(&format!("expected `{}`", token)))
+        }
+    }
+
+    fn error(&self, msg: &str) -> Error {
+        self.error_at(self.pos, msg)
+    }
+
+    fn error_at(&self, pos: usize, msg: &str) -> Error {
+        let start = self.text[..pos].rfind('\n').map_or(0, |i| i + 1);
+        let end = self.text[pos..]
+            .find('\n')
+            .map_or(self.text.len(), |i| pos + i);
+        let line = self.text[..pos].matches('\n').count() + 1;
+        let col = self.text[start..pos].chars().count() + 1;
+        Error::SyntaxError(
+            msg.into(),
+            line,
+            col,
+            self.text[start..end].trim_end().into(),
+            "^",
+        )
+    }
+
+    // Converts a group of `#` comment lines into a comment node.
+    fn comment(lines: &[&str]) -> Document {
+        let lines = lines.iter().map(|s| s.trim()).collect::<Vec<_>>();
+        let lines = Relax::strip_leading_prefix(&lines, '#');
+        let lines = Relax::strip_leading_prefix(&lines, ' ');
+        Document::Comment(lines.join("\n"), CommentFormat::Hash)
+    }
+
+    // Skips whitespace, line breaks and comments.  Consecutive comment lines
+    // are gathered into a single comment node.
+    fn comments(&mut self) -> Vec<Document> {
+        let mut result = Vec::new();
+        let mut lines = Vec::new();
+        let mut blank = true;
+        loop {
+            self.skip_space();
+            match self.peek() {
+                Some('#') => {
+                    let end = self.line_end();
+                    lines.push(&self.text[self.pos..end]);
+                    self.pos = end;
+                    blank = false;
+                }
+                Some('\n') => {
+                    if blank && !lines.is_empty() {
+                        result.push(Self::comment(&lines));
+                        lines.clear();
+                    }
+                    self.pos += 1;
+                    blank = true;
+                }
+                _ => break,
+            }
+        }
+        if !lines.is_empty() {
+            result.push(Self::comment(&lines));
+        }
+        result
+    }
+
+    // Consumes the remainder of the current line, returning the trailing
+    // comment if there is one.
+    fn trailing(&mut self) -> Result<Option<Document>> {
+        self.skip_space();
+        let comment = if self.peek() == Some('#') {
+            let end = self.line_end();
+            let line = &self.text[self.pos..end];
+            self.pos = end;
+            Some(Self::comment(&[line]))
+        } else {
+            None
+        };
+        match self.peek() {
+            None => {}
+            Some('\n') => self.pos += 1,
+            _ => return Err(self.error("expected a newline")),
+        }
+        Ok(comment)
+    }
+
+    fn key_is(kv: &Document, name: &str) -> bool {
+        matches!(kv.as_kv(), Ok((k, _)) if k.as_str().ok() == Some(name))
+    }
+
+    // Returns the entries of the table named by `keys`, relative to
+    // `entries`, implicitly creating any missing tables.  A key naming an
+    // array of tables refers to the last table in the array.
+    fn table_mut<'d>(
+        &self,
+        mut entries: &'d mut Vec<Document>,
+        keys: &[Document],
+    ) -> Result<&'d mut Vec<Document>> {
+        for key in keys {
+            let name = key.as_str()?;
+            let index = match entries.iter().position(|kv| Self::key_is(kv, name)) {
+                Some(i) => i,
+                None => {
+                    entries.push(Document::Fragment(vec![
+                        key.clone(),
+                        Document::Mapping(vec![]),
+                    ]));
+                    entries.len() - 1
+                }
+            };
+            let (_, value) = entries[index].as_kv_mut()?;
+            entries = match value {
+                Document::Mapping(m) => m,
+                // Only the elements of an array of tables are bare mappings;
+                // the inline tables of a static array are compact.
+                Document::Sequence(s) => match s.last_mut() {
+                    Some(Document::Mapping(m)) => m,
+                    Some(Document::Fragment(nodes)) => {
+                        match nodes.iter_mut().find_map(|n| match n {
+                            Document::Mapping(m) => Some(m),
+                            _ => None,
+                        }) {
+                            Some(m) => m,
+                            None => return Err(self.error(&format!("`{}` is not a table", name))),
+                        }
+                    }
+                    _ => return Err(self.error(&format!("`{}` is not a table", name))),
+                },
+                _ => return Err(self.error(&format!("`{}` is not a table", name))),
+            };
+        }
+        Ok(entries)
+    }
+
+    fn parse_document(&mut self) -> Result<Document> {
+        let mut root = Vec::new();
+        let mut current = Vec::new();
+        let mut defined = HashSet::new();
+        // The arrays of tables and the tables created by dotted keys.
+        let mut arrays = HashSet::new();
+        let mut dotted = HashSet::new();
+        let tail = loop {
+            let comments = self.comments();
+            if self.at_end() {
+                break comments;
+            }
+            let start = self.pos;
+            if self.peek() == Some('[') {
+                let array = self.rest().starts_with("[[");
+                self.expect(if array { "[[" } else { "[" })?;
+                let keys = self.parse_keys()?;
+                self.expect(if array { "]]" } else { "]" })?;
+                let post = self.trailing()?;
+                let name = keys
+                    .iter()
+                    .map(|k| k.as_str().map(String::from))
+                    .collect::<Result<Vec<_>>>()?;
+                let (last, parents) = keys.split_last().unwrap();
+                let parent = self.table_mut(&mut root, parents)?;
+                let index = parent
+                    .iter()
+                    .position(|kv| Self::key_is(kv, &name[parents.len()]));
+                if array {
+                    let mut elem = comments;
+                    elem.push(Document::Mapping(vec![]));
+                    elem.extend(post);
+                    let elem = if elem.len() == 1 {
+                        elem.pop().unwrap()
+                    } else {
+                        Document::Fragment(elem)
+                    };
+                    match index {
+                        Some(i) => match parent[i].as_kv_mut()?.1 {
+                            Document::Sequence(s) if arrays.contains(&name) => s.push(elem),
+                            Document::Sequence(_) => {
+                                return Err(self.error_at(start, "cannot append to a static array"))
+                            }
+                            _ => return Err(self.error_at(start, "key is already defined")),
+                        },
+                        None => parent.push(Document::Fragment(vec![
+                            last.clone(),
+                            Document::Sequence(vec![elem]),
+                        ])),
+                    }
+                    // The tables beneath the new array element may be
+                    // defined again.
+                    defined.retain(|p: &Vec<String>| !p.starts_with(&name));
+                    arrays.retain(|p: &Vec<String>| !p.starts_with(&name));
+                    dotted.retain(|p: &Vec<String>| !p.starts_with(&name));
+                    arrays.insert(name);
+                } else {
+                    if dotted.contains(&name) {
+                        return Err(
+                            self.error_at(start, "table is already defined by a dotted key")
+                        );
+                    }
+                    if !defined.insert(name) {
+                        return Err(self.error_at(start, "table is defined twice"));
+                    }
+                    match index {
+                        Some(i) => {
+                            // The table was implicitly created by an earlier
+                            // header; attach this header's comments to it.
+                            if let Document::Fragment(nodes) = &mut parent[i] {
+                                if !matches!(nodes.last(), Some(Document::Mapping(_))) {
+                                    return Err(self.error_at(start, "key is already defined"));
+                                }
+                                nodes.splice(0..0, comments);
+                                nodes.extend(post);
+                            }
+                        }
+                        None => {
+                            let mut nodes = comments;
+                            nodes.push(last.clone());
+                            nodes.push(Document::Mapping(vec![]));
+                            nodes.extend(post);
+                            parent.push(Document::Fragment(nodes));
+                        }
+                    }
+                }
+                current = keys;
+            } else {
+                let keys = self.parse_keys()?;
+                self.expect("=")?;
+                let value = self.parse_value()?;
+                let post = self.trailing()?;
+                let (last, parents) = keys.split_last().unwrap();
+                let mut path = current
+                    .iter()
+                    .map(|k| k.as_str().map(String::from))
+                    .collect::<Result<Vec<_>>>()?;
+                for key in parents {
+                    path.push(key.as_str()?.into());
+                    dotted.insert(path.clone());
+                }
+                let table = self.table_mut(&mut root, &current)?;
+                let table = self.table_mut(table, parents)?;
+                if table
+                    .iter()
+                    .any(|kv| Self::key_is(kv, last.as_str().unwrap_or_default()))
+                {
+                    return Err(self.error_at(start, "key is defined twice"));
+                }
+                let mut nodes = comments;
+                nodes.push(last.clone());
+                nodes.push(value);
+                nodes.extend(post);
+                table.push(Document::Fragment(nodes));
+            }
+        };
+        let root = Document::Mapping(root);
+        if tail.is_empty() {
+            Ok(root)
+        } else {
+            let mut nodes = vec![root];
+            nodes.extend(tail);
+            Ok(Document::Fragment(nodes))
+        }
+    }
+
+    // Parses a possibly dotted key.
+    fn parse_keys(&mut self) -> Result<Vec<Document>> {
+        let mut keys = Vec::new();
+        loop {
+            self.skip_space();
+            let key = match self.peek() {
+                Some('"') => Document::String(self.basic_string()?, StrFormat::Quoted),
+                Some('\'') => Document::String(self.literal_string()?, StrFormat::Quoted),
+                _ => {
+                    let start = self.pos;
+                    while matches!(self.peek(), Some(c) if c.is_ascii_alphanumeric() || c == '_' || c == '-')
+                    {
+                        self.pos += 1;
+                    }
+                    if start == self.pos {
+                        return Err(self.error("expected a key"));
+                    }
+                    Document::String(self.text[start..self.pos].into(), StrFormat::Standard)
+                }
+            };
+            keys.push(key);
+            self.skip_space();
+            if self.peek() == Some('.') {
+                self.pos += 1;
+            } else {
+                break;
+            }
+        }
+        Ok(keys)
+    }
+
+    fn parse_value(&mut self) -> Result<Document> {
+        match self.peek() {
+            Some('"') if self.rest().starts_with("\"\"\"") => Ok(Document::String(
+                self.multiline_string("\"\"\"")?,
+                StrFormat::Multiline,
+            )),
+            Some('\'') if self.rest().starts_with("'''") => Ok(Document::String(
+                self.multiline_string("'''")?,
+                StrFormat::Multiline,
+            )),
+            Some('"') => Ok(Document::String(self.basic_string()?, StrFormat::Standard)),
+            Some('\'') => Ok(Document::String(
+                self.literal_string()?,
+                StrFormat::Standard,
+            )),
+            Some('[') => self.parse_array(),
+            Some('{') => Ok(Document::Compact(Box::new(self.parse_inline_table()?))),
+            _ => self.parse_scalar(),
+        }
+    }
+
+    fn parse_array(&mut self) -> Result<Document> {
+        let start = self.pos;
+        self.pos += 1;
+        let mut items = Vec::new();
+        let mut pending = self.comments();
+        loop {
+            if self.peek() == Some(']') {
+                self.pos += 1;
+                if !pending.is_empty() {
+                    items.push(Document::Fragment(pending));
+                }
+                break;
+            }
+            if self.at_end() {
+                return Err(self.error_at(start, "unterminated array"));
+            }
+            let mut nodes = std::mem::take(&mut pending);
+            nodes.push(self.parse_value()?);
+            // A comment on the same line as the element belongs to it.
+            self.skip_space();
+            let separator = self.peek() == Some(',');
+            if separator {
+                self.pos += 1;
+                self.skip_space();
+            }
+            if self.peek() == Some('#') {
+                let end = self.line_end();
+                nodes.push(Self::comment(&[&self.text[self.pos..end]]));
+                self.pos = end;
+            }
+            if nodes.len() == 1 {
+                items.push(nodes.pop().unwrap());
+            } else {
+                items.push(Document::Fragment(nodes));
+            }
+            pending = self.comments();
+            if !separator && self.peek() != Some(']') {
+                return Err(self.error("expected `,` or `]`"));
+            }
+        }
+        Ok(Document::Sequence(items))
+    }
+
+    fn parse_inline_table(&mut self) -> Result<Document> {
+        self.pos += 1;
+        let mut entries = Vec::new();
+        self.comments();
+        if self.peek() == Some('}') {
+            self.pos += 1;
+            return Ok(Document::Mapping(entries));
+        }
+        loop {
+            self.comments();
+            let start = self.pos;
+            let keys = self.parse_keys()?;
+            self.expect("=")?;
+            let value = self.parse_value()?;
+            let (last, parents) = keys.split_last().unwrap();
+            let table = self.table_mut(&mut entries, parents)?;
+            if table
+                .iter()
+                .any(|kv| Self::key_is(kv, last.as_str().unwrap_or_default()))
+            {
+                return Err(self.error_at(start, "key is defined twice"));
+            }
+            table.push(Document::Fragment(vec![last.clone(), value]));
+            self.comments();
+            match self.peek() {
+                Some(',') => self.pos += 1,
+                Some('}') => {
+                    self.pos += 1;
+                    break;
+                }
+                _ => return Err(self.error("expected `,` or `}`")),
+            }
+        }
+        Ok(Document::Mapping(entries))
+    }
+
+    fn is_integer(s: &str) -> bool {
+        fn digits(s: &str, f: fn(&u8) -> bool) -> bool {
+            !s.is_empty() && s.as_bytes().iter().all(f)
+        }
+        if let Some(s) = s.strip_prefix("0x") {
+            digits(s, u8::is_ascii_hexdigit)
+        } else if let Some(s) = s.strip_prefix("0o") {
+            digits(s, |b| (b'0'..=b'7').contains(b))
+        } else if let Some(s) = s.strip_prefix("0b") {
+            digits(s, |b| *b == b'0' || *b == b'1')
+        } else {
+            digits(s, u8::is_ascii_digit)
+        }
+    }
+
+    fn is_datetime(s: &str) -> bool {
+        let b = s.as_bytes();
+        let digits = |n: usize| b.len() > n && b[..n].iter().all(u8::is_ascii_digit);
+        (digits(4) && b[4] == b'-') || (digits(2) && b[2] == b':')
+    }
+
+    fn parse_scalar(&mut self) -> Result<Document> {
+        let start = self.pos;
+        let bytes = self.text.as_bytes();
+        let token_end = |mut i: usize| {
+            while i < bytes.len()
+                && !matches!(bytes[i], b' ' | b'\t' | b'\n' | b',' | b']' | b'}' | b'#')
+            {
+                i += 1;
+            }
+            i
+        };
+        let mut end = token_end(start);
+        // A datetime may separate the date and the time with a space.
+        if Self::is_datetime(&self.text[start..end])
+            && bytes.get(end) == Some(&b' ')
+            && Self::is_datetime(&self.text[end + 1..token_end(end + 1)])
+        {
+            end = token_end(end + 1);
+        }
+        let token = &self.text[start..end];
+        self.pos = end;
+        let doc = match token {
+            "true" => Document::Boolean(true),
+            "false" => Document::Boolean(false),
+            "inf" | "+inf" => Document::Float(f64::INFINITY),
+            "-inf" => Document::Float(f64::NEG_INFINITY),
+            "nan" | "+nan" | "-nan" => Document::Float(f64::NAN),
+            _ if Self::is_datetime(token) => Document::String(token.into(), StrFormat::Standard),
+            _ => {
+                let digits = token.replace('_', "");
+                let unsigned = digits.strip_prefix(['+', '-']).unwrap_or(&digits);
+                let signed = unsigned.len() < digits.len();
+                if signed && ["0x", "0o", "0b"].iter().any(|p| unsigned.starts_with(p)) {
+                    return Err(self.error_at(start, "non-decimal integers cannot be signed"));
+                }
+                if Self::is_integer(unsigned) {
+                    Document::Int(
+                        Int::from_str_radix(token, 0)
+                            .map_err(|_| self.error_at(start, "integer out of range"))?,
+                    )
+                } else if unsigned.starts_with(|c: char| c.is_ascii_digit()) {
+                    Document::Float(
+                        digits
+                            .parse::<f64>()
+                            .map_err(|_| self.error_at(start, "invalid number"))?,
+                    )
+                } else {
+                    return Err(self.error_at(start, "expected a value"));
+                }
+            }
+        };
+        Ok(doc)
+    }
+
+    fn hex_escape(&mut self, len: usize) -> Result<char> {
+        let digits = self.rest().get(..len).unwrap_or("");
+        let value = u32::from_str_radix(digits, 16)
+            .map_err(|_| self.error("bad unicode escape sequence"))?;
+        self.pos += len;
+        Ok(char::try_from(value)?)
+    }
+
+    // Decodes the escape sequence following a backslash.
+    fn escape(&mut self) -> Result<char> {
+        let esc = self
+            .peek()
+            .ok_or_else(|| self.error("unterminated string"))?;
+        self.pos += esc.len_utf8();
+        Ok(match esc {
+            'b' => '\x08',
+            't' => '\t',
+            'n' => '\n',
+            'f' => '\x0c',
+            'r' => '\r',
+            'e' => '\x1b',
+            '"' => '"',
+            '\\' => '\\',
+            'u' => self.hex_escape(4)?,
+            'U' => self.hex_escape(8)?,
+            _ => return Err(Error::EscapeError(esc)),
+        })
+    }
+
+    fn basic_string(&mut self) -> Result<String> {
+        let start = self.pos;
+        self.pos += 1;
+        let mut s = String::new();
+        loop {
+            match self.peek() {
+                None | Some('\n') => return Err(self.error_at(start, "unterminated string")),
+                Some('"') => {
+                    self.pos += 1;
+                    break;
+                }
+                Some('\\') => {
+                    self.pos += 1;
+                    s.push(self.escape()?);
+                }
+                Some(ch) => {
+                    self.pos += ch.len_utf8();
+                    s.push(ch);
+                }
+            }
+        }
+        Ok(s)
+    }
+
+    fn literal_string(&mut self) -> Result<String> {
+        let start = self.pos;
+        self.pos += 1;
+        let end = self.rest()[..self.line_end() - self.pos]
+            .find('\'')
+            .ok_or_else(|| self.error_at(start, "unterminated string"))?;
+        let s = self.rest()[..end].to_string();
+        self.pos += end + 1;
+        Ok(s)
+    }
+
+    // Parses a multiline basic (`"""`) or literal (`'''`) string.
+    fn multiline_string(&mut self, quote: &str) -> Result<String> {
+        let start = self.pos;
+        let literal = quote == "'''";
+        self.pos += quote.len();
+        // A newline immediately following the opening delimiter is trimmed.
+        if self.peek() == Some('\n') {
+            self.pos += 1;
+        }
+        let mut s = String::new();
+        loop {
+            if self.rest().starts_with(quote) {
+                // Up to two quotes may precede the closing delimiter.
+                let n = self.rest().len() - self.rest().trim_start_matches(&quote[..1]).len();
+                let n = std::cmp::min(n, 5);
+                s.push_str(&self.rest()[..n - 3]);
+                self.pos += n;
+                break;
+            }
+            match self.peek() {
+                None => return Err(self.error_at(start, "unterminated string")),
+                Some('\\') if !literal => {
+                    self.pos += 1;
+                    let save = self.pos;
+                    self.skip_space();
+                    if self.peek() == Some('\n') {
+                        // A line ending backslash trims all whitespace up to
+                        // the next non-whitespace character.
+                        while matches!(self.peek(), Some(' ' | '\t' | '\n')) {
+                            self.pos += 1;
+                        }
+                    } else {
+                        self.pos = save;
+                        s.push(self.escape()?);
+                    }
+                }
+                Some(ch) => {
+                    self.pos += ch.len_utf8();
+                    s.push(ch);
+                }
+            }
+        }
+        Ok(s)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use anyhow::{anyhow, Result};
+
+    fn get<'a>(doc: &'a Document, key: &str) -> Result<&'a Document> {
+        if let Document::Mapping(m) = doc.as_value()? {
+            for kv in m {
+                let (k, v) = kv.as_kv()?;
+                if k.as_str()? == key {
+                    return Ok(v);
+                }
+            }
+        }
+        Err(anyhow!("key {} not found in {:?}", key, doc))
+    }
+
+    #[test]
+    fn test_values() -> Result<()> {
+        let doc = TomlParser::parse(
+            r#"
+str = "tab\t\u00e9"
+lit = 'C:\path'
+hex = 0xdead_beef
+oct = 0o755
+bin = 0b1010
+dec = -1_000
+flt = 6.25e-1
+inf = -inf
+yes = true
+date = 1979-05-27 07:32:00Z
+ml = """
+one \
+  two"""
+"#,
+        )?;
+        assert_eq!(get(&doc, "str")?.as_str()?, "tab\t\u{e9}");
+        assert_eq!(get(&doc, "lit")?.as_str()?, "C:\\path");
+        match get(&doc, "hex")? {
+            Document::Int(i) => assert_eq!(i.to_string(), "0xDEAD_BEEF"),
+            _ => return Err(anyhow!("expected an Int")),
+        }
+        assert_eq!(u32::try_from(get(&doc, "oct")?)?, 0o755);
+        assert_eq!(u32::try_from(get(&doc, "bin")?)?, 10);
+        assert_eq!(i32::try_from(get(&doc, "dec")?)?, -1000);
+        assert_eq!(f64::try_from(get(&doc, "flt")?)?, 0.625);
+        assert_eq!(f64::try_from(get(&doc, "inf")?)?, f64::NEG_INFINITY);
+        assert!(bool::try_from(get(&doc, "yes")?)?);
+        assert_eq!(get(&doc, "date")?.as_str()?, "1979-05-27 07:32:00Z");
+        assert_eq!(get(&doc, "ml")?.as_str()?, "one two");
+        Ok(())
+    }
+
+    #[test]
+    fn test_tables() -> Result<()> {
+        let doc = TomlParser::parse(
+            r#"
+name = "fw"
+point = { x = 1, y.z = 2 }
+
+[server.main]
+port = 80
+
+[[image]]
+offset = 0
+
+[[image]]
+offset = 0x1000
+[image.flags]
+boot = true
+"#,
+        )?;
+        assert!(matches!(get(&doc, "point")?, Document::Compact(_)));
+        assert_eq!(u32::try_from(get(get(get(&doc, "point")?, "y")?, "z")?)?, 2);
+        let server = get(&doc, "server")?;
+        assert_eq!(u32::try_from(get(get(server, "main")?, "port")?)?, 80);
+        match get(&doc, "image")? {
+            Document::Sequence(s) => {
+                assert_eq!(s.len(), 2);
+                assert!(bool::try_from(get(get(&s[1], "flags")?, "boot")?)?);
+            }
+            _ => return Err(anyhow!("expected a Sequence")),
+        }
+        Ok(())
+    }
+
+    #[test]
+    fn test_comments() -> Result<()> {
+        let doc = TomlParser::parse(
+            r#"# leading
+a = 1 # trailing
+
+# table
+[t] # header
+list = [
+  # first
+  1,
+  2, # second
+]
+"#,
+        )?;
+        let Document::Mapping(m) = &doc else {
+            return Err(anyhow!("expected a mapping"));
+        };
+        let a = m[0].fragments()?;
+        assert_eq!(a[0].comment(), Some(("leading", &CommentFormat::Hash)));
+        assert_eq!(a[3].comment().unwrap().0, "trailing");
+        let t = m[1].fragments()?;
+        assert_eq!(t[0].comment().unwrap().0, "table");
+        assert_eq!(t[3].comment().unwrap().0, "header");
+        let Document::Sequence(s) = get(&doc, "t").and_then(|t| get(t, "list"))? else {
+            return Err(anyhow!("expected a sequence"));
+        };
+        assert_eq!(s[0].fragments()?[0].comment().unwrap().0, "first");
+        assert_eq!(s[1].fragments()?[1].comment().unwrap().0, "second");
+        Ok(())
+    }
+
+    #[test]
+    fn test_errors() -> Result<()> {
+        let err = TomlParser::parse("a = 1\na = 2").unwrap_err();
+        assert_eq!(
+            err.to_string(),
+            "syntax error: key is defined twice at 2:1\n| a = 2\n| ^"
+        );
+        assert!(TomlParser::parse("[a]\n[a]").is_err());
+        assert!(TomlParser::parse("a = [1 2]").is_err());
+        assert!(TomlParser::parse("a = 1 b = 2").is_err());
+        assert!(TomlParser::parse("a = 1\n[a.b]").is_err());
+        let err = TomlParser::parse("a = -0x10").unwrap_err();
+        assert_eq!(
+            err.to_string(),
+            "syntax error: non-decimal integers cannot be signed at 1:5\n| a = -0x10\n|     ^"
+        );
+        let err = TomlParser::parse("a = [{x = 1}]\n[[a]]").unwrap_err();
+        assert_eq!(
+            err.to_string(),
+            "syntax error: cannot append to a static array at 2:1\n| [[a]]\n| ^"
+        );
+        assert!(TomlParser::parse("a = [{x = 1}]\n[a.b]").is_err());
+        let err = TomlParser::parse("a.b = 1\n[a]").unwrap_err();
+        assert_eq!(
+            err.to_string(),
+            "syntax error: table is already defined by a dotted key at 2:1\n| [a]\n| ^"
+        );
+        assert!(TomlParser::parse("[t]\na.b = 1\n[t.a]").is_err());
+        // Sub-tables of a table defined by dotted keys may be added, and
+        // tables beneath a new array element may use dotted keys again.
+        TomlParser::parse("a.b = 1\n[a.c]")?;
+        TomlParser::parse("[[t]]\na.b = 1\n[[t]]\n[t.a]")?;
+        TomlParser::parse("[[t]]\n[[t.u]]\n[[t]]\n[[t.u]]")?;
+        Ok(())
+    }
+}
@@ -0,0 +1,678 @@
+// Validation of documents against JSON Schemas.
+use std::cmp::Ordering;
+use std::collections::HashMap;
+use std::fmt;
+
+use regex::Regex;
+
+use crate::de::path_to_string;
+use crate::doc_iter::DocPath;
+use crate::document::Document;
+use crate::error::Error;
+use crate::hexdump;
+use crate::integer::Int;
+use crate::schema::FORMAT_KEYWORD;
+
+type Result<T> = std::result::Result<T, Error>;
+
+/// A violation of a schema by a node of a document.
+#[derive(Clone, Debug)]
+pub struct Violation<'a> {
+    /// The object-path of the offending node.
+    pub path: Vec<DocPath<'a>>,
+    /// A description of the violation.
+    pub message: String,
+}
+
+impl fmt::Display for Violation<'_> {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        if self.path.is_empty() {
+            write!(f, "{}", self.message)
+        } else {
+            write!(f, "`{}`: {}", path_to_string(&self.path), self.message)
+        }
+    }
+}
+
+impl Document {
+    /// Validates this document against the JSON Schema `schema` and returns
+    /// all of the violations found.  An empty list means the document is
+    /// valid.
+    ///
+    /// The validator understands the following keywords: `$ref` (local
+    /// references only), `type`, `enum`, `const`, `allOf`, `anyOf`, `oneOf`,
+    /// `not`, `minimum`, `maximum`, `exclusiveMinimum`, `exclusiveMaximum`,
+    /// `multipleOf`, `minLength`, `maxLength`, `pattern`, `items`,
+    /// `prefixItems`, `minItems`, `maxItems`, `uniqueItems`, `properties`,
+    /// `required` and `additionalProperties`.  Other keywords are ignored.
+    ///
+    /// Values are interpreted the way the deserializer would interpret them:
+    /// integers compare numerically regardless of their base, strings holding
+    /// integers are accepted where an integer is expected, and `Bytes` nodes
+    /// are arrays of integers.  Where the schema's `x-format` keyword names a
+    /// bytes format (`hexstr`, `hexdump`, `xxd`, `base64`, `base64url`,
+    /// `base32`, `c_array`, `xxd_i`, `ihex` or `srec`), strings are decoded
+    /// as bytes before validation.
+    ///
+    /// An error is returned if the schema itself is malformed.
+    pub fn validate(&self, schema: &Document) -> Result<Vec<Violation<'_>>> {
+        let root = schema.as_value()?;
+        let mut validator = Validator {
+            root,
+            path: Vec::new(),
+            violations: Vec::new(),
+            refs: Vec::new(),
+            regexes: HashMap::new(),
+        };
+        validator.validate(self, root)?;
+        Ok(validator.violations)
+    }
+}
+
+fn schema_error(msg: impl Into<String>) -> Error {
+    Error::SchemaError(msg.into())
+}
+
+// Returns the value of the keyword `name` in a schema object.
+fn keyword<'s>(schema: &'s [Document], name: &str) -> Option<&'s Document> {
+    schema.iter().find_map(|entry| match entry.as_kv() {
+        Ok((k, v)) if k.as_str().ok() == Some(name) => v.as_value().ok(),
+        _ => None,
+    })
+}
+
+fn keyword_usize(schema: &[Document], name: &str) -> Result<Option<usize>> {
+    keyword(schema, name)
+        .map(|v| {
+            u64::try_from(v)
+                .map(|v| v as usize)
+                .map_err(|_| schema_error(format!("`{}` must be an integer", name)))
+        })
+        .transpose()
+}
+
+fn keyword_list<'s>(schema: &'s [Document], name: &str) -> Result<Option<Vec<&'s Document>>> {
+    match keyword(schema, name) {
+        None => Ok(None),
+        Some(Document::Sequence(s)) => {
+            Ok(Some(s.iter().filter_map(|v| v.as_value().ok()).collect()))
+        }
+        Some(_) => Err(schema_error(format!("`{}` must be an array", name))),
+    }
+}
+
+// Returns the JSON type name of a value.
+fn type_name(doc: &Document) -> &'static str {
+    match doc {
+        Document::Null => "null",
+        Document::Boolean(_) => "boolean",
+        Document::Int(_) => "integer",
+        Document::Float(_) => "number",
+        Document::String(..) | Document::StaticStr(..) => "string",
+        Document::Mapping(_) => "object",
+        Document::Sequence(_) | Document::Bytes(_) => "array",
+        _ => doc.variant(),
+    }
+}
+
+fn has_type(doc: &Document, name: &str) -> bool {
+    match (name, doc) {
+        ("integer", Document::Float(f)) => f.fract() == 0.0,
+        ("number", Document::Int(_)) => true,
+        _ => type_name(doc) == name,
+    }
+}
+
+// Returns whether `schema` explicitly accepts the type of `doc`.
+fn accepts_type(doc: &Document, schema: &Document) -> bool {
+    let Document::Mapping(m) = schema else {
+        return false;
+    };
+    match keyword(m, "type") {
+        Some(Document::Sequence(s)) => s
+            .iter()
+            .any(|t| t.as_str().map(|t| has_type(doc, t)).unwrap_or(false)),
+        Some(t) => t.as_str().map(|t| has_type(doc, t)).unwrap_or(false),
+        None => false,
+    }
+}
+
+// Renders a value for use in a violation message.
+fn render(doc: &Document) -> String {
+    match doc {
+        Document::Int(i) => i.to_string(),
+        _ => doc.clone().to_json().compact(true).to_string(),
+    }
+}
+
+fn compare(a: &Document, b: &Document) -> Option<Ordering> {
+    match (a, b) {
+        (Document::Int(a), Document::Int(b)) => Some(a.value().cmp(b.value())),
+        _ => f64::try_from(a).ok()?.partial_cmp(&f64::try_from(b).ok()?),
+    }
+}
+
+fn bytes_to_sequence(bytes: &[u8]) -> Document {
+    Document::Sequence(bytes.iter().map(|b| Document::Int((*b).into())).collect())
+}
+
+struct Validator<'a, 's> {
+    root: &'s Document,
+    path: Vec<DocPath<'a>>,
+    violations: Vec<Violation<'a>>,
+    // The targets of the references being followed and the nodes they are
+    // applied to.
+    refs: Vec<(&'s Document, &'a Document)>,
+    // The compiled regular expressions of the `pattern` keywords.
+    regexes: HashMap<&'s str, Regex>,
+}
+
+impl<'a, 's> Validator<'a, 's> {
+    fn report(&mut self, message: String) {
+        self.violations.push(Violation {
+            path: self.path.clone(),
+            message,
+        });
+    }
+
+    // Validates a document which doesn't live as long as the document
+    // being validated.  Such documents are scalars or sequences of scalars
+    // created by interpreting a value, so the only path elements they can
+    // contribute are indices.
+    fn validate_owned(&mut self, doc: &Document, schema: &'s Document) -> Result<()> {
+        let mut validator = Validator {
+            root: self.root,
+            path: Vec::new(),
+            violations: Vec::new(),
+            refs: Vec::new(),
+            regexes: std::mem::take(&mut self.regexes),
+        };
+        let result = validator.validate(doc, schema);
+        self.regexes = validator.regexes;
+        result?;
+        for v in validator.violations {
+            let mut path = self.path.clone();
+            path.extend(v.path.iter().filter_map(|p| match p {
+                DocPath::Index(i) => Some(DocPath::Index(*i)),
+                DocPath::Name(_) => None,
+            }));
+            self.violations.push(Violation {
+                path,
+                message: v.message,
+            });
+        }
+        Ok(())
+    }
+
+    // Validates `doc` against `schema`, returning the violations instead
+    // of recording them.
+    fn trial(&mut self, doc: &'a Document, schema: &'s Document) -> Result<Vec<Violation<'a>>> {
+        let saved = std::mem::take(&mut self.violations);
+        let result = self.validate(doc, schema);
+        let violations = std::mem::replace(&mut self.violations, saved);
+        result.map(|_| violations)
+    }
+
+    fn resolve(&self, reference: &str) -> Result<&'s Document> {
+        let pointer = reference
+            .strip_prefix('#')
+            .ok_or_else(|| schema_error(format!("unsupported reference `{}`", reference)))?;
+        self.root
+            .pointer(pointer)
+            .ok_or_else(|| schema_error(format!("unresolved reference `{}`", reference)))
+    }
+
+    fn validate(&mut self, doc: &'a Document, schema: &'s Document) -> Result<()> {
+        let doc = match doc.as_value() {
+            Ok(doc) => doc,
+            // Comments have no value to validate.
+            Err(_) => return Ok(()),
+        };
+        let keywords = match schema.as_value()? {
+            Document::Boolean(true) => return Ok(()),
+            Document::Boolean(false) => {
+                self.report("no value is permitted here".into());
+                return Ok(());
+            }
+            Document::Mapping(m) => m.as_slice(),
+            _ => return Err(schema_error("a schema must be an object or a boolean")),
+        };
+
+        let types = match keyword(keywords, "type") {
+            None => Vec::new(),
+            Some(Document::Sequence(s)) => s
+                .iter()
+                .map(Document::as_str)
+                .collect::<std::result::Result<Vec<_>, _>>()?,
+            Some(t) => vec![t.as_str()?],
+        };
+
+        // Interpret values the way the deserializer would.
+        match doc {
+            Document::Bytes(b) => return self.validate_owned(&bytes_to_sequence(b), schema),
+            Document::String(..) | Document::StaticStr(..) => {
+                let s = doc.as_str()?;
+                let format = keyword(keywords, FORMAT_KEYWORD).and_then(|f| f.as_str().ok());
+                if matches!(
+                    format,
+                    Some(
+                        "hexstr"
+                            | "hexdump"
+                            | "xxd"
+                            | "base64"
+                            | "base64url"
+                            | "base32"
+                            | "c_array"
+                            | "xxd_i"
+                            | "ihex"
+                            | "srec"
+                    )
+                ) {
+                    if let Ok(b) = hexdump::from_str(s) {
+                        return self.validate_owned(&bytes_to_sequence(&b), schema);
+                    }
+                }
+                let numeric = types.iter().any(|t| *t == "integer" || *t == "number");
+                if numeric && !types.contains(&"string") {
+                    if let Ok(i) = Int::from_str_radix(s, 0) {
+                        return self.validate_owned(&Document::Int(i), schema);
+                    }
+                }
+            }
+            _ => {}
+        }
+
+        if let Some(r) = keyword(keywords, "$ref") {
+            let r = r.as_str()?;
+            let target = self.resolve(r)?;
+            // Following a reference back to a schema which is already being
+            // applied to the same node would never terminate.
+            if self
+                .refs
+                .iter()
+                .any(|(t, d)| std::ptr::eq(*t, target) && std::ptr::eq(*d, doc))
+            {
+                return Err(schema_error(format!("circular reference `{}`", r)));
+            }
+            self.refs.push((target, doc));
+            let result = self.validate(doc, target);
+            self.refs.pop();
+            result?;
+        }
+        if !types.is_empty() && !types.iter().any(|t| has_type(doc, t)) {
+            self.report(format!(
+                "expected {} but got {}",
+                types.join(" or "),
+                type_name(doc)
+            ));
+            return Ok(());
+        }
+        if let Some(values) = keyword_list(keywords, "enum")? {
+            if !values.iter().any(|v| doc.value_eq(v)) {
+                let values = values.iter().map(|v| render(v)).collect::<Vec<_>>();
+                self.report(format!(
+                    "{} is not one of {}",
+                    render(doc),
+                    values.join(", ")
+                ));
+            }
+        }
+        if let Some(value) = keyword(keywords, "const") {
+            if !doc.value_eq(value) {
+                self.report(format!(
+                    "expected {} but got {}",
+                    render(value),
+                    render(doc)
+                ));
+            }
+        }
+        self.validate_combinators(doc, keywords)?;
+        match doc {
+            Document::Int(_) | Document::Float(_) => self.validate_number(doc, keywords)?,
+            Document::String(..) | Document::StaticStr(..) => {
+                self.validate_string(doc.as_str()?, keywords)?
+            }
+            Document::Sequence(s) => self.validate_array(s, keywords)?,
+            Document::Mapping(m) => self.validate_object(m, keywords)?,
+            _ => {}
+        }
+        Ok(())
+    }
+
+    fn validate_combinators(&mut self, doc: &'a Document, keywords: &'s [Document]) -> Result<()> {
+        if let Some(schemas) = keyword_list(keywords, "allOf")? {
+            for s in schemas {
+                self.validate(doc, s)?;
+            }
+        }
+        for name in ["anyOf", "oneOf"] {
+            let Some(schemas) = keyword_list(keywords, name)? else {
+                continue;
+            };
+            let mut failures = Vec::new();
+            let mut matches = 0;
+            for s in schemas.iter() {
+                let violations = self.trial(doc, s)?;
+                if violations.is_empty() {
+                    matches += 1;
+                } else {
+                    failures.push((s, violations));
+                }
+            }
+            if matches == 0 {
+                // If only one alternative accepts the type of the value or
+                // failed below this node, that's the alternative the author
+                // meant; report its violations.
+                let depth = self.path.len();
+                let mut deep = failures
+                    .into_iter()
+                    .filter(|(s, f)| accepts_type(doc, s) || f.iter().all(|v| v.path.len() > depth))
+                    .map(|(_, f)| f)
+                    .collect::<Vec<_>>();
+                if deep.len() == 1 {
+                    self.violations.append(&mut deep[0]);
+                } else {
+                    self.report(format!(
+                        "{} does not match any alternative of `{}`",
+                        render(doc),
+                        name
+                    ));
+                }
+            } else if name == "oneOf" && matches > 1 {
+                self.report(format!(
+                    "{} matches {} alternatives of `oneOf` but must match exactly one",
+                    render(doc),
+                    matches
+                ));
+            }
+        }
+        if let Some(s) = keyword(keywords, "not") {
+            if self.trial(doc, s)?.is_empty() {
+                self.report(format!(
+                    "{} must not match the schema of `not`",
+                    render(doc)
+                ));
+            }
+        }
+        Ok(())
+    }
+
+    fn validate_number(&mut self, doc: &Document, keywords: &[Document]) -> Result<()> {
+        // The keyword, the permitted orderings of the value relative to
+        // the limit and the description of a violation.
+        let checks = [
+            (
+                "minimum",
+                &[Ordering::Equal, Ordering::Greater][..],
+                "less than the minimum",
+            ),
+            (
+                "maximum",
+                &[Ordering::Less, Ordering::Equal][..],
+                "greater than the maximum",
+            ),
+            (
+                "exclusiveMinimum",
+                &[Ordering::Greater][..],
+                "not greater than",
+            ),
+            ("exclusiveMaximum", &[Ordering::Less][..], "not less than"),
+        ];
+        for (name, ok, msg) in checks {
+            if let Some(limit) = keyword(keywords, name) {
+                let order = compare(doc, limit)
+                    .ok_or_else(|| schema_error(format!("`{}` must be a number", name)))?;
+                if !ok.contains(&order) {
+                    self.report(format!("{} is {} {}", render(doc), msg, render(limit)));
+                }
+            }
+        }
+        if let Some(m) = keyword(keywords, "multipleOf") {
+            let m = f64::try_from(m).map_err(|_| schema_error("`multipleOf` must be a number"))?;
+            let v = f64::try_from(doc)?;
+            if m <= 0.0 {
+                return Err(schema_error("`multipleOf` must be greater than 0"));
+            }
+            if (v / m).fract() != 0.0 {
+                self.report(format!("{} is not a multiple of {}", render(doc), m));
+            }
+        }
+        Ok(())
+    }
+
+    fn validate_string(&mut self, s: &str, keywords: &'s [Document]) -> Result<()> {
+        let len = s.chars().count();
+        if let Some(min) = keyword_usize(keywords, "minLength")? {
+            if len < min {
+                self.report(format!("string is shorter than {} characters", min));
+            }
+        }
+        if let Some(max) = keyword_usize(keywords, "maxLength")? {
+            if len > max {
+                self.report(format!("string is longer than {} characters", max));
+            }
+        }
+        if let Some(pattern) = keyword(keywords, "pattern") {
+            let pattern = pattern.as_str()?;
+            if !self.regexes.contains_key(pattern) {
+                let re = Regex::new(pattern).map_err(|e| schema_error(e.to_string()))?;
+                self.regexes.insert(pattern, re);
+            }
+            if !self.regexes[pattern].is_match(s) {
+                self.report(format!("{:?} does not match the pattern {:?}", s, pattern));
+            }
+        }
+        Ok(())
+    }
+
+    fn validate_array(&mut self, seq: &'a [Document], keywords: &'s [Document]) -> Result<()> {
+        let items = seq.iter().filter(|v| v.has_value()).collect::<Vec<_>>();
+        if let Some(min) = keyword_usize(keywords, "minItems")? {
+            if items.len() < min {
+                self.report(format!(
+                    "expected at least {} items but got {}",
+                    min,
+                    items.len()
+                ));
+            }
+        }
+        if let Some(max) = keyword_usize(keywords, "maxItems")? {
+            if items.len() > max {
+                self.report(format!(
+                    "expected at most {} items but got {}",
+                    max,
+                    items.len()
+                ));
+            }
+        }
+        if let Some(Document::Boolean(true)) = keyword(keywords, "uniqueItems") {
+            for (i, a) in items.iter().enumerate() {
+                if items[..i].iter().any(|b| a.value_eq(b)) {
+                    self.report(format!("item {} is a duplicate", i));
+                }
+            }
+        }
+        let prefix = keyword_list(keywords, "prefixItems")?.unwrap_or_default();
+        let rest = keyword(keywords, "items");
+        for (i, item) in items.into_iter().enumerate() {
+            let schema = match prefix.get(i) {
+                Some(s) => Some(*s),
+                None => rest,
+            };
+            if let Some(schema) = schema {
+                self.path.push(DocPath::Index(i));
+                let result = self.validate(item, schema);
+                self.path.pop();
+                result?;
+            }
+        }
+        Ok(())
+    }
+
+    fn validate_object(&mut self, map: &'a [Document], keywords: &'s [Document]) -> Result<()> {
+        let entries = map
+            .iter()
+            .filter_map(|v| v.as_kv().ok())
+            .map(|(k, v)| (k.as_str().unwrap_or("?"), v))
+            .collect::<Vec<_>>();
+        if let Some(required) = keyword_list(keywords, "required")? {
+            for name in required {
+                let name = name.as_str()?;
+                if !entries.iter().any(|(k, _)| *k == name) {
+                    self.report(format!("missing required property `{}`", name));
+                }
+            }
+        }
+        let properties = match keyword(keywords, "properties") {
+            None => &[][..],
+            Some(Document::Mapping(m)) => m.as_slice(),
+            Some(_) => return Err(schema_error("`properties` must be an object")),
+        };
+        let additional = keyword(keywords, "additionalProperties");
+        for (k, v) in entries {
+            let schema = match keyword(properties, k) {
+                Some(s) => s,
+                None => match additional {
+                    Some(Document::Boolean(false)) => {
+                        self.report(format!("unexpected property `{}`", k));
+                        continue;
+                    }
+                    Some(s) => s,
+                    None => continue,
+                },
+            };
+            self.path.push(DocPath::Name(k));
+            let result = self.validate(v, schema);
+            self.path.pop();
+            result?;
+        }
+        Ok(())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use anyhow::Result;
+
+    const SCHEMA: &str = r##"{
+        type: "object",
+        properties: {
+            name: {type: "string", pattern: "^[a-z_]+$"},
+            regs: {
+                type: "array",
+                items: {$ref: "#/$defs/reg"},
+            },
+            mode: {enum: ["fast", "slow"]},
+            data: {
+                type: "array",
+                items: {type: "integer", maximum: 0x7f},
+                maxItems: 4,
+                "x-format": "hexstr",
+            },
+        },
+        required: ["name", "regs"],
+        additionalProperties: false,
+        $defs: {
+            reg: {
+                type: "object",
+                properties: {
+                    addr: {type: "integer", minimum: 0, maximum: 0xFFFF},
+                    size: {anyOf: [{type: "integer"}, {type: "null"}]},
+                },
+                required: ["addr"],
+            },
+        },
+    }"##;
+
+    fn violations(text: &str) -> Result<Vec<String>> {
+        let schema = Document::parse(SCHEMA)?;
+        let doc = Document::parse(text)?;
+        Ok(doc
+            .validate(&schema)?
+            .iter()
+            .map(Violation::to_string)
+            .collect())
+    }
+
+    #[test]
+    fn test_valid() -> Result<()> {
+        let v = violations(
+            r#"{
+                name: "uart",
+                // Registers.
+                regs: [
+                    {addr: 0x10},
+                    {addr: 0b100, size: 4},
+                    {addr: "0x20", size: null},
+                ],
+                mode: "fast",
+                data: "00 01 7f",
+            }"#,
+        )?;
+        assert!(v.is_empty(), "{:?}", v);
+        Ok(())
+    }
+
+    #[test]
+    fn test_violations() -> Result<()> {
+        let v = violations(
+            r#"{
+                name: "Uart",
+                regs: [
+                    {addr: 0x10},
+                    {addr: 0x10000, size: "big"},
+                    {size: 1},
+                    7,
+                ],
+                mode: "medium",
+                data: "00 80 01 02 03",
+                extra: true,
+            }"#,
+        )?;
+        assert_eq!(
+            v,
+            vec![
+                r#"`name`: "Uart" does not match the pattern "^[a-z_]+$""#,
+                "`regs[1].addr`: 0x10000 is greater than the maximum 0xFFFF",
+                r#"`regs[1].size`: "big" does not match any alternative of `anyOf`"#,
+                "`regs[2]`: missing required property `addr`",
+                "`regs[3]`: expected object but got integer",
+                r#"`mode`: "medium" is not one of "fast", "slow""#,
+                "`data`: expected at most 4 items but got 5",
+                "`data[1]`: 128 is greater than the maximum 0x7F",
+                "unexpected property `extra`",
+            ]
+        );
+        Ok(())
+    }
+
+    #[test]
+    fn test_bad_schema() -> Result<()> {
+        let schema = Document::parse(r##"{$ref: "#/nowhere"}"##)?;
+        let doc = Document::parse("1")?;
+        assert!(matches!(doc.validate(&schema), Err(Error::SchemaError(_))));
+        for schema in [r##"{$ref: "#"}"##, r##"{anyOf: [{$ref: "#"}]}"##] {
+            let schema = Document::parse(schema)?;
+            assert!(matches!(doc.validate(&schema), Err(Error::SchemaError(_))));
+        }
+        // A recursive schema which descends into the document is fine.
+        let schema = Document::parse(r##"{type: "array", items: {$ref: "#"}}"##)?;
+        assert!(Document::parse("[[], [[]]]")?.validate(&schema)?.is_empty());
+        Ok(())
+    }
+
+    #[test]
+    fn test_const_mapping() -> Result<()> {
+        // Entries are matched one-to-one regardless of the operand order.
+        let schema = Document::parse(r#"{"const": {a: 1, a: 1}}"#)?;
+        let doc = Document::parse("{a: 1, b: 2}")?;
+        assert!(!doc.validate(&schema)?.is_empty());
+        let schema = Document::parse(r#"{"const": {a: 1, b: 2}}"#)?;
+        let doc = Document::parse("{a: 1, a: 1}")?;
+        assert!(!doc.validate(&schema)?.is_empty());
+        let doc = Document::parse("{b: 2, a: 1}")?;
+        assert!(doc.validate(&schema)?.is_empty());
+        Ok(())
+    }
+}
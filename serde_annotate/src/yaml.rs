@@ -1,7 +1,10 @@
 use crate::color::{ColorProfile, PaintExt};
 use crate::document::{CommentFormat, Document, StrFormat};
 use crate::error::Error;
+use crate::hexdump;
 use crate::integer::Int;
+use crate::yaml_parser::YamlParser;
+use anstyle::Style;
 use std::fmt::{self, Display};
 
 type Result<T> = std::result::Result<T, Error>;
@@ -33,19 +36,27 @@ impl Yaml {
     }
 }
 
-impl fmt::Display for Yaml {
-    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
-        let mut emitter = YamlEmitter {
+impl Yaml {
+    // Returns an emitter configured with the formatting properties.
+    pub(crate) fn emitter(&self) -> YamlEmitter {
+        YamlEmitter {
             level: -1,
             indent: self.indent,
             color: self.color,
             compact: self.compact,
             is_key: false,
-        };
+        }
+    }
+}
+
+impl fmt::Display for Yaml {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
         if self.header {
             writeln!(f, "---")?;
         }
-        emitter.emit_node(f, &self.document).map_err(|_| fmt::Error)
+        self.emitter()
+            .emit_node(f, &self.document)
+            .map_err(|_| fmt::Error)
     }
 }
 
@@ -62,11 +73,11 @@ impl Document {
 }
 
 pub struct YamlEmitter {
-    level: isize,
+    pub(crate) level: isize,
     indent: usize,
-    color: ColorProfile,
-    compact: bool,
-    is_key: bool,
+    pub(crate) color: ColorProfile,
+    pub(crate) compact: bool,
+    pub(crate) is_key: bool,
 }
 
 impl Default for YamlEmitter {
@@ -83,7 +94,7 @@ impl Default for YamlEmitter {
 
 impl YamlEmitter {
     const SPACE: &'static str = "                                                                                                    ";
-    fn emit_node<W: fmt::Write>(&mut self, w: &mut W, node: &Document) -> Result<()> {
+    pub(crate) fn emit_node<W: fmt::Write>(&mut self, w: &mut W, node: &Document) -> Result<()> {
         match node {
             Document::Comment(c, f) => self.emit_comment_newline(w, c, f),
             Document::String(v, f) => self.emit_string(w, v.as_str(), *f),
@@ -222,7 +233,7 @@ impl YamlEmitter {
             self.level += 1;
         }
         for (i, frag) in mapping.iter().enumerate() {
-            let nodes = frag.fragments()?;
+            let nodes = frag.entry_nodes()?;
             if i > 0 {
                 if self.compact {
                     write!(w, ", ")?;
@@ -237,7 +248,7 @@ impl YamlEmitter {
             while let Some(node) = it.next() {
                 let next = it.peek();
                 if let Some((c, f)) = node.comment() {
-                    if val_done {
+                    if val_done && !self.compact {
                         write!(w, " ")?;
                     }
                     if self.emit_comment(w, c, f)? && next.is_some() {
@@ -271,6 +282,21 @@ impl YamlEmitter {
         Ok(())
     }
 
+    // Emits comments following a value on the same line.
+    pub(crate) fn emit_trailing<W: fmt::Write>(
+        &mut self,
+        w: &mut W,
+        comments: &[Document],
+    ) -> Result<()> {
+        for (c, f) in comments.iter().filter_map(Document::comment) {
+            if !self.compact {
+                write!(w, " ")?;
+                self.emit_comment(w, c, f)?;
+            }
+        }
+        Ok(())
+    }
+
     fn emit_comment_newline<W: fmt::Write>(
         &mut self,
         w: &mut W,
@@ -284,7 +310,7 @@ impl YamlEmitter {
         Ok(())
     }
 
-    fn emit_comment<W: fmt::Write>(
+    pub(crate) fn emit_comment<W: fmt::Write>(
         &mut self,
         w: &mut W,
         comment: &str,
@@ -316,9 +342,8 @@ impl YamlEmitter {
         match f {
             StrFormat::Multiline => self.emit_string_multiline(w, value)?,
             StrFormat::Quoted => self.escape_str(w, value, true)?,
-            StrFormat::Unquoted | StrFormat::Standard => {
-                self.escape_str(w, value, need_quotes(value))?
-            }
+            StrFormat::Unquoted => self.escape_str(w, value, !is_plain(value, self.compact))?,
+            StrFormat::Standard => self.escape_str(w, value, need_quotes(value))?,
         }
         Ok(())
     }
@@ -330,11 +355,20 @@ impl YamlEmitter {
         } else {
             write!(w, "{}", self.color.punctuation.paint("|-"))?;
         }
+        // Hexdumps are styled piecewise, if the color profile styles them.
+        let dump = hexdump::colorize(value, &self.color);
         self.level += 1;
-        for line in value.split('\n') {
+        for (i, line) in value.split('\n').enumerate() {
             writeln!(w)?;
             self.emit_indent(w)?;
-            self.escape_str(w, line, false)?;
+            match &dump {
+                Some(dump) => {
+                    for (style, text) in &dump[i] {
+                        self.escape_str_styled(w, text, false, *style)?;
+                    }
+                }
+                None => self.escape_str(w, line, false)?,
+            }
         }
         self.level -= 1;
         Ok(())
@@ -384,11 +418,15 @@ impl YamlEmitter {
         Ok(())
     }
 
-    fn emit_indent<W: fmt::Write>(&mut self, w: &mut W) -> Result<()> {
+    pub(crate) fn emit_indent<W: fmt::Write>(&mut self, w: &mut W) -> Result<()> {
         self.emit_indent_extra(w, 0)
     }
 
-    fn emit_indent_extra<W: fmt::Write>(&mut self, w: &mut W, extra: isize) -> Result<()> {
+    pub(crate) fn emit_indent_extra<W: fmt::Write>(
+        &mut self,
+        w: &mut W,
+        extra: isize,
+    ) -> Result<()> {
         let extra = self.level + extra;
         if self.compact || extra < 0 {
             return Ok(());
@@ -402,7 +440,7 @@ impl YamlEmitter {
         Ok(())
     }
 
-    fn writeln<W: fmt::Write>(&mut self, w: &mut W, s: impl Display) -> Result<()> {
+    pub(crate) fn writeln<W: fmt::Write>(&mut self, w: &mut W, s: impl Display) -> Result<()> {
         if self.compact {
             write!(w, "{}", s)?;
         } else {
@@ -423,6 +461,16 @@ impl YamlEmitter {
         } else {
             self.color.string
         };
+        self.escape_str_styled(wr, v, quoted, color)
+    }
+
+    fn escape_str_styled<W: fmt::Write>(
+        &self,
+        wr: &mut W,
+        v: &str,
+        quoted: bool,
+        color: Style,
+    ) -> std::result::Result<(), fmt::Error> {
         if quoted {
             write!(wr, "{}", self.color.punctuation.paint("\""))?;
         }
@@ -507,7 +555,7 @@ fn need_quotes(string: &str) -> bool {
 
     string.is_empty()
         || need_quotes_spaces(string)
-        || string.starts_with(&['&', '*', '?', '|', '-', '<', '>', '=', '!', '%', '@'])
+        || string.starts_with(['&', '*', '?', '|', '-', '<', '>', '=', '!', '%', '@'])
         || string.contains(|character: char| {
             matches!(character,
             ':'
@@ -528,25 +576,62 @@ fn need_quotes(string: &str) -> bool {
             | '\x0e'..='\x1a'
             | '\x1c'..='\x1f')
         })
-        || [
-            // http://yaml.org/type/bool.html
-            // Note: 'y', 'Y', 'n', 'N', is not quoted deliberately, as in libyaml. PyYAML also parse
-            // them as string, not booleans, although it is violating the YAML 1.1 specification.
-            // See https://github.com/dtolnay/serde-yaml/pull/83#discussion_r152628088.
-            "yes", "Yes", "YES", "no", "No", "NO", "True", "TRUE", "true", "False", "FALSE",
-            "false", "on", "On", "ON", "off", "Off", "OFF",
-            // http://yaml.org/type/null.html
-            "null", "Null", "NULL", "~",
-        ]
-        .contains(&string)
+        || is_ambiguous(string)
+}
+
+// Returns whether a plain scalar looks like a boolean, null or number to
+// common YAML parsers.
+fn is_ambiguous(string: &str) -> bool {
+    #[rustfmt::skip]
+    let words = [
+        // http://yaml.org/type/bool.html
+        // Note: 'y', 'Y', 'n', 'N', is not quoted deliberately, as in libyaml. PyYAML also parse
+        // them as string, not booleans, although it is violating the YAML 1.1 specification.
+        // See https://github.com/dtolnay/serde-yaml/pull/83#discussion_r152628088.
+        "yes", "Yes", "YES", "no", "No", "NO", "True", "TRUE", "true", "False", "FALSE",
+        "false", "on", "On", "ON", "off", "Off", "OFF",
+        // http://yaml.org/type/null.html
+        "null", "Null", "NULL", "~",
+    ];
+    words.contains(&string)
         || string.starts_with('.')
         || string.starts_with("0x")
         || string.starts_with("0b")
         || string.starts_with("0o")
         || string.parse::<i64>().is_ok()
+        || Int::from_str_radix(string, 10).is_ok()
         || string.parse::<f64>().is_ok()
 }
 
+// Returns whether `string` can be written as a plain scalar, which is the
+// case for more strings than `need_quotes` allows.  In flow collections
+// (`flow`), the flow indicators must be quoted as well.
+fn is_plain(string: &str, flow: bool) -> bool {
+    let bytes = string.as_bytes();
+    let Some(&first) = bytes.first() else {
+        return false;
+    };
+    // `-`, `?` and `:` only start a plain scalar when they are not followed
+    // by a space.
+    let indicator = match first {
+        b'-' | b'?' | b':' => matches!(bytes.get(1), None | Some(b' ' | b'\t')),
+        _ => b"&*!|>'\"%@`#,[]{}".contains(&first),
+    };
+    !indicator
+        && !string.starts_with("---")
+        && !string.starts_with("...")
+        && !string.starts_with([' ', '\t'])
+        && !string.ends_with([' ', '\t', ':'])
+        && !string.contains(": ")
+        && !string.contains(" #")
+        && !string.contains(|c: char| {
+            c.is_control() || matches!(c, '\\' | '\u{2028}' | '\u{2029}' | '\u{feff}')
+        })
+        && !(flow && string.contains([',', '[', ']', '{', '}']))
+        && !is_ambiguous(string)
+        && matches!(YamlParser::resolve_plain(string), Document::String(..))
+}
+
 #[cfg(test)]
 mod tests {
     use super::*;
@@ -594,6 +679,7 @@ mod tests {
     }
 
     #[test]
+    #[allow(clippy::approx_constant)]
     fn basic_document() {
         let c = comment("woohoo!").to_yaml().header(false);
         assert_eq!(c.to_string(), "# woohoo!\n");
@@ -733,4 +819,52 @@ backwardsCompatible: with JSON"#;
         println!("{}", map);
         assert_eq!(map.to_string(), expect);
     }
+    #[test]
+    fn unquoted_strings() -> Result<()> {
+        let unquoted = |v: &str| Document::String(v.to_string(), StrFormat::Unquoted);
+        let map = Document::Mapping(vec![
+            kv("url", unquoted("http://example.com/a#b")),
+            kv("flow", unquoted("a, [b]")),
+            kv("dash", unquoted("-x")),
+            kv("quote", unquoted("it's \"here\"")),
+            kv("colon", unquoted("key: value")),
+            kv("comment", unquoted("a #b")),
+            kv("word", unquoted("true")),
+            kv("number", unquoted("0X10")),
+            kv("float", unquoted("-.inf")),
+            kv("seq", unquoted("- a")),
+            kv("path", unquoted("C:\\dir")),
+            kv("compact", Document::Compact(unquoted("a, b").into())),
+        ]);
+        let text = map.clone().to_yaml().to_string();
+        assert_eq!(
+            text,
+            r#"---
+url: http://example.com/a#b
+flow: a, [b]
+dash: -x
+quote: it's "here"
+colon: "key: value"
+comment: "a #b"
+word: "true"
+number: "0X10"
+float: "-.inf"
+seq: "- a"
+path: "C:\\dir"
+compact: "a, b""#
+        );
+        let doc = Document::from_yaml(&text)?;
+        let Document::Mapping(kvs) = &doc else {
+            panic!("expected a mapping: {doc:?}");
+        };
+        let Document::Mapping(expect) = &map else {
+            unreachable!()
+        };
+        for (parsed, original) in kvs.iter().zip(expect) {
+            let (_, v) = parsed.as_kv()?;
+            let (_, e) = original.as_kv()?;
+            assert_eq!(v.as_value()?.as_str()?, e.as_value()?.as_str()?);
+        }
+        Ok(())
+    }
 }
@@ -0,0 +1,998 @@
+// Parser for YAML documents.
+use std::borrow::Cow;
+
+use crate::document::{CommentFormat, Document, StrFormat};
+use crate::error::Error;
+use crate::integer::Int;
+use crate::relax::Relax;
+
+type Result<T> = std::result::Result<T, Error>;
+
+// The chomping indicator of a block scalar.
+#[derive(Clone, Copy, PartialEq)]
+enum Chomp {
+    Strip,
+    Clip,
+    Keep,
+}
+
+/// `YamlParser` parses YAML documents into a `Document`.
+///
+/// The parser understands block and flow collections, plain, quoted and block
+/// (`|`, `>`) scalars and `#` comments.  Comments are attached to the
+/// neighboring nodes in the same way the `Relax` parser attaches comments in
+/// JSON documents, and integers remember the base they were written in.
+///
+/// Anchors, aliases, tags and multi-document streams are not supported.
+#[derive(Clone, Copy)]
+pub struct YamlParser<'a> {
+    text: &'a str,
+    pos: usize,
+}
+
+impl<'a> YamlParser<'a> {
+    /// Parses a string into a `Document`.
+    pub fn parse(text: &str) -> Result<Document> {
+        let text = if text.contains('\r') {
+            Cow::Owned(text.replace("\r\n", "\n"))
+        } else {
+            Cow::Borrowed(text)
+        };
+        let mut parser = YamlParser {
+            text: &text,
+            pos: 0,
+        };
+        parser.parse_document()
+    }
+
+    fn rest(&self) -> &'a str {
+        &self.text[self.pos..]
+    }
+
+    fn peek(&self) -> Option<char> {
+        self.rest().chars().next()
+    }
+
+    // Returns the byte `n` bytes past the current position.
+    fn byte_at(&self, n: usize) -> Option<u8> {
+        self.text.as_bytes().get(self.pos + n).copied()
+    }
+
+    fn at_end(&self) -> bool {
+        self.pos >= self.text.len()
+    }
+
+    fn at_eol(&self) -> bool {
+        matches!(self.peek(), None | Some('\n'))
+    }
+
+    fn line_end(&self) -> usize {
+        self.rest()
+            .find('\n')
+            .map(|i| self.pos + i)
+            .unwrap_or(self.text.len())
+    }
+
+    // Returns the indentation of the line starting at the current position.
+    fn line_indent(&self) -> Result<usize> {
+        let n = self.rest().len() - self.rest().trim_start_matches(' ').len();
+        if self.byte_at(n) == Some(b'\t') {
+            return Err(self.error_at(self.pos + n, "tabs are not allowed for indentation"));
+        }
+        Ok(n)
+    }
+
+    fn column(&self) -> usize {
+        let start = self.text[..self.pos].rfind('\n').map_or(0, |i| i + 1);
+        self.pos - start
+    }
+
+    fn skip_space(&mut self) {
+        while matches!(self.peek(), Some(' ') | Some('\t')) {
+            self.pos += 1;
+        }
+    }
+
+    fn is_indicator(&self, ch: char) -> bool {
+        self.peek() == Some(ch) && matches!(self.byte_at(1), None | Some(b' ' | b'\t' | b'\n'))
+    }
+
+    fn is_sequence_entry(&self) -> bool {
+        self.is_indicator('-')
+    }
+
+    fn is_document_marker(&self) -> bool {
+        let rest = self.rest();
+        (rest.starts_with("---") || rest.starts_with("..."))
+            && matches!(self.byte_at(3), None | Some(b' ' | b'\t' | b'\n'))
+    }
+
+    fn error(&self, msg: &str) -> Error {
+        self.error_at(self.pos, msg)
+    }
+
+    fn error_at(&self, pos: usize, msg: &str) -> Error {
+        let start = self.text[..pos].rfind('\n').map_or(0, |i| i + 1);
+        let end = self.text[pos..]
+            .find('\n')
+            .map_or(self.text.len(), |i| pos + i);
+        let line = self.text[..pos].matches('\n').count() + 1;
+        let col = self.text[start..pos].chars().count() + 1;
+        Error::SyntaxError(
+            msg.into(),
+            line,
+            col,
+            self.text[start..end].trim_end().into(),
+            "^",
+        )
+    }
+
+    // Converts a group of `#` comment lines into a comment node.
+    fn comment(lines: &[&str]) -> Document {
+        let lines = lines.iter().map(|s| s.trim()).collect::<Vec<_>>();
+        let lines = Relax::strip_leading_prefix(&lines, '#');
+        let lines = Relax::strip_leading_prefix(&lines, ' ');
+        Document::Comment(lines.join("\n"), CommentFormat::Hash)
+    }
+
+    // Skips blank lines and gathers comment lines.  Consecutive comment lines
+    // are gathered into a single comment node.  On return, the position is at
+    // the start of the next content line or at the end of the input.
+    fn comments(&mut self) -> Result<Vec<Document>> {
+        let mut result = Vec::new();
+        let mut lines = Vec::new();
+        while !self.at_end() {
+            let end = self.line_end();
+            let line = &self.text[self.pos..end];
+            let trimmed = line.trim_start_matches([' ', '\t']);
+            if trimmed.starts_with('#') {
+                lines.push(trimmed);
+            } else if trimmed.is_empty() {
+                if !lines.is_empty() {
+                    result.push(Self::comment(&lines));
+                    lines.clear();
+                }
+            } else {
+                break;
+            }
+            self.pos = std::cmp::min(end + 1, self.text.len());
+        }
+        if !lines.is_empty() {
+            result.push(Self::comment(&lines));
+        }
+        Ok(result)
+    }
+
+    // Consumes the remainder of the current line, returning the trailing
+    // comment if there is one.
+    fn trailing(&mut self) -> Result<Option<Document>> {
+        self.skip_space();
+        let comment = if self.peek() == Some('#') {
+            let end = self.line_end();
+            let line = &self.text[self.pos..end];
+            self.pos = end;
+            Some(Self::comment(&[line]))
+        } else {
+            None
+        };
+        if !self.at_eol() {
+            return Err(self.error("unexpected characters after value"));
+        }
+        if !self.at_end() {
+            self.pos += 1;
+        }
+        Ok(comment)
+    }
+
+    fn parse_document(&mut self) -> Result<Document> {
+        let mut nodes = self.comments()?;
+        let mut value = None;
+        if self.rest().starts_with("---") && self.is_document_marker() {
+            self.pos += 3;
+            self.skip_space();
+            if self.peek() == Some('#') || self.at_eol() {
+                nodes.extend(self.trailing()?);
+                nodes.extend(self.comments()?);
+            } else {
+                let (v, comment) = self.parse_inline(-1)?;
+                value = Some(v);
+                nodes.extend(comment);
+            }
+        }
+        if value.is_none() && !self.at_end() && !self.is_document_marker() {
+            let indent = self.line_indent()?;
+            self.pos += indent;
+            let (leading, v, comment) = self.parse_node(indent, std::mem::take(&mut nodes))?;
+            nodes = leading;
+            value = Some(v);
+            nodes.extend(comment);
+        }
+        if let Some(v) = value {
+            nodes.push(v);
+        }
+        nodes.extend(self.comments()?);
+        if self.rest().starts_with("...") && self.is_document_marker() {
+            self.pos += 3;
+            nodes.extend(self.trailing()?);
+            nodes.extend(self.comments()?);
+        }
+        if !self.at_end() {
+            return Err(if self.is_document_marker() {
+                self.error("multiple documents are not supported")
+            } else {
+                self.error("unexpected content")
+            });
+        }
+        match nodes.len() {
+            0 => Ok(Document::Null),
+            1 => Ok(nodes.pop().unwrap()),
+            _ => Ok(Document::Fragment(nodes)),
+        }
+    }
+
+    // Parses the node starting at the current position, which is at column
+    // `indent`.  Returns the comments preceding the node, the node itself and
+    // any trailing comment.  The `leading` comments are attached to the first
+    // entry if the node is a block collection.
+    fn parse_node(
+        &mut self,
+        indent: usize,
+        leading: Vec<Document>,
+    ) -> Result<(Vec<Document>, Document, Option<Document>)> {
+        if self.is_sequence_entry() {
+            Ok((vec![], self.parse_sequence(indent, leading)?, None))
+        } else if self.looks_like_key() {
+            Ok((vec![], self.parse_mapping(indent, leading)?, None))
+        } else {
+            let (value, comment) = self.parse_inline(indent as isize - 1)?;
+            Ok((leading, value, comment))
+        }
+    }
+
+    // Parses the value following a `key:` or `-` indicator on a line at
+    // column `indent`.
+    fn parse_value(
+        &mut self,
+        indent: usize,
+        entry: bool,
+    ) -> Result<(Vec<Document>, Document, Option<Document>)> {
+        self.skip_space();
+        if self.peek() == Some('#') || self.at_eol() {
+            // The value, if any, is on the following lines.  A comment
+            // following the indicator is placed before the key or entry.
+            let leading = self.trailing()?.into_iter().collect::<Vec<_>>();
+            let save = self.pos;
+            let comments = self.comments()?;
+            if !self.at_end() && !self.is_document_marker() {
+                let n = self.line_indent()?;
+                if n > indent {
+                    self.pos += n;
+                    let (mut pre, value, post) = self.parse_node(n, comments)?;
+                    pre.splice(0..0, leading);
+                    return Ok((pre, value, post));
+                }
+                if n == indent && !entry {
+                    self.pos += n;
+                    if self.is_sequence_entry() {
+                        return Ok((leading, self.parse_sequence(n, comments)?, None));
+                    }
+                }
+            }
+            self.pos = save;
+            Ok((leading, Document::Null, None))
+        } else if entry && self.is_sequence_entry() {
+            let column = self.column();
+            Ok((vec![], self.parse_sequence(column, vec![])?, None))
+        } else if entry && self.looks_like_key() {
+            let column = self.column();
+            Ok((vec![], self.parse_mapping(column, vec![])?, None))
+        } else {
+            let (value, comment) = self.parse_inline(indent as isize)?;
+            Ok((vec![], value, comment))
+        }
+    }
+
+    // Advances to the next entry of a block collection at column `indent`,
+    // collecting the entry's leading comments.  Returns false and leaves the
+    // position unchanged if the collection has no more entries.
+    fn next_entry(
+        &mut self,
+        indent: usize,
+        sequence: bool,
+        leading: &mut Vec<Document>,
+    ) -> Result<bool> {
+        let save = self.pos;
+        let comments = self.comments()?;
+        if !self.at_end() && !self.is_document_marker() {
+            let n = self.line_indent()?;
+            if n > indent {
+                self.pos += n;
+                return Err(self.error("bad indentation"));
+            }
+            if n == indent {
+                self.pos += n;
+                if self.is_sequence_entry() == sequence {
+                    *leading = comments;
+                    return Ok(true);
+                }
+            }
+        }
+        self.pos = save;
+        Ok(false)
+    }
+
+    fn parse_sequence(&mut self, indent: usize, mut leading: Vec<Document>) -> Result<Document> {
+        let mut items = Vec::new();
+        loop {
+            let mut nodes = std::mem::take(&mut leading);
+            // Skip the `-` indicator.
+            self.pos += 1;
+            let (pre, value, post) = self.parse_value(indent, true)?;
+            nodes.extend(pre);
+            nodes.push(value);
+            nodes.extend(post);
+            if nodes.len() == 1 {
+                items.push(nodes.pop().unwrap());
+            } else {
+                items.push(Document::Fragment(nodes));
+            }
+            if !self.next_entry(indent, true, &mut leading)? {
+                break;
+            }
+        }
+        Ok(Document::Sequence(items))
+    }
+
+    fn parse_mapping(&mut self, indent: usize, mut leading: Vec<Document>) -> Result<Document> {
+        let mut kvs = Vec::new();
+        loop {
+            let mut nodes = std::mem::take(&mut leading);
+            let key = self.parse_key()?;
+            let (pre, value, post) = self.parse_value(indent, false)?;
+            nodes.extend(pre);
+            nodes.push(key);
+            nodes.push(value);
+            nodes.extend(post);
+            kvs.push(Document::Fragment(nodes));
+            if !self.next_entry(indent, false, &mut leading)? {
+                break;
+            }
+        }
+        Ok(Document::Mapping(kvs))
+    }
+
+    // Returns whether the current position is at a `key:` pair.
+    fn looks_like_key(&self) -> bool {
+        let mut p = *self;
+        let ok = match p.peek() {
+            Some('"') => p.double_quoted().is_ok(),
+            Some('\'') => p.single_quoted().is_ok(),
+            Some('[' | '{') | None => false,
+            _ => !p.plain(false).is_empty(),
+        };
+        p.skip_space();
+        ok && p.is_indicator(':')
+    }
+
+    fn parse_key(&mut self) -> Result<Document> {
+        let key = match self.peek() {
+            Some('"') => Document::String(self.double_quoted()?, StrFormat::Quoted),
+            Some('\'') => Document::String(self.single_quoted()?, StrFormat::Quoted),
+            _ => {
+                self.check_unsupported()?;
+                let key = self.plain(false);
+                if key.is_empty() {
+                    return Err(self.error("expected a key"));
+                }
+                Document::String(key.into(), StrFormat::Standard)
+            }
+        };
+        self.skip_space();
+        if self.peek() != Some(':') {
+            return Err(self.error("expected `:`"));
+        }
+        self.pos += 1;
+        Ok(key)
+    }
+
+    fn check_unsupported(&self) -> Result<()> {
+        match self.peek() {
+            Some('&') => Err(self.error("anchors are not supported")),
+            Some('*') => Err(self.error("aliases are not supported")),
+            Some('!') => Err(self.error("tags are not supported")),
+            Some('%' | '@' | '`') => Err(self.error("reserved indicator")),
+            Some('?') if self.is_indicator('?') => {
+                Err(self.error("complex keys are not supported"))
+            }
+            _ => Ok(()),
+        }
+    }
+
+    // Parses a scalar or flow collection that starts on the current line.
+    // `parent` is the indentation of the enclosing block collection.
+    fn parse_inline(&mut self, parent: isize) -> Result<(Document, Option<Document>)> {
+        let value = match self.peek() {
+            Some('|' | '>') => return self.block_scalar(parent),
+            Some('[' | '{') => Document::Compact(Box::new(self.flow()?)),
+            Some('"') => Document::String(self.double_quoted()?, StrFormat::Quoted),
+            Some('\'') => Document::String(self.single_quoted()?, StrFormat::Quoted),
+            _ => {
+                self.check_unsupported()?;
+                let first = self.plain(false);
+                match self.plain_lines(parent, first) {
+                    Some(value) => Document::String(value, StrFormat::Standard),
+                    None => Self::resolve_plain(first),
+                }
+            }
+        };
+        let comment = self.trailing()?;
+        Ok((value, comment))
+    }
+
+    // Scans the continuation lines of a multi-line plain scalar whose first
+    // line is `first`.  The continuation lines must be indented more than
+    // `parent`.  Returns `None` if the scalar is a single line.
+    fn plain_lines(&mut self, parent: isize, first: &str) -> Option<String> {
+        let mut value = None;
+        loop {
+            let save = self.pos;
+            let mut breaks = 0;
+            let mut line = self.pos;
+            loop {
+                self.skip_space();
+                if self.peek() != Some('\n') {
+                    break;
+                }
+                self.pos += 1;
+                breaks += 1;
+                line = self.pos;
+            }
+            let n = self.text[line..].len() - self.text[line..].trim_start_matches(' ').len();
+            let end = breaks == 0
+                || self.at_end()
+                || n as isize <= parent
+                || self.peek() == Some('#')
+                || self.looks_like_key()
+                || (n == 0 && self.is_document_marker());
+            let next = if end { "" } else { self.plain(false) };
+            if next.is_empty() {
+                self.pos = save;
+                return value;
+            }
+            let value = value.get_or_insert_with(|| first.to_string());
+            if breaks == 1 {
+                value.push(' ');
+            } else {
+                value.extend(std::iter::repeat_n('\n', breaks - 1));
+            }
+            value.push_str(next);
+        }
+    }
+
+    // Scans a plain (unquoted) scalar.  Trailing whitespace is not consumed.
+    fn plain(&mut self, flow: bool) -> &'a str {
+        let bytes = self.text.as_bytes();
+        let start = self.pos;
+        let mut end = start;
+        let mut i = start;
+        while i < bytes.len() {
+            match bytes[i] {
+                b'\n' => break,
+                b'#' if i > start && matches!(bytes[i - 1], b' ' | b'\t') => break,
+                b':' if matches!(bytes.get(i + 1), None | Some(b' ' | b'\t' | b'\n')) => break,
+                b':' if flow && matches!(bytes.get(i + 1), Some(b',' | b']' | b'}')) => break,
+                b',' | b'[' | b']' | b'{' | b'}' if flow => break,
+                b' ' | b'\t' => {}
+                _ => end = i + 1,
+            }
+            i += 1;
+        }
+        self.pos = end;
+        &self.text[start..end]
+    }
+
+    // Digits may be separated by underscores, as in YAML 1.1.
+    fn is_integer(s: &str) -> bool {
+        fn digits(s: &str, f: fn(&u8) -> bool) -> bool {
+            !s.is_empty() && s.as_bytes().iter().all(|b| f(b) || *b == b'_')
+        }
+        if let Some(s) = s.strip_prefix("0x").or_else(|| s.strip_prefix("0X")) {
+            digits(s, u8::is_ascii_hexdigit)
+        } else if let Some(s) = s.strip_prefix("0o").or_else(|| s.strip_prefix("0O")) {
+            digits(s, |b| (b'0'..=b'7').contains(b))
+        } else if let Some(s) = s.strip_prefix("0b").or_else(|| s.strip_prefix("0B")) {
+            digits(s, |b| *b == b'0' || *b == b'1')
+        } else {
+            digits(s, u8::is_ascii_digit)
+        }
+    }
+
+    fn is_float(s: &str) -> bool {
+        s.bytes().any(|b| b.is_ascii_digit())
+            && s.bytes()
+                .all(|b| b.is_ascii_digit() || matches!(b, b'.' | b'e' | b'E' | b'+' | b'-'))
+    }
+
+    // Converts a plain scalar into the node it represents.
+    pub(crate) fn resolve_plain(s: &str) -> Document {
+        match s {
+            "" | "~" | "null" | "Null" | "NULL" => return Document::Null,
+            "true" | "True" | "TRUE" => return Document::Boolean(true),
+            "false" | "False" | "FALSE" => return Document::Boolean(false),
+            ".inf" | ".Inf" | ".INF" | "+.inf" | "+.Inf" | "+.INF" => {
+                return Document::Float(f64::INFINITY)
+            }
+            "-.inf" | "-.Inf" | "-.INF" => return Document::Float(f64::NEG_INFINITY),
+            ".nan" | ".NaN" | ".NAN" => return Document::Float(f64::NAN),
+            _ => {}
+        }
+        let unsigned = s.strip_prefix(['-', '+']).unwrap_or(s);
+        if Self::is_integer(unsigned) {
+            if let Ok(i) = Int::from_str_radix(s, 0) {
+                return Document::Int(i);
+            }
+        } else if Self::is_float(unsigned) {
+            if let Ok(f) = s.parse::<f64>() {
+                return Document::Float(f);
+            }
+        }
+        Document::String(s.into(), StrFormat::Standard)
+    }
+
+    // Folds a line break inside a quoted scalar.  The line break has already
+    // been consumed.
+    fn fold(&mut self, s: &mut String) {
+        s.truncate(s.trim_end_matches([' ', '\t']).len());
+        let mut breaks = 0;
+        loop {
+            self.skip_space();
+            if self.peek() == Some('\n') {
+                self.pos += 1;
+                breaks += 1;
+            } else {
+                break;
+            }
+        }
+        if breaks == 0 {
+            s.push(' ');
+        } else {
+            s.extend(std::iter::repeat_n('\n', breaks));
+        }
+    }
+
+    fn hex_escape(&mut self, len: usize) -> Result<char> {
+        let digits = self.rest().get(..len).unwrap_or("");
+        let value = u32::from_str_radix(digits, 16)
+            .map_err(|_| self.error("bad hexadecimal escape sequence"))?;
+        self.pos += len;
+        Ok(char::try_from(value)?)
+    }
+
+    fn double_quoted(&mut self) -> Result<String> {
+        let start = self.pos;
+        self.pos += 1;
+        let mut s = String::new();
+        loop {
+            let ch = self
+                .peek()
+                .ok_or_else(|| self.error_at(start, "unterminated string"))?;
+            self.pos += ch.len_utf8();
+            match ch {
+                '"' => break,
+                '\n' => self.fold(&mut s),
+                '\\' => {
+                    let esc = self
+                        .peek()
+                        .ok_or_else(|| self.error_at(start, "unterminated string"))?;
+                    self.pos += esc.len_utf8();
+                    let decoded = match esc {
+                        '0' => '\0',
+                        'a' => '\x07',
+                        'b' => '\x08',
+                        't' | '\t' => '\t',
+                        'n' => '\n',
+                        'v' => '\x0b',
+                        'f' => '\x0c',
+                        'r' => '\r',
+                        'e' => '\x1b',
+                        ' ' => ' ',
+                        '"' => '"',
+                        '/' => '/',
+                        '\\' => '\\',
+                        'N' => '\u{85}',
+                        '_' => '\u{a0}',
+                        'L' => '\u{2028}',
+                        'P' => '\u{2029}',
+                        'x' => self.hex_escape(2)?,
+                        'u' => self.hex_escape(4)?,
+                        'U' => self.hex_escape(8)?,
+                        '\n' => {
+                            // An escaped line break joins the lines without
+                            // inserting a space.
+                            self.skip_space();
+                            continue;
+                        }
+                        _ => return Err(Error::EscapeError(esc)),
+                    };
+                    s.push(decoded);
+                }
+                _ => s.push(ch),
+            }
+        }
+        Ok(s)
+    }
+
+    fn single_quoted(&mut self) -> Result<String> {
+        let start = self.pos;
+        self.pos += 1;
+        let mut s = String::new();
+        loop {
+            let ch = self
+                .peek()
+                .ok_or_else(|| self.error_at(start, "unterminated string"))?;
+            self.pos += ch.len_utf8();
+            match ch {
+                '\'' if self.peek() == Some('\'') => {
+                    self.pos += 1;
+                    s.push('\'');
+                }
+                '\'' => break,
+                '\n' => self.fold(&mut s),
+                _ => s.push(ch),
+            }
+        }
+        Ok(s)
+    }
+
+    // Parses a literal (`|`) or folded (`>`) block scalar.
+    fn block_scalar(&mut self, parent: isize) -> Result<(Document, Option<Document>)> {
+        let literal = self.peek() == Some('|');
+        self.pos += 1;
+        let mut chomp = Chomp::Clip;
+        let mut indent = None;
+        loop {
+            match self.peek() {
+                Some('-') => chomp = Chomp::Strip,
+                Some('+') => chomp = Chomp::Keep,
+                Some(ch @ '1'..='9') => {
+                    indent = Some(parent.max(0) as usize + (ch as usize - '0' as usize))
+                }
+                _ => break,
+            }
+            self.pos += 1;
+        }
+        let comment = self.trailing()?;
+
+        let mut lines = Vec::new();
+        let mut last_break = false;
+        while !self.at_end() {
+            let end = self.line_end();
+            let line = &self.text[self.pos..end];
+            if line.trim().is_empty() {
+                lines.push("");
+            } else {
+                let n = line.len() - line.trim_start_matches(' ').len();
+                let ind = *indent.get_or_insert(n);
+                if n as isize <= parent || n < ind || (n == 0 && self.is_document_marker()) {
+                    break;
+                }
+                lines.push(&line[ind..]);
+            }
+            last_break = end < self.text.len();
+            self.pos = std::cmp::min(end + 1, self.text.len());
+        }
+
+        let blank = lines.iter().rev().take_while(|l| l.is_empty()).count();
+        let content = &lines[..lines.len() - blank];
+        let mut value = if literal {
+            content.join("\n")
+        } else {
+            let mut s = String::new();
+            for (i, line) in content.iter().enumerate() {
+                if i > 0 {
+                    let prev = content[i - 1];
+                    if line.is_empty() {
+                        s.push('\n');
+                    } else if prev.is_empty() {
+                        // The line break was replaced by the empty lines.
+                    } else if prev.starts_with([' ', '\t']) || line.starts_with([' ', '\t']) {
+                        s.push('\n');
+                    } else {
+                        s.push(' ');
+                    }
+                }
+                s.push_str(line);
+            }
+            s
+        };
+        let final_break = !content.is_empty() && (blank > 0 || last_break);
+        match chomp {
+            Chomp::Strip => {}
+            Chomp::Clip => {
+                if final_break {
+                    value.push('\n');
+                }
+            }
+            Chomp::Keep => {
+                if final_break {
+                    value.push('\n');
+                }
+                value.extend(std::iter::repeat_n('\n', blank));
+            }
+        }
+        Ok((Document::String(value, StrFormat::Multiline), comment))
+    }
+
+    // Skips whitespace and line breaks inside a flow collection, gathering
+    // any comments.
+    fn flow_space(&mut self) -> Vec<Document> {
+        let mut comments = Vec::new();
+        loop {
+            match self.peek() {
+                Some(' ' | '\t' | '\n') => self.pos += 1,
+                Some('#') => {
+                    let end = self.line_end();
+                    let line = &self.text[self.pos..end];
+                    self.pos = end;
+                    comments.push(Self::comment(&[line]));
+                }
+                _ => break,
+            }
+        }
+        comments
+    }
+
+    fn flow_node(&mut self) -> Result<Document> {
+        match self.peek() {
+            Some('[' | '{') => self.flow(),
+            Some('"') => Ok(Document::String(self.double_quoted()?, StrFormat::Quoted)),
+            Some('\'') => Ok(Document::String(self.single_quoted()?, StrFormat::Quoted)),
+            _ => {
+                self.check_unsupported()?;
+                let s = self.plain(true);
+                if s.is_empty() {
+                    return Err(self.error("expected a value"));
+                }
+                Ok(Self::resolve_plain(s))
+            }
+        }
+    }
+
+    // Parses a flow sequence (`[...]`) or flow mapping (`{...}`).
+    fn flow(&mut self) -> Result<Document> {
+        let start = self.pos;
+        let mapping = self.peek() == Some('{');
+        let close = if mapping { '}' } else { ']' };
+        self.pos += 1;
+        let mut items = Vec::new();
+        loop {
+            let mut nodes = self.flow_space();
+            if self.peek() == Some(close) {
+                self.pos += 1;
+                if !nodes.is_empty() {
+                    items.push(Document::Fragment(nodes));
+                }
+                break;
+            }
+            if self.at_end() {
+                return Err(self.error_at(start, "unterminated flow collection"));
+            }
+            if mapping {
+                let key = self.flow_node()?;
+                nodes.extend(self.flow_space());
+                if self.peek() != Some(':') {
+                    return Err(self.error("expected `:`"));
+                }
+                self.pos += 1;
+                nodes.extend(self.flow_space());
+                nodes.push(key);
+            }
+            nodes.push(self.flow_node()?);
+            nodes.extend(self.flow_space());
+            match self.peek() {
+                Some(',') => self.pos += 1,
+                Some(ch) if ch == close => {}
+                _ => return Err(self.error(&format!("expected `,` or `{}`", close))),
+            }
+            if mapping || nodes.len() > 1 {
+                items.push(Document::Fragment(nodes));
+            } else {
+                items.push(nodes.pop().unwrap());
+            }
+        }
+        if mapping {
+            Ok(Document::Mapping(items))
+        } else {
+            Ok(Document::Sequence(items))
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use anyhow::{anyhow, Result};
+
+    fn parse_kv(doc: &Document) -> Result<Vec<(String, &Document)>> {
+        if let Document::Mapping(m) = doc {
+            m.iter()
+                .map(|kv| {
+                    let (k, v) = kv.as_kv()?;
+                    Ok((k.as_str()?.to_string(), v))
+                })
+                .collect()
+        } else {
+            Err(anyhow!("Didn't return Document::Mapping()\n{:?}", doc))
+        }
+    }
+
+    #[test]
+    fn test_scalars() -> Result<()> {
+        assert!(matches!(YamlParser::parse("null")?, Document::Null));
+        assert!(matches!(YamlParser::parse("~")?, Document::Null));
+        assert!(matches!(
+            YamlParser::parse("true")?,
+            Document::Boolean(true)
+        ));
+        assert!(matches!(YamlParser::parse("1.5")?, Document::Float(_)));
+        assert_eq!(YamlParser::parse("foo bar")?.as_str()?, "foo bar");
+        assert_eq!(YamlParser::parse("'it''s'")?.as_str()?, "it's");
+        assert_eq!(
+            YamlParser::parse(r#""\"\\\t™\x41""#)?.as_str()?,
+            "\"\\\t\u{2122}A"
+        );
+        let doc = YamlParser::parse("k: foo\n  bar\n\n  baz # c\nl: 1\n  2")?;
+        let kv = parse_kv(&doc)?;
+        assert_eq!(kv[0].1.as_str()?, "foo bar\nbaz");
+        assert_eq!(kv[1].1.as_str()?, "1 2");
+        let seq = YamlParser::parse("- a\n  b\n- c")?;
+        let Document::Sequence(items) = &seq else {
+            return Err(anyhow!("Didn't return Document::Sequence()\n{:?}", seq));
+        };
+        assert_eq!(items[0].as_str()?, "a b");
+        assert_eq!(items[1].as_str()?, "c");
+        Ok(())
+    }
+
+    #[test]
+    fn test_integers() -> Result<()> {
+        let doc = YamlParser::parse("[10, 0x10, 0o10, 0b10, 0x0010]")?;
+        let ints = doc
+            .as_value()?
+            .iter()
+            .filter_map(|d| match d {
+                Document::Int(i) => Some(i.to_string()),
+                _ => None,
+            })
+            .collect::<Vec<_>>();
+        assert_eq!(ints, ["10", "0x10", "0o10", "0b10", "0x0010"]);
+        assert_eq!(i64::try_from(&YamlParser::parse("-0x5678")?)?, -0x5678);
+        Ok(())
+    }
+
+    #[test]
+    fn test_block_collections() -> Result<()> {
+        let doc = YamlParser::parse(
+            r#"---
+a: 1
+b:
+  c: two
+  d:
+    - 3
+    - - 4
+      - 5
+    - e: 6
+      f: 7
+g:
+- x
+- y
+"#,
+        )?;
+        let kv = parse_kv(&doc)?;
+        assert_eq!(kv.len(), 3);
+        assert_eq!(u32::try_from(kv[0].1)?, 1);
+        let b = parse_kv(kv[1].1)?;
+        assert_eq!(b[0].1.as_str()?, "two");
+        match b[1].1 {
+            Document::Sequence(s) => {
+                assert_eq!(s.len(), 3);
+                assert!(matches!(s[1], Document::Sequence(_)));
+                let m = parse_kv(&s[2])?;
+                assert_eq!(m[1].0, "f");
+            }
+            _ => return Err(anyhow!("expected a sequence")),
+        }
+        assert!(matches!(kv[2].1, Document::Sequence(s) if s.len() == 2));
+        Ok(())
+    }
+
+    #[test]
+    fn test_flow_collections() -> Result<()> {
+        let doc = YamlParser::parse("{a: [1, 2,], \"b\": {c: d}}")?;
+        let kv = parse_kv(doc.as_value()?)?;
+        assert_eq!(kv[0].0, "a");
+        assert!(matches!(kv[0].1, Document::Sequence(s) if s.len() == 2));
+        assert_eq!(parse_kv(kv[1].1)?[0].1.as_str()?, "d");
+        Ok(())
+    }
+
+    #[test]
+    fn test_block_scalars() -> Result<()> {
+        let doc = YamlParser::parse(
+            r#"
+literal: |
+  line one
+    indented
+  line three
+strip: |-
+  text
+keep: |+
+  text
+
+folded: >
+  one
+  two
+
+  three
+last: end
+"#,
+        )?;
+        let kv = parse_kv(&doc)?;
+        assert_eq!(kv[0].1.as_str()?, "line one\n  indented\nline three\n");
+        assert_eq!(kv[1].1.as_str()?, "text");
+        assert_eq!(kv[2].1.as_str()?, "text\n\n");
+        assert_eq!(kv[3].1.as_str()?, "one two\nthree\n");
+        assert_eq!(kv[4].1.as_str()?, "end");
+        Ok(())
+    }
+
+    #[test]
+    fn test_comments() -> Result<()> {
+        let doc = YamlParser::parse(
+            r#"
+# leading
+# comment
+a: 1  # trailing
+b:
+  # nested
+  - 2
+  - 3 # three
+"#,
+        )?;
+        let Document::Mapping(m) = &doc else {
+            return Err(anyhow!("expected a mapping"));
+        };
+        let a = m[0].fragments()?;
+        assert_eq!(
+            a[0].comment(),
+            Some(("leading\ncomment", &CommentFormat::Hash))
+        );
+        assert_eq!(a[3].comment(), Some(("trailing", &CommentFormat::Hash)));
+        let (_, b) = m[1].as_kv()?;
+        let Document::Sequence(s) = b else {
+            return Err(anyhow!("expected a sequence"));
+        };
+        assert_eq!(s[0].fragments()?[0].comment().unwrap().0, "nested");
+        assert_eq!(s[1].fragments()?[1].comment().unwrap().0, "three");
+        Ok(())
+    }
+
+    #[test]
+    fn test_errors() -> Result<()> {
+        let err = YamlParser::parse("a: 1\n   b: 2").unwrap_err();
+        assert_eq!(
+            err.to_string(),
+            "syntax error: bad indentation at 2:4\n|    b: 2\n|    ^"
+        );
+        assert!(YamlParser::parse("a: &x 1").is_err());
+        assert!(YamlParser::parse("[1, 2").is_err());
+        assert!(YamlParser::parse("a: 1\n---\nb: 2").is_err());
+        let err = YamlParser::parse("a:\n\tb: 1").unwrap_err();
+        assert_eq!(
+            err.to_string(),
+            "syntax error: tabs are not allowed for indentation at 2:1\n| \tb: 1\n| ^"
+        );
+        assert!(YamlParser::parse("a:\n  - 1\n \t- 2").is_err());
+        Ok(())
+    }
+}
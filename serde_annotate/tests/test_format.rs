@@ -2,6 +2,7 @@ use anyhow::Result;
 use serde::{Deserialize, Serialize};
 use serde_annotate::serialize;
 use serde_annotate::Annotate;
+use serde_annotate::Document;
 
 fn fixdoc(doc: &str) -> String {
     let mut s = String::new();
@@ -22,6 +23,16 @@ fn fixdoc(doc: &str) -> String {
     s
 }
 
+// Like `fixdoc`, but keeps the blank lines which separate TOML tables.
+fn fixtoml(doc: &str) -> String {
+    let doc = doc.trim_start_matches('\n');
+    let dedent = doc.find(|c: char| !c.is_whitespace()).unwrap();
+    doc.split('\n')
+        .map(|line| line.get(dedent..).unwrap_or(""))
+        .collect::<Vec<_>>()
+        .join("\n")
+}
+
 // Emit and check the string form out of our serializer.
 // Parse through the published deserializers and check for equality.
 macro_rules! tester {
@@ -73,6 +84,22 @@ macro_rules! tester {
         assert_eq!(string, fixdoc($expect));
         let decode: $t = serde_yaml::from_str(&string)?;
         assert_eq!($value, &decode);
+        let decode: $t = serde_annotate::Deserialize::from(Document::from_yaml(&string)?).into()?;
+        assert_eq!($value, &decode);
+    }};
+    (relax_yaml, $t:ty, $value:expr, $expect:expr) => {{
+        let doc = serialize($value)?;
+        let string = doc.to_yaml().to_string();
+        assert_eq!(string, fixdoc($expect));
+        let decode: $t = serde_annotate::Deserialize::from(Document::from_yaml(&string)?).into()?;
+        assert_eq!($value, &decode);
+    }};
+    (relax_toml, $t:ty, $value:expr, $expect:expr) => {{
+        let doc = serialize($value)?;
+        let string = doc.to_toml().to_string();
+        assert_eq!(string, fixtoml($expect));
+        let decode: $t = serde_annotate::Deserialize::from(Document::from_toml(&string)?).into()?;
+        assert_eq!($value, &decode);
     }};
     (ser_yaml, $t:ty, $value:expr, $expect:expr) => {{
         let doc = serialize($value)?;
@@ -251,6 +278,23 @@ fn test_sfdp() -> Result<()> {
           reserved: 0b11111111"#
     );
 
+    tester!(
+        relax_toml,
+        Sfdp,
+        &value,
+        r#"
+        [header]
+        # Signature value='SFDP' (should be 'SFDP')
+        signature = 0x50444653
+        # SFDP Version
+        minor = 6
+        major = 1
+        # Number of parameter headers (minus 1)
+        nph = 2
+        # Reserved field should be all ones
+        reserved = 0b11111111"#
+    );
+
     Ok(())
 }
 
@@ -262,7 +306,6 @@ enum NesAddress {
     Prg(#[annotate(format=hex)] u8, #[annotate(format=hex)] u16),
     #[annotate(format=compact, comment="NES CHR bank:address")]
     Chr(#[annotate(format=hex)] u8, #[annotate(format=hex)] u16),
-    // TODO(serde-annotate#6): Currently, we do not emit comments for unit variants.
     #[annotate(comment = "Bad Address")]
     Invalid,
 }
@@ -340,7 +383,7 @@ fn test_nes_addresses() -> Result<()> {
             0xFFFC,
             0xFFFE
           ],
-          inv: "Invalid"
+          inv: "Invalid" // Bad Address
         }"#
     );
 
@@ -363,7 +406,28 @@ fn test_nes_addresses() -> Result<()> {
             - 0xFFFA
             - 0xFFFC
             - 0xFFFE
-          inv: Invalid"#
+          inv: Invalid # Bad Address"#
+    );
+
+    tester!(
+        relax_toml,
+        Addresses,
+        &value,
+        r#"
+        vectors = [0xFFFA, 0xFFFC, 0xFFFE]
+        inv = "Invalid" # Bad Address
+
+        [a]
+        # NES file offset
+        File = 16400
+
+        [b]
+        # NES PRG bank:address
+        Prg = [0x1, 0x8000]
+
+        [c]
+        # NES CHR bank:address
+        Chr = [0x2, 0x400]"#
     );
 
     Ok(())
@@ -394,11 +458,10 @@ fn test_bytes() -> Result<()> {
         fourth_stanza: "the lamb was sure to go".into(),
     };
 
-    // We can't test deserialization of the yaml data yet because we don't yet
-    // have a parser or adapter for yaml that emits a `serde_annotate::Document`
-    // to feed to our deserializer implementation.
+    // serde_yaml can't decode the hexdump and xxd stanzas, so use our own
+    // yaml parser to test deserialization.
     tester!(
-        ser_yaml,
+        relax_yaml,
         Poem,
         &value,
         r#"
@@ -514,6 +577,239 @@ fn test_bytes() -> Result<()> {
         }"#
     );
 
+    tester!(
+        relax_toml,
+        Poem,
+        &value,
+        r#"
+        # No special bytes encoding
+        first_stanza = [
+          0x4D, 0x61, 0x72, 0x79, 0x20, 0x68, 0x61, 0x64, 0x20, 0x61, 0x20, 0x6C, 0x69, 0x74, 0x74, 0x6C,
+          0x65, 0x20, 0x6C, 0x61, 0x6D, 0x62,
+        ]
+        # Encoded as `hexstr`
+        second_stanza = "69747320666c656563652077617320776869746520617320736e6f77"
+        # Encoded as `hexdump`
+        third_stanza = '''
+        00000000  45 76 65 72 79 77 68 65  72 65 20 74 68 61 74 20  |Everywhere that |
+        00000010  4d 61 72 79 20 77 65 6e  74                       |Mary went|'''
+        # Encoded as `xxd`
+        fourth_stanza = '''
+        00000000: 7468 6520 6c61 6d62 2077 6173 2073 7572  the lamb was sur
+        00000010: 6520 746f 2067 6f                        e to go'''"#
+    );
+
+    Ok(())
+}
+
+#[derive(Deserialize, Annotate, Debug, PartialEq)]
+struct Blobs {
+    #[serde(with = "serde_bytes")]
+    #[annotate(format = base64)]
+    key: Vec<u8>,
+    #[serde(with = "serde_bytes")]
+    #[annotate(format = base64url)]
+    token: Vec<u8>,
+    #[serde(with = "serde_bytes")]
+    #[annotate(format = base32)]
+    secret: Vec<u8>,
+    #[serde(with = "serde_bytes")]
+    #[annotate(format = c_array)]
+    magic: Vec<u8>,
+    #[serde(with = "serde_bytes")]
+    #[annotate(format = xxd_i)]
+    image: Vec<u8>,
+}
+
+#[test]
+fn test_bytes_encodings() -> Result<()> {
+    let value = Blobs {
+        key: vec![0x98, 0xab, 0x45, 0xcd, 0xea, 0xff],
+        token: vec![0xfb, 0xff, 0xbf, 0x10],
+        secret: "foobar".into(),
+        magic: vec![0x7f, b'E', b'L', b'F'],
+        image: (0..16).collect(),
+    };
+    tester!(
+        relax_yaml,
+        Blobs,
+        &value,
+        r#"
+        ---
+        key: mKtFzer/
+        token: "-_-_EA"
+        secret: MZXW6YTBOI======
+        magic: "{0x7f, 0x45, 0x4c, 0x46}"
+        image: |-
+          unsigned char data[] = {
+            0x00, 0x01, 0x02, 0x03, 0x04, 0x05, 0x06, 0x07, 0x08, 0x09, 0x0a, 0x0b,
+            0x0c, 0x0d, 0x0e, 0x0f
+          };
+          unsigned int data_len = 16;"#
+    );
+    tester!(
+        relax_json5,
+        Blobs,
+        &value,
+        r#"
+        {
+          key: "mKtFzer/",
+          token: "-_-_EA",
+          secret: "MZXW6YTBOI======",
+          magic: "{0x7f, 0x45, 0x4c, 0x46}",
+          image: "unsigned char data[] = {\
+          0x00, 0x01, 0x02, 0x03, 0x04, 0x05, 0x06, 0x07, 0x08, 0x09, 0x0a, 0x0b,\
+          0x0c, 0x0d, 0x0e, 0x0f\
+        };\
+        unsigned int data_len = 16;"
+        }"#
+    );
+
+    // Encoded text which is also a valid hex string decodes as hex.
+    let value = Blobs {
+        key: vec![0x00, 0x10, 0x83],
+        token: vec![],
+        secret: vec![],
+        magic: vec![],
+        image: vec![],
+    };
+    let string = serialize(&value)?.to_yaml().to_string();
+    assert!(string.contains("key: ABCD"));
+    let decode: Blobs = serde_annotate::Deserialize::from(Document::from_yaml(&string)?).into()?;
+    assert_eq!(decode.key, [0xab, 0xcd]);
+    Ok(())
+}
+
+#[derive(Deserialize, Annotate, Debug, PartialEq)]
+struct FlashImage {
+    #[serde(with = "serde_bytes")]
+    #[annotate(format = ihex(base = 0x0800_0000))]
+    image: Vec<u8>,
+    #[serde(with = "serde_bytes")]
+    #[annotate(format = srec)]
+    otp: Vec<u8>,
+}
+
+#[test]
+fn test_bytes_images() -> Result<()> {
+    let mut image = b"boot".to_vec();
+    image.resize(40, 0xFF);
+    image.extend(b"app");
+    let value = FlashImage {
+        image,
+        otp: vec![0xa5, 0x5a, 0x00, 0x01],
+    };
+    tester!(
+        relax_yaml,
+        FlashImage,
+        &value,
+        r#"
+        ---
+        image: |-
+          :020000040800F2
+          :10000000626F6F74FFFFFFFFFFFFFFFFFFFFFFFF48
+          :0B002000FFFFFFFFFFFFFFFF6170709C
+          :00000001FF
+        otp: |-
+          S0030000FC
+          S1070000A55A0001F8
+          S9030000FC"#
+    );
+    Ok(())
+}
+
+#[derive(Deserialize, Annotate, Debug, PartialEq)]
+struct MemoryDump {
+    #[serde(with = "serde_bytes")]
+    #[annotate(format = hexdump(group = 4, width = 8, offset = 0x100))]
+    rom: Vec<u8>,
+    #[serde(with = "serde_bytes")]
+    #[annotate(format = xxd(group = 4, width = 32, offset = 0x2000_0000))]
+    sram: Vec<u8>,
+}
+
+#[test]
+fn test_bytes_geometry() -> Result<()> {
+    let value = MemoryDump {
+        rom: "The quick brown fox".into(),
+        sram: "The quick brown fox jumped over the lazy dog!".into(),
+    };
+    tester!(
+        relax_yaml,
+        MemoryDump,
+        &value,
+        r#"
+        ---
+        rom: |-
+          00000100  54 68 65 20  71 75 69 63  |The quic|
+          00000108  6b 20 62 72  6f 77 6e 20  |k brown |
+          00000110  66 6f 78                  |fox|
+        sram: |-
+          20000000: 54686520 71756963 6b206272 6f776e20 666f7820 6a756d70 6564206f 76657220  The quick brown fox jumped over 
+          20000020: 74686520 6c617a79 20646f67 21                                            the lazy dog!"#
+    );
+    Ok(())
+}
+
+#[derive(Deserialize, Annotate, Debug, PartialEq)]
+struct Firmware {
+    #[serde(with = "serde_bytes")]
+    #[annotate(
+        format = xxd(group = 4, width = 8),
+        region(offset = 0, length = 4, label = "magic", format = hexstr),
+        region(offset = 4, length = 2, label = "version", format = dec),
+        region(offset = 8, length = 4, label = "entry", format = hex),
+        region(offset = 12, length = 8, label = "name"),
+    )]
+    header: Vec<u8>,
+    #[serde(with = "serde_bytes")]
+    #[annotate(format = hexdump, regions = image_regions())]
+    image: Vec<u8>,
+}
+
+impl Firmware {
+    fn image_regions(&self) -> Vec<serde_annotate::annotate::Region> {
+        use serde_annotate::annotate::Region;
+        vec![
+            Region::new(0, 16, "vectors"),
+            Region::new(16, self.image.len() - 16, "text"),
+        ]
+    }
+}
+
+#[test]
+fn test_bytes_regions() -> Result<()> {
+    let value = Firmware {
+        header: b"OTFW\x02\x00\x00\x00\x80\x00\x00\x20rom_ext\x00".to_vec(),
+        image: [&b"\x6f\x00\x00\x08".repeat(4), &b"text"[..]].concat(),
+    };
+    tester!(
+        relax_yaml,
+        Firmware,
+        &value,
+        r#"
+        ---
+        header: |-
+          00000000: 4f544657 02000000  OTFW....  # magic = 4f544657, version = 2
+          00000008: 80000020 726f6d5f  ... rom_  # entry = 0x20000080, name
+          00000010: 65787400           ext.
+        image: |-
+          00000000  6f 00 00 08 6f 00 00 08  6f 00 00 08 6f 00 00 08  |o...o...o...o...|  # vectors
+          00000010  74 65 78 74                                       |text|              # text"#
+    );
+    tester!(
+        relax_json5,
+        Firmware,
+        &value,
+        r#"
+        {
+          header: "00000000: 4f544657 02000000  OTFW....  # magic = 4f544657, version = 2\
+        00000008: 80000020 726f6d5f  ... rom_  # entry = 0x20000080, name\
+        00000010: 65787400           ext.",
+          image: "00000000  6f 00 00 08 6f 00 00 08  6f 00 00 08 6f 00 00 08  |o...o...o...o...|  # vectors\
+        00000010  74 65 78 74                                       |text|              # text"
+        }"#
+    );
     Ok(())
 }
 
@@ -571,3 +867,460 @@ fn test_untagged_data() -> Result<()> {
     );
     Ok(())
 }
+
+#[derive(Deserialize, Annotate, Debug, PartialEq)]
+#[serde(rename_all = "camelCase", deny_unknown_fields)]
+struct Flash {
+    #[annotate(format=hex, comment="Base address")]
+    base_addr: u32,
+    #[annotate(comment = "Size in bytes")]
+    size_bytes: u32,
+    label: Label,
+    regions: Vec<Region>,
+}
+
+#[derive(Deserialize, Annotate, Debug, PartialEq)]
+#[serde(transparent)]
+struct Label(String);
+
+#[derive(Deserialize, Annotate, Debug, PartialEq)]
+#[serde(tag = "kind", rename_all = "snake_case")]
+enum Region {
+    Rom {
+        #[annotate(format = hex)]
+        base: u32,
+    },
+    MainRam {
+        #[annotate(format=hex, comment="Start of RAM")]
+        base: u32,
+        size: u32,
+    },
+}
+
+#[test]
+fn test_serde_container_attrs() -> Result<()> {
+    let flash = Flash {
+        base_addr: 0x2000_0000,
+        size_bytes: 4096,
+        label: Label("boot".into()),
+        regions: vec![
+            Region::Rom { base: 0x8000 },
+            Region::MainRam {
+                base: 0x1000,
+                size: 256,
+            },
+        ],
+    };
+    tester!(
+        json5,
+        Flash,
+        &flash,
+        r#"
+        {
+          // Base address
+          baseAddr: 0x20000000,
+          // Size in bytes
+          sizeBytes: 4096,
+          label: "boot",
+          regions: [
+            {
+              kind: "rom",
+              base: 0x8000
+            },
+            {
+              kind: "main_ram",
+              // Start of RAM
+              base: 0x1000,
+              size: 256
+            }
+          ]
+        }"#
+    );
+    tester!(
+        relax_json5,
+        Flash,
+        &flash,
+        r#"
+        {
+          // Base address
+          baseAddr: 0x20000000,
+          // Size in bytes
+          sizeBytes: 4096,
+          label: "boot",
+          regions: [
+            {
+              kind: "rom",
+              base: 0x8000
+            },
+            {
+              kind: "main_ram",
+              // Start of RAM
+              base: 0x1000,
+              size: 256
+            }
+          ]
+        }"#
+    );
+    Ok(())
+}
+
+#[derive(Annotate, Deserialize, Debug, PartialEq)]
+#[annotate(format = hex, comment = "Timer registers")]
+struct Timer {
+    ctrl: u32,
+    compare: u64,
+    #[annotate(format = dec, comment = "Prescaler")]
+    prescale: u16,
+}
+
+#[derive(Annotate, Deserialize, Debug, PartialEq)]
+#[annotate(format = compact)]
+struct Range(u32, u32);
+
+#[derive(Annotate, Deserialize, Debug, PartialEq)]
+#[annotate(comment = "Timer mode")]
+enum TimerMode {
+    #[annotate(comment = "Stopped")]
+    Off,
+    Periodic,
+}
+
+#[derive(Annotate, Deserialize, Debug, PartialEq)]
+struct Peripheral {
+    timer: Timer,
+    window: Range,
+    mode: TimerMode,
+}
+
+#[test]
+fn test_container_annotations() -> Result<()> {
+    let p = Peripheral {
+        timer: Timer {
+            ctrl: 1,
+            compare: 0x8000,
+            prescale: 16,
+        },
+        window: Range(10, 20),
+        mode: TimerMode::Off,
+    };
+    tester!(
+        json5,
+        Peripheral,
+        &p,
+        r#"
+        {
+          // Timer registers
+          timer: {
+            ctrl: 0x1,
+            compare: 0x8000,
+            // Prescaler
+            prescale: 16
+          },
+          window: [10, 20],
+          // Timer mode
+          mode: "Off" // Stopped
+        }"#
+    );
+    tester!(
+        yaml,
+        Peripheral,
+        &p,
+        r#"
+        ---
+        # Timer registers
+        timer:
+          ctrl: 0x1
+          compare: 0x8000
+          # Prescaler
+          prescale: 16
+        window: [10, 20]
+        # Timer mode
+        mode: "Off" # Stopped"#
+    );
+    tester!(
+        relax_toml,
+        Peripheral,
+        &p,
+        r#"
+        window = [10, 20]
+        # Timer mode
+        mode = "Off" # Stopped
+
+        # Timer registers
+        [timer]
+        ctrl = 0x1
+        compare = 0x8000
+        # Prescaler
+        prescale = 16"#
+    );
+    tester!(
+        json5,
+        Timer,
+        &p.timer,
+        r#"
+        // Timer registers
+        {
+          ctrl: 0x1,
+          compare: 0x8000,
+          // Prescaler
+          prescale: 16
+        }"#
+    );
+    let decode: Peripheral = serde_annotate::Deserialize::from(serialize(&p)?).into()?;
+    assert_eq!(p, decode);
+    Ok(())
+}
+
+/// The doc comment of the type itself is not emitted.
+#[derive(Annotate, Deserialize, Debug, PartialEq)]
+#[annotate(doc_comments)]
+struct Uart {
+    /// Baud rate in bits per second.
+    baud: u32,
+    /// Parity checking.
+    ///
+    /// Even parity is the most common.
+    parity: Parity,
+    /// Overridden by the explicit comment.
+    #[annotate(format = hex, comment = "Base address")]
+    base: u32,
+    fifo: bool,
+}
+
+#[derive(Annotate, Deserialize, Debug, PartialEq)]
+#[annotate(doc_comments)]
+enum Parity {
+    /// No parity bit
+    None,
+    Even,
+}
+
+#[test]
+fn test_doc_comments() -> Result<()> {
+    let uart = Uart {
+        baud: 115200,
+        parity: Parity::None,
+        base: 0x4000_0000,
+        fifo: true,
+    };
+    tester!(
+        json5,
+        Uart,
+        &uart,
+        r#"
+        {
+          // Baud rate in bits per second.
+          baud: 115200,
+          // Parity checking.
+          //
+          // Even parity is the most common.
+          parity: "None", // No parity bit
+          // Base address
+          base: 0x40000000,
+          fifo: true
+        }"#
+    );
+    tester!(
+        yaml,
+        Uart,
+        &uart,
+        r#"
+        ---
+        # Baud rate in bits per second.
+        baud: 115200
+        # Parity checking.
+        #
+        # Even parity is the most common.
+        parity: None # No parity bit
+        # Base address
+        base: 0x40000000
+        fifo: true"#
+    );
+    Ok(())
+}
+
+#[derive(Annotate, Deserialize, Debug, PartialEq)]
+#[annotate(comment_style = hash)]
+struct Device {
+    #[annotate(format = quoted, comment = "Always quoted")]
+    name: String,
+    #[annotate(format = unquoted, comment = "Quoted only when needed")]
+    vendor: String,
+    #[annotate(comment_style = block, comment = "Block comment")]
+    id: u32,
+    #[annotate(comment_style = block, comment = "Multi-line\nblock comment")]
+    rev: u32,
+    #[annotate(comment_style = slashslash, comment = "Slash comment")]
+    bus: String,
+}
+
+#[test]
+fn test_string_and_comment_styles() -> Result<()> {
+    let dev = Device {
+        name: "uart0".into(),
+        vendor: "lowrisc".into(),
+        id: 1,
+        rev: 2,
+        bus: "tl".into(),
+    };
+    tester!(
+        hjson,
+        Device,
+        &dev,
+        r#"
+        {
+          # Always quoted
+          name: "uart0",
+          # Quoted only when needed
+          vendor: lowrisc
+          /* Block comment */
+          id: 1,
+          /*
+           * Multi-line
+           * block comment
+           */
+          rev: 2,
+          // Slash comment
+          bus: "tl"
+        }"#
+    );
+    tester!(
+        relax_json5,
+        Device,
+        &dev,
+        r#"
+        {
+          // Always quoted
+          name: "uart0",
+          // Quoted only when needed
+          vendor: "lowrisc",
+          /* Block comment */
+          id: 1,
+          /*
+           * Multi-line
+           * block comment
+           */
+          rev: 2,
+          // Slash comment
+          bus: "tl"
+        }"#
+    );
+    tester!(
+        yaml,
+        Device,
+        &dev,
+        r#"
+        ---
+        # Always quoted
+        name: "uart0"
+        # Quoted only when needed
+        vendor: lowrisc
+        # Block comment
+        id: 1
+        # Multi-line
+        # block comment
+        rev: 2
+        # Slash comment
+        bus: tl"#
+    );
+    Ok(())
+}
+
+#[derive(Annotate, Deserialize, Debug, PartialEq)]
+struct Sources {
+    #[annotate(comment_style = block, comment = "Matches src/*/*.rs")]
+    glob: String,
+    #[annotate(comment_style = block, comment = "Ends with */\nand continues")]
+    end: String,
+}
+
+#[test]
+fn test_block_comment_terminator() -> Result<()> {
+    let sources = Sources {
+        glob: "src/*/*.rs".into(),
+        end: "*/".into(),
+    };
+    tester!(
+        relax_json5,
+        Sources,
+        &sources,
+        r#"
+        {
+          // Matches src/*/*.rs
+          glob: "src/*/*.rs",
+          // Ends with */
+          // and continues
+          end: "*/"
+        }"#
+    );
+    tester!(
+        hjson,
+        Sources,
+        &sources,
+        r#"
+        {
+          # Matches src/*/*.rs
+          glob: "src/*/*.rs",
+          # Ends with */
+          # and continues
+          end: "*/"
+        }"#
+    );
+    Ok(())
+}
+
+#[derive(Annotate, Deserialize, Debug, PartialEq)]
+#[annotate(format = hex, width = 8)]
+struct Dma {
+    addr: u32,
+    #[annotate(group = 4)]
+    len: u32,
+    #[annotate(format = bin, bits = 8, group = 4)]
+    flags: u8,
+    #[annotate(format = dec, group = 3)]
+    count: u64,
+}
+
+#[test]
+fn test_int_width_and_grouping() -> Result<()> {
+    let dma = Dma {
+        addr: 0x1000,
+        len: 0x100,
+        flags: 5,
+        count: 1000000,
+    };
+    tester!(
+        relax_json5,
+        Dma,
+        &dma,
+        r#"
+        {
+          addr: 0x00001000,
+          len: "0x0000_0100",
+          flags: 5,
+          count: "1_000_000"
+        }"#
+    );
+    tester!(
+        relax_yaml,
+        Dma,
+        &dma,
+        r#"
+        ---
+        addr: 0x00001000
+        len: 0x0000_0100
+        flags: 0b0000_0101
+        count: 1_000_000"#
+    );
+    tester!(
+        relax_toml,
+        Dma,
+        &dma,
+        r#"
+        addr = 0x00001000
+        len = 0x0000_0100
+        flags = 0b0000_0101
+        count = 1_000_000"#
+    );
+    Ok(())
+}
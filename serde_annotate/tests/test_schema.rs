@@ -0,0 +1,436 @@
+use anyhow::Result;
+use serde_annotate::schema::schema_for;
+use serde_annotate::{serialize, Annotate, Document, Schema};
+
+#[derive(Debug, serde::Deserialize, Annotate, Schema)]
+enum Access {
+    #[annotate(comment = "Read only")]
+    RO,
+    RW,
+    Fixed(u8),
+}
+
+#[derive(Debug, serde::Deserialize, Annotate, Schema)]
+struct Register {
+    #[annotate(comment = "Name of the register")]
+    name: String,
+    #[annotate(format = hex, comment = "Offset from the base address")]
+    offset: u32,
+    access: Access,
+    #[serde(rename = "resetValue")]
+    reset: Option<u16>,
+    #[serde(skip)]
+    #[allow(dead_code)]
+    cache: u32,
+}
+
+#[derive(Debug, serde::Deserialize, Annotate, Schema)]
+struct Block {
+    #[annotate(format = hexdump)]
+    data: Vec<u8>,
+    regs: Vec<Register>,
+    coord: (u8, i8),
+}
+
+#[test]
+fn test_struct_schema() -> Result<()> {
+    let schema = schema_for::<Register>().to_json5().to_string();
+    assert_eq!(
+        schema,
+        r#"{
+  $schema: "https://json-schema.org/draft/2020-12/schema",
+  type: "object",
+  properties: {
+    name: {
+      type: "string",
+      description: "Name of the register"
+    },
+    offset: {
+      type: "integer",
+      minimum: 0,
+      maximum: 4294967295,
+      description: "Offset from the base address",
+      "x-format": "hex"
+    },
+    access: {
+      oneOf: [
+        {
+          "const": "RO",
+          description: "Read only"
+        },
+        {
+          "const": "RW"
+        },
+        {
+          type: "object",
+          properties: {
+            Fixed: {
+              type: "integer",
+              minimum: 0,
+              maximum: 255
+            }
+          },
+          required: [
+            "Fixed"
+          ],
+          additionalProperties: false
+        }
+      ]
+    },
+    resetValue: {
+      anyOf: [
+        {
+          type: "integer",
+          minimum: 0,
+          maximum: 65535
+        },
+        {
+          type: "null"
+        }
+      ]
+    }
+  },
+  required: [
+    "name",
+    "offset",
+    "access"
+  ]
+}"#
+    );
+    Ok(())
+}
+
+#[test]
+fn test_nested_schema() -> Result<()> {
+    let schema = Block::schema().to_json5().compact(true).to_string();
+    assert!(schema.starts_with(concat!(
+        r#"{type: "object", properties: {"#,
+        r#"data: {type: "array", items: {type: "integer", minimum: 0, maximum: 255}, "x-format": "hexdump"}, "#,
+        r#"regs: {type: "array", items: {type: "object", properties: {name: "#,
+    )));
+    assert!(schema.ends_with(concat!(
+        r#"coord: {type: "array", prefixItems: [{type: "integer", minimum: 0, maximum: 255}, "#,
+        r#"{type: "integer", minimum: -128, maximum: 127}], minItems: 2, maxItems: 2}}, "#,
+        r#"required: ["data", "regs", "coord"]}"#,
+    )));
+    Ok(())
+}
+
+#[test]
+fn test_validate_against_derived_schema() -> Result<()> {
+    let schema = schema_for::<Register>();
+    let good = Document::parse(
+        r#"{
+            name: "ctrl",
+            // Offsets are usually written in hex.
+            offset: 0x40,
+            access: {Fixed: 3},
+            resetValue: null,
+        }"#,
+    )?;
+    assert!(good.validate(&schema)?.is_empty());
+
+    let bad = Document::parse(r#"{name: "ctrl", offset: -4, access: "WO", resetValue: 0x10000}"#)?;
+    let violations = bad
+        .validate(&schema)?
+        .iter()
+        .map(|v| v.to_string())
+        .collect::<Vec<_>>();
+    assert_eq!(
+        violations,
+        vec![
+            "`offset`: -4 is less than the minimum 0",
+            r#"`access`: "WO" does not match any alternative of `oneOf`"#,
+            "`resetValue`: 0x10000 is greater than the maximum 65535",
+        ]
+    );
+    Ok(())
+}
+
+#[derive(Debug, serde::Deserialize, Annotate, Schema)]
+#[serde(rename_all = "camelCase", deny_unknown_fields)]
+struct Window {
+    base_addr: u32,
+    name: Name,
+    kind: Kind,
+    mode: Mode,
+    value: Value,
+}
+
+#[derive(Debug, serde::Deserialize, Annotate, Schema)]
+#[serde(transparent)]
+struct Name(String);
+
+#[derive(Debug, serde::Deserialize, Annotate, Schema)]
+#[serde(tag = "type", rename_all = "lowercase")]
+#[allow(dead_code)]
+enum Kind {
+    Rom,
+    #[annotate(comment = "Random access memory")]
+    Ram {
+        size: u32,
+    },
+}
+
+#[derive(Debug, serde::Deserialize, Annotate, Schema)]
+#[serde(tag = "t", content = "c")]
+#[allow(dead_code)]
+enum Mode {
+    Off,
+    Level(u8),
+}
+
+#[derive(Debug, serde::Deserialize, Annotate, Schema)]
+#[serde(untagged)]
+#[allow(dead_code)]
+enum Value {
+    Flag(bool),
+    Count(u8),
+}
+
+#[test]
+fn test_container_attrs_schema() -> Result<()> {
+    let schema = Window::schema().to_json5().compact(true).to_string();
+    assert_eq!(
+        schema,
+        concat!(
+            r#"{type: "object", properties: {"#,
+            r#"baseAddr: {type: "integer", minimum: 0, maximum: 4294967295}, "#,
+            r#"name: {type: "string"}, "#,
+            r#"kind: {oneOf: ["#,
+            r#"{type: "object", properties: {type: {"const": "rom"}}, required: ["type"]}, "#,
+            r#"{type: "object", properties: {type: {"const": "ram"}, "#,
+            r#"size: {type: "integer", minimum: 0, maximum: 4294967295}}, "#,
+            r#"required: ["type", "size"], description: "Random access memory"}]}, "#,
+            r#"mode: {oneOf: ["#,
+            r#"{type: "object", properties: {t: {"const": "Off"}}, "#,
+            r#"required: ["t"], additionalProperties: false}, "#,
+            r#"{type: "object", properties: {t: {"const": "Level"}, "#,
+            r#"c: {type: "integer", minimum: 0, maximum: 255}}, "#,
+            r#"required: ["t", "c"], additionalProperties: false}]}, "#,
+            r#"value: {anyOf: [{type: "boolean"}, {type: "integer", minimum: 0, maximum: 255}]}}, "#,
+            r#"required: ["baseAddr", "name", "kind", "mode", "value"], "#,
+            r#"additionalProperties: false}"#,
+        )
+    );
+
+    let window = Document::parse(
+        r#"{baseAddr: 0x1000, name: "sram", kind: {type: "ram", size: 4096},
+            mode: {t: "Level", c: 3}, value: true}"#,
+    )?;
+    assert!(window.validate(&schema_for::<Window>())?.is_empty());
+    let extra = Document::parse(
+        r#"{baseAddr: 0, name: "x", kind: {type: "rom"}, mode: {t: "Off"}, value: 1, extra: 1}"#,
+    )?;
+    let violations = extra.validate(&schema_for::<Window>())?;
+    assert_eq!(violations.len(), 1);
+    Ok(())
+}
+
+#[derive(Debug, serde::Deserialize, Annotate, Schema)]
+#[annotate(format = hex, comment = "Interrupt controller")]
+#[allow(dead_code)]
+struct Intc {
+    enable: u32,
+    #[annotate(format = dec)]
+    priority: u8,
+}
+
+#[derive(Debug, serde::Deserialize, Annotate, Schema)]
+#[annotate(format = compact)]
+#[allow(dead_code)]
+struct Span(u16, u16);
+
+#[test]
+fn test_container_annotations_schema() -> Result<()> {
+    let schema = Intc::schema().to_json5().compact(true).to_string();
+    assert_eq!(
+        schema,
+        concat!(
+            r#"{type: "object", properties: {"#,
+            r#"enable: {type: "integer", minimum: 0, maximum: 4294967295, "x-format": "hex"}, "#,
+            r#"priority: {type: "integer", minimum: 0, maximum: 255, "x-format": "dec"}}, "#,
+            r#"required: ["enable", "priority"], description: "Interrupt controller"}"#,
+        )
+    );
+    let schema = Span::schema().to_json5().compact(true).to_string();
+    assert!(schema.ends_with(r#"minItems: 2, maxItems: 2, "x-format": "compact"}"#));
+    Ok(())
+}
+
+#[derive(Debug, serde::Deserialize, Annotate, Schema)]
+struct Encoded {
+    #[serde(with = "serde_bytes")]
+    #[annotate(format = hexstr)]
+    hexstr: Vec<u8>,
+    #[serde(with = "serde_bytes")]
+    #[annotate(format = hexdump)]
+    hexdump: Vec<u8>,
+    #[serde(with = "serde_bytes")]
+    #[annotate(format = xxd)]
+    xxd: Vec<u8>,
+    #[serde(with = "serde_bytes")]
+    #[annotate(format = base64)]
+    base64: Vec<u8>,
+    #[serde(with = "serde_bytes")]
+    #[annotate(format = base64url)]
+    base64url: Vec<u8>,
+    #[serde(with = "serde_bytes")]
+    #[annotate(format = base32)]
+    base32: Vec<u8>,
+    #[serde(with = "serde_bytes")]
+    #[annotate(format = c_array)]
+    c_array: Vec<u8>,
+    #[serde(with = "serde_bytes")]
+    #[annotate(format = xxd_i)]
+    xxd_i: Vec<u8>,
+    #[serde(with = "serde_bytes")]
+    #[annotate(format = ihex(base = 0x1000))]
+    ihex: Vec<u8>,
+    #[serde(with = "serde_bytes")]
+    #[annotate(format = srec)]
+    srec: Vec<u8>,
+}
+
+#[test]
+fn test_validate_bytes_formats() -> Result<()> {
+    let data = b"serde_annotate".to_vec();
+    let value = Encoded {
+        hexstr: data.clone(),
+        hexdump: data.clone(),
+        xxd: data.clone(),
+        base64: data.clone(),
+        base64url: data.clone(),
+        base32: data.clone(),
+        c_array: data.clone(),
+        xxd_i: data.clone(),
+        ihex: data.clone(),
+        srec: data,
+    };
+    let schema = schema_for::<Encoded>();
+    // Every bytes format decodes to an array of bytes, whichever document
+    // type it was written to.
+    for text in [
+        serialize(&value)?.to_json5().to_string(),
+        serialize(&value)?.to_yaml().to_string(),
+    ] {
+        let doc = Document::parse(&text).or_else(|_| Document::from_yaml(&text))?;
+        let violations = doc.validate(&schema)?;
+        assert!(violations.is_empty(), "{text}: {violations:?}");
+    }
+    Ok(())
+}
+
+#[derive(Debug, serde::Deserialize, Schema)]
+struct Pair<K, V> {
+    key: K,
+    value: Option<V>,
+}
+
+#[derive(Debug, serde::Deserialize, Schema)]
+enum Either<L, R>
+where
+    L: Clone,
+{
+    Left(L),
+    Right(R),
+}
+
+#[test]
+fn test_generic_schema() -> Result<()> {
+    let schema = schema_for::<Pair<String, Either<u8, bool>>>();
+    let doc = Document::parse(r#"{key: "a", value: {Left: 1}}"#)?;
+    assert!(doc.validate(&schema)?.is_empty());
+    let pair: Pair<String, Either<u8, bool>> = serde_annotate::from_str(r#"{key: "a"}"#)?;
+    assert_eq!(pair.key, "a");
+    assert!(pair.value.is_none());
+    let doc = Document::parse(r#"{key: "a"}"#)?;
+    assert!(doc.validate(&schema)?.is_empty());
+    let doc = Document::parse(r#"{key: 1, value: {Right: true}}"#)?;
+    assert!(!doc.validate(&schema)?.is_empty());
+    let doc = Document::parse(r#"{key: "a", value: {Left: true}}"#)?;
+    assert!(!doc.validate(&schema)?.is_empty());
+    Ok(())
+}
+
+#[derive(Debug, serde::Deserialize, Schema)]
+struct Node {
+    name: String,
+    children: Vec<Node>,
+}
+
+#[derive(Debug, serde::Deserialize, Schema)]
+struct Tree {
+    root: Option<Node>,
+    size: u8,
+}
+
+#[test]
+fn test_recursive_schema() -> Result<()> {
+    let schema = schema_for::<Tree>();
+    assert_eq!(
+        schema.clone().to_json5().compact(true).to_string(),
+        concat!(
+            r#"{$schema: "https://json-schema.org/draft/2020-12/schema", type: "object", "#,
+            r##"properties: {root: {anyOf: [{$ref: "#/$defs/Node"}, {type: "null"}]}, "##,
+            r#"size: {type: "integer", minimum: 0, maximum: 255}}, "#,
+            r#"required: ["size"], $defs: {Node: {type: "object", properties: {"#,
+            r##"name: {type: "string"}, children: {type: "array", items: {$ref: "#/$defs/Node"}}}, "##,
+            r#"required: ["name", "children"]}}}"#
+        )
+    );
+    let text = r#"{root: {name: "a", children: [{name: "b", children: []}]}, size: 2}"#;
+    assert!(Document::parse(text)?.validate(&schema)?.is_empty());
+    let tree: Tree = serde_annotate::from_str(text)?;
+    assert_eq!(tree.size, 2);
+    assert_eq!(
+        tree.root.map(|r| r.children[0].name.clone()),
+        Some("b".into())
+    );
+    let doc =
+        Document::parse(r#"{root: {name: "a", children: [{name: 1, children: []}]}, size: 2}"#)?;
+    assert!(!doc.validate(&schema)?.is_empty());
+
+    // A recursive root type is defined like any other.
+    let schema = schema_for::<Node>();
+    let doc = Document::parse(r#"{name: "a", children: [{name: "b", children: []}]}"#)?;
+    assert!(doc.validate(&schema)?.is_empty());
+    let doc = Document::parse(r#"{name: "a", children: [{children: []}]}"#)?;
+    assert!(!doc.validate(&schema)?.is_empty());
+    Ok(())
+}
+
+#[derive(Debug, serde::Deserialize, Schema)]
+struct Common {
+    id: u8,
+    label: Option<String>,
+}
+
+#[derive(Debug, serde::Deserialize, Schema)]
+struct Flattened {
+    #[serde(flatten)]
+    common: Common,
+    value: bool,
+}
+
+#[test]
+fn test_flatten_schema() -> Result<()> {
+    let schema = schema_for::<Flattened>();
+    assert_eq!(
+        schema.clone().to_json5().compact(true).to_string(),
+        concat!(
+            r#"{$schema: "https://json-schema.org/draft/2020-12/schema", type: "object", "#,
+            r#"properties: {value: {type: "boolean"}, id: {type: "integer", minimum: 0, maximum: 255}, "#,
+            r#"label: {anyOf: [{type: "string"}, {type: "null"}]}}, required: ["value", "id"]}"#
+        )
+    );
+    let text = r#"{id: 1, value: true}"#;
+    assert!(Document::parse(text)?.validate(&schema)?.is_empty());
+    let flattened: Flattened = serde_annotate::from_str(text)?;
+    assert_eq!(flattened.common.id, 1);
+    assert!(flattened.common.label.is_none());
+    assert!(flattened.value);
+    let doc = Document::parse(r#"{common: {id: 1}, value: true}"#)?;
+    assert!(!doc.validate(&schema)?.is_empty());
+    Ok(())
+}
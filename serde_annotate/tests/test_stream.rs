@@ -0,0 +1,132 @@
+use anyhow::Result;
+use serde::Serialize;
+use serde_annotate::{serialize, serialize_to_writer, Annotate, StreamFormat};
+use std::collections::BTreeMap;
+
+#[derive(Annotate)]
+enum Access {
+    #[annotate(comment = "Read only")]
+    RO,
+    #[annotate(format = compact, comment = "Fixed value")]
+    Fixed(#[annotate(format = hex)] u8, u8),
+    #[annotate(comment = "Write-1-to-clear")]
+    W1C { mask: u32 },
+    #[annotate(format = compact)]
+    Alias(String),
+}
+
+#[derive(Annotate)]
+#[annotate(format = compact, comment = "A pair")]
+struct Pair(
+    #[annotate(comment = "Low")] u8,
+    #[annotate(format = hex)] u8,
+);
+
+#[derive(Annotate)]
+#[annotate(format = hex, comment = "A register")]
+struct Register {
+    #[annotate(comment = "Name of the register")]
+    name: String,
+    #[annotate(format = hex, comment = "Offset from the base address")]
+    offset: u32,
+    access: Access,
+    #[annotate(format = block)]
+    description: String,
+    reset: Option<u16>,
+}
+
+#[derive(Annotate)]
+struct Block {
+    #[annotate(comment = "Registers in the block\nin address order")]
+    regs: Vec<Register>,
+    #[annotate(format = hexdump)]
+    #[serde(with = "serde_bytes")]
+    rom: Vec<u8>,
+    empty: Vec<u32>,
+    nothing: BTreeMap<String, u32>,
+    labels: BTreeMap<String, Vec<u16>>,
+    pairs: Vec<Pair>,
+    nested: Vec<Vec<u8>>,
+    unit: (),
+}
+
+fn block() -> Block {
+    Block {
+        regs: vec![
+            Register {
+                name: "ctrl".into(),
+                offset: 0x40,
+                access: Access::RO,
+                description: "Control\nregister".into(),
+                reset: None,
+            },
+            Register {
+                name: "status".into(),
+                offset: 0x44,
+                access: Access::Fixed(0x1f, 2),
+                description: "Status".into(),
+                reset: Some(7),
+            },
+            Register {
+                name: "irq".into(),
+                offset: 0x48,
+                access: Access::W1C { mask: 0xff },
+                description: String::new(),
+                reset: Some(0),
+            },
+            Register {
+                name: "alias".into(),
+                offset: 0x4c,
+                access: Access::Alias("ctrl".into()),
+                description: "x".into(),
+                reset: None,
+            },
+        ],
+        rom: (0..40).collect(),
+        empty: vec![],
+        nothing: BTreeMap::new(),
+        labels: BTreeMap::from([("boot".into(), vec![0x100, 0x200]), ("idle".into(), vec![])]),
+        pairs: vec![Pair(1, 2), Pair(3, 4)],
+        nested: vec![vec![1, 2], vec![], vec![3]],
+        unit: (),
+    }
+}
+
+fn stream<T: Serialize>(format: StreamFormat, value: &T) -> Result<String> {
+    let mut out = Vec::new();
+    serialize_to_writer(&mut out, format, value)?;
+    Ok(String::from_utf8(out)?)
+}
+
+#[test]
+fn test_stream_matches_document() -> Result<()> {
+    let value = block();
+    let doc = serialize(&value)?;
+    assert_eq!(
+        stream(StreamFormat::Json, &value)?,
+        doc.clone().to_json().to_string()
+    );
+    assert_eq!(
+        stream(StreamFormat::Json5, &value)?,
+        doc.clone().to_json5().to_string()
+    );
+    assert_eq!(
+        stream(StreamFormat::Yaml, &value)?,
+        doc.to_yaml().to_string()
+    );
+    Ok(())
+}
+
+#[test]
+fn test_stream_scalars() -> Result<()> {
+    assert_eq!(
+        stream(StreamFormat::Json5, &Access::RO)?,
+        "// Read only\n\"RO\""
+    );
+    assert_eq!(stream(StreamFormat::Yaml, &5u8)?, "---\n5");
+    assert_eq!(
+        stream(StreamFormat::Json5, &Access::Fixed(1, 2))?,
+        "{\n  // Fixed value\n  Fixed: [0x1, 2]\n}"
+    );
+    Ok(())
+}
@@ -1,6 +1,9 @@
-use crate::attr::{self, Attrs};
+use crate::attr::{self, Attrs, RenameRule};
 use proc_macro2::Span;
-use syn::{Data, DataEnum, DataStruct, DeriveInput, Error, Fields, Ident, Index, Member, Result};
+use syn::ext::IdentExt;
+use syn::{
+    Data, DataEnum, DataStruct, DeriveInput, Error, Fields, Ident, Index, Member, Result, Type,
+};
 
 #[derive(Debug)]
 pub enum Input<'a> {
@@ -8,11 +11,18 @@ pub enum Input<'a> {
     Enum(Enum<'a>),
 }
 
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Style {
+    Struct,
+    Tuple,
+    Unit,
+}
+
 #[derive(Debug)]
 pub struct Struct<'a> {
-    #[allow(unused)]
     pub attrs: Attrs<'a>,
     pub ident: Ident,
+    pub style: Style,
     pub fields: Vec<Field<'a>>,
 }
 
@@ -20,11 +30,11 @@ pub struct Struct<'a> {
 pub struct Field<'a> {
     pub attrs: Attrs<'a>,
     pub member: Member,
+    pub ty: &'a Type,
 }
 
 #[derive(Debug)]
 pub struct Enum<'a> {
-    #[allow(unused)]
     pub attrs: Attrs<'a>,
     pub ident: Ident,
     pub variants: Vec<Variant<'a>>,
@@ -34,6 +44,7 @@ pub struct Enum<'a> {
 pub struct Variant<'a> {
     pub attrs: Attrs<'a>,
     pub ident: Ident,
+    pub style: Style,
     pub fields: Vec<Field<'a>>,
 }
 
@@ -50,11 +61,18 @@ impl<'a> Input<'a> {
 impl<'a> Struct<'a> {
     fn from_syn(node: &'a DeriveInput, data: &'a DataStruct) -> Result<Self> {
         let attrs = attr::get(&node.attrs)?;
+        attrs.check_container()?;
         let span = Span::call_site();
-        let fields = Field::multiple_from_syn(&data.fields, span)?;
+        let mut fields = Field::multiple_from_syn(&data.fields, span)?;
+        if attrs.doc_comments {
+            for f in fields.iter_mut() {
+                f.attrs.use_doc_comment();
+            }
+        }
         Ok(Struct {
             attrs,
             ident: node.ident.clone(),
+            style: Style::from_syn(&data.fields),
             fields,
         })
     }
@@ -63,12 +81,19 @@ impl<'a> Struct<'a> {
 impl<'a> Enum<'a> {
     fn from_syn(node: &'a DeriveInput, data: &'a DataEnum) -> Result<Self> {
         let attrs = attr::get(&node.attrs)?;
+        attrs.check_container()?;
         let span = Span::call_site();
         let variants = data
             .variants
             .iter()
             .map(|node| {
-                let v = Variant::from_syn(node, span)?;
+                let mut v = Variant::from_syn(node, span)?;
+                if attrs.doc_comments {
+                    v.attrs.use_doc_comment();
+                    for f in v.fields.iter_mut() {
+                        f.attrs.use_doc_comment();
+                    }
+                }
                 Ok(v)
             })
             .collect::<Result<_>>()?;
@@ -90,25 +115,77 @@ impl<'a> Field<'a> {
     }
 
     fn from_syn(i: usize, node: &'a syn::Field, span: Span) -> Result<Self> {
+        let attrs = attr::get(&node.attrs)?;
+        attrs.check_member()?;
         Ok(Field {
-            attrs: attr::get(&node.attrs)?,
+            attrs,
             member: node.ident.clone().map(Member::Named).unwrap_or_else(|| {
                 Member::Unnamed(Index {
                     index: i as u32,
                     span,
                 })
             }),
+            ty: &node.ty,
         })
     }
 }
 
+impl Field<'_> {
+    /// Returns the name of a named field in the serialized document, given
+    /// the `rename_all` rule of its container.
+    pub fn serialized_name(&self, rule: Option<RenameRule>) -> Option<String> {
+        let Member::Named(id) = &self.member else {
+            return None;
+        };
+        let name = id.unraw().to_string();
+        Some(match (&self.attrs.serde.rename, rule) {
+            (Some(rename), _) => rename.clone(),
+            (None, Some(rule)) => rule.apply_to_field(&name),
+            (None, None) => name,
+        })
+    }
+}
+
+impl Enum<'_> {
+    /// Returns the `rename_all` rule which applies to the fields of `v`.
+    pub fn field_rule(&self, v: &Variant) -> Option<RenameRule> {
+        v.attrs
+            .serde
+            .rename_all
+            .or(self.attrs.serde.rename_all_fields)
+    }
+}
+
 impl<'a> Variant<'a> {
+    /// Returns the name of the variant in the serialized document, given
+    /// the `rename_all` rule of its enum.
+    pub fn serialized_name(&self, rule: Option<RenameRule>) -> String {
+        let name = self.ident.unraw().to_string();
+        match (&self.attrs.serde.rename, rule) {
+            (Some(rename), _) => rename.clone(),
+            (None, Some(rule)) => rule.apply_to_variant(&name),
+            (None, None) => name,
+        }
+    }
+
     fn from_syn(node: &'a syn::Variant, span: Span) -> Result<Self> {
         let attrs = attr::get(&node.attrs)?;
+        attrs.check_member()?;
         Ok(Variant {
             attrs,
             ident: node.ident.clone(),
+            style: Style::from_syn(&node.fields),
             fields: Field::multiple_from_syn(&node.fields, span)?,
         })
     }
 }
+
+impl Style {
+    fn from_syn(fields: &Fields) -> Self {
+        match fields {
+            Fields::Named(_) => Style::Struct,
+            Fields::Unnamed(_) => Style::Tuple,
+            Fields::Unit => Style::Unit,
+        }
+    }
+}
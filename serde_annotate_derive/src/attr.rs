@@ -1,5 +1,10 @@
+use proc_macro2::Group;
+use syn::meta::ParseNestedMeta;
 use syn::parse::ParseStream;
-use syn::{parenthesized, Attribute, Error, Ident, LitStr, Result, Token};
+use syn::{
+    parenthesized, token, Attribute, Error, Expr, ExprLit, Ident, Lit, LitInt, LitStr, Meta,
+    Result, Token,
+};
 
 #[derive(Debug, PartialEq)]
 pub enum Format {
@@ -9,10 +14,51 @@ pub enum Format {
     Decimal,
     Hex,
     Octal,
-    Compact,
     HexStr,
-    Hexdump,
-    Xxd,
+    Hexdump(Option<Dump>),
+    Xxd(Option<Dump>),
+    Base64,
+    Base64Url,
+    Base32,
+    CArray,
+    XxdInclude,
+    IntelHex(u32),
+    Srec(u32),
+    Quoted,
+    Unquoted,
+}
+
+/// The layout of a hexdump.
+#[derive(Debug, PartialEq)]
+pub struct Dump {
+    pub group: usize,
+    pub width: usize,
+    pub offset: u64,
+}
+
+/// A named range of bytes of a bytes member.
+#[derive(Debug, PartialEq)]
+pub struct Region {
+    pub offset: usize,
+    pub length: usize,
+    pub label: String,
+    pub format: Format,
+}
+
+#[derive(Debug, PartialEq)]
+pub enum Regions {
+    None,
+    Static(Vec<Region>),
+    Function(Ident),
+}
+
+#[derive(Debug, PartialEq)]
+pub enum CommentStyle {
+    None,
+    Standard,
+    Block,
+    Hash,
+    SlashSlash,
 }
 
 #[derive(Debug, PartialEq)]
@@ -27,34 +73,471 @@ pub enum Comment {
 pub struct Attrs<'a> {
     pub annotate: Option<&'a Attribute>,
     pub format: Format,
+    pub compact: bool,
+    pub width: Option<usize>,
+    pub bits: Option<usize>,
+    pub group: Option<usize>,
     pub comment: Comment,
+    pub comment_style: CommentStyle,
+    pub regions: Regions,
+    pub doc_comments: bool,
+    pub doc: Option<String>,
+    pub serde: Serde,
+}
+
+/// The subset of `#[serde(...)]` member and container attributes which
+/// affect the shape of the serialized document.
+#[derive(Debug, Default)]
+pub struct Serde {
+    pub rename: Option<String>,
+    pub skip: bool,
+    pub optional: bool,
+    pub rename_all: Option<RenameRule>,
+    pub rename_all_fields: Option<RenameRule>,
+    pub tag: Option<String>,
+    pub content: Option<String>,
+    pub untagged: bool,
+    pub transparent: bool,
+    pub deny_unknown_fields: bool,
+    pub flatten: bool,
+}
+
+/// A `rename_all` case convention.
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum RenameRule {
+    Lower,
+    Upper,
+    Pascal,
+    Camel,
+    Snake,
+    ScreamingSnake,
+    Kebab,
+    ScreamingKebab,
+}
+
+impl RenameRule {
+    fn from_lit(lit: &LitStr) -> Result<Self> {
+        Ok(match lit.value().as_str() {
+            "lowercase" => RenameRule::Lower,
+            "UPPERCASE" => RenameRule::Upper,
+            "PascalCase" => RenameRule::Pascal,
+            "camelCase" => RenameRule::Camel,
+            "snake_case" => RenameRule::Snake,
+            "SCREAMING_SNAKE_CASE" => RenameRule::ScreamingSnake,
+            "kebab-case" => RenameRule::Kebab,
+            "SCREAMING-KEBAB-CASE" => RenameRule::ScreamingKebab,
+            _ => return Err(Error::new_spanned(lit, "unknown rename rule")),
+        })
+    }
+
+    /// Applies the rule to a `snake_case` field name, as serde does.
+    pub fn apply_to_field(self, field: &str) -> String {
+        match self {
+            RenameRule::Lower | RenameRule::Snake => field.to_string(),
+            RenameRule::Upper | RenameRule::ScreamingSnake => field.to_ascii_uppercase(),
+            RenameRule::Pascal => {
+                let mut pascal = String::new();
+                let mut capitalize = true;
+                for ch in field.chars() {
+                    if ch == '_' {
+                        capitalize = true;
+                    } else if capitalize {
+                        pascal.push(ch.to_ascii_uppercase());
+                        capitalize = false;
+                    } else {
+                        pascal.push(ch);
+                    }
+                }
+                pascal
+            }
+            RenameRule::Camel => {
+                let pascal = RenameRule::Pascal.apply_to_field(field);
+                pascal[..1].to_ascii_lowercase() + &pascal[1..]
+            }
+            RenameRule::Kebab => field.replace('_', "-"),
+            RenameRule::ScreamingKebab => field.to_ascii_uppercase().replace('_', "-"),
+        }
+    }
+
+    /// Applies the rule to a `PascalCase` variant name, as serde does.
+    pub fn apply_to_variant(self, variant: &str) -> String {
+        match self {
+            RenameRule::Pascal => variant.to_string(),
+            RenameRule::Lower => variant.to_ascii_lowercase(),
+            RenameRule::Upper => variant.to_ascii_uppercase(),
+            RenameRule::Camel => variant[..1].to_ascii_lowercase() + &variant[1..],
+            RenameRule::Snake => {
+                let mut snake = String::new();
+                for (i, ch) in variant.char_indices() {
+                    if i > 0 && ch.is_uppercase() {
+                        snake.push('_');
+                    }
+                    snake.push(ch.to_ascii_lowercase());
+                }
+                snake
+            }
+            RenameRule::ScreamingSnake => RenameRule::Snake
+                .apply_to_variant(variant)
+                .to_ascii_uppercase(),
+            RenameRule::Kebab => RenameRule::Snake
+                .apply_to_variant(variant)
+                .replace('_', "-"),
+            RenameRule::ScreamingKebab => RenameRule::ScreamingSnake
+                .apply_to_variant(variant)
+                .replace('_', "-"),
+        }
+    }
+}
+
+impl Attrs<'_> {
+    /// Uses the doc comment as the comment of a member without an explicit
+    /// `comment` annotation.
+    pub fn use_doc_comment(&mut self) {
+        if let (Comment::None, Some(doc)) = (&self.comment, &self.doc) {
+            self.comment = Comment::Static(doc.clone());
+        }
+    }
+
+    /// Returns the minimum number of digits of an integer in `format`,
+    /// converting a `bits` annotation to digits of the integer's base.
+    pub fn digits(&self, format: &Format) -> Option<usize> {
+        self.width.or_else(|| {
+            let bits = self.bits?;
+            Some(match format {
+                Format::Binary => bits,
+                Format::Octal => bits.div_ceil(3),
+                Format::Hex => bits.div_ceil(4),
+                _ => 0,
+            })
+        })
+    }
+
+    /// Rejects the annotations which are only valid on a struct or enum.
+    pub fn check_member(&self) -> Result<()> {
+        match self.annotate {
+            Some(attr) if self.doc_comments => Err(Error::new_spanned(
+                attr,
+                "doc_comments is only permitted on a struct or enum",
+            )),
+            _ => Ok(()),
+        }
+    }
+
+    /// Rejects the annotations which are only valid on a field or variant.
+    pub fn check_container(&self) -> Result<()> {
+        match self.annotate {
+            Some(attr) if self.regions != Regions::None => Err(Error::new_spanned(
+                attr,
+                "regions are only permitted on a field or variant",
+            )),
+            _ => Ok(()),
+        }
+    }
 }
 
 pub fn get(input: &[Attribute]) -> Result<Attrs<'_>> {
     let mut attrs = Attrs {
         annotate: None,
         format: Format::None,
+        compact: false,
+        width: None,
+        bits: None,
+        group: None,
         comment: Comment::None,
+        comment_style: CommentStyle::None,
+        regions: Regions::None,
+        doc_comments: false,
+        doc: None,
+        serde: Serde::default(),
     };
 
+    let mut doc = Vec::new();
     for attr in input {
-        if attr.path().is_ident("annotate") {
+        if attr.path().is_ident("doc") {
+            if let Meta::NameValue(nv) = &attr.meta {
+                if let Expr::Lit(ExprLit {
+                    lit: Lit::Str(s), ..
+                }) = &nv.value
+                {
+                    doc.push(s.value());
+                }
+            }
+        } else if attr.path().is_ident("annotate") {
             attrs.annotate = Some(attr);
             parse_annotate_attribute(&mut attrs, attr)?;
+        } else if attr.path().is_ident("serde") && matches!(attr.meta, Meta::List(_)) {
+            parse_serde_attribute(&mut attrs.serde, attr)?;
         }
     }
+    attrs.doc = doc_text(&doc);
+    check_integer(&attrs)?;
     Ok(attrs)
 }
 
+// Rejects integer layout annotations which don't apply to the format.
+fn check_integer(attrs: &Attrs) -> Result<()> {
+    let Some(attr) = attrs.annotate else {
+        return Ok(());
+    };
+    if attrs.width.is_none() && attrs.bits.is_none() && attrs.group.is_none() {
+        return Ok(());
+    }
+    if attrs.width.is_some() && attrs.bits.is_some() {
+        return Err(Error::new_spanned(
+            attr,
+            "width and bits are mutually exclusive",
+        ));
+    }
+    match attrs.format {
+        Format::None | Format::Binary | Format::Octal | Format::Hex => Ok(()),
+        Format::Decimal if attrs.bits.is_none() => Ok(()),
+        _ => Err(Error::new_spanned(
+            attr,
+            "width, bits and group require an integer format",
+        )),
+    }
+}
+
+// Joins the lines of a doc comment, removing the space which follows `///`
+// and any blank lines around the text.
+fn doc_text(lines: &[String]) -> Option<String> {
+    let lines = lines
+        .iter()
+        .flat_map(|l| l.split('\n'))
+        .map(|l| l.strip_prefix(' ').unwrap_or(l).trim_end())
+        .collect::<Vec<_>>();
+    let start = lines.iter().position(|l| !l.is_empty())?;
+    // Unwrap is ok: there is at least one non-empty line.
+    let end = lines.iter().rposition(|l| !l.is_empty()).unwrap();
+    Some(lines[start..=end].join("\n"))
+}
+
+// Parses the serialization name of an attribute of the form `name = "..."`
+// or `name(serialize = "...", deserialize = "...")`.
+fn serialize_name(meta: &ParseNestedMeta) -> Result<Option<LitStr>> {
+    if meta.input.peek(Token![=]) {
+        return Ok(Some(meta.value()?.parse()?));
+    }
+    let mut name = None;
+    meta.parse_nested_meta(|meta| {
+        let value: LitStr = meta.value()?.parse()?;
+        if meta.path.is_ident("serialize") {
+            name = Some(value);
+        }
+        Ok(())
+    })?;
+    Ok(name)
+}
+
+fn parse_serde_attribute(serde: &mut Serde, attr: &Attribute) -> Result<()> {
+    attr.parse_nested_meta(|meta| {
+        let path = &meta.path;
+        if path.is_ident("rename") {
+            if let Some(name) = serialize_name(&meta)? {
+                serde.rename = Some(name.value());
+            }
+            return Ok(());
+        } else if path.is_ident("rename_all") {
+            if let Some(rule) = serialize_name(&meta)? {
+                serde.rename_all = Some(RenameRule::from_lit(&rule)?);
+            }
+            return Ok(());
+        } else if path.is_ident("rename_all_fields") {
+            if let Some(rule) = serialize_name(&meta)? {
+                serde.rename_all_fields = Some(RenameRule::from_lit(&rule)?);
+            }
+            return Ok(());
+        } else if path.is_ident("tag") {
+            serde.tag = Some(meta.value()?.parse::<LitStr>()?.value());
+            return Ok(());
+        } else if path.is_ident("content") {
+            serde.content = Some(meta.value()?.parse::<LitStr>()?.value());
+            return Ok(());
+        } else if path.is_ident("untagged") {
+            serde.untagged = true;
+        } else if path.is_ident("transparent") {
+            serde.transparent = true;
+        } else if path.is_ident("deny_unknown_fields") {
+            serde.deny_unknown_fields = true;
+        } else if path.is_ident("flatten") {
+            serde.flatten = true;
+        } else if path.is_ident("skip") || path.is_ident("skip_serializing") {
+            serde.skip = true;
+        } else if path.is_ident("default") || path.is_ident("skip_serializing_if") {
+            serde.optional = true;
+        }
+        // Consume the arguments of attributes we don't otherwise care about.
+        if meta.input.peek(Token![=]) {
+            let _value: Expr = meta.value()?.parse()?;
+        } else if meta.input.peek(token::Paren) {
+            let _args = meta.input.parse::<Group>()?;
+        }
+        Ok(())
+    })
+}
+
 fn function_call(input: ParseStream) -> Result<bool> {
     let content;
     let _result = parenthesized!(content in input);
     Ok(content.is_empty())
 }
 
+// Parses the optional `(name = <integer>, ...)` arguments of a format,
+// calling `arg` with each name and value.
+fn format_args(
+    input: ParseStream,
+    mut arg: impl FnMut(&Ident, &LitInt) -> Result<()>,
+) -> Result<()> {
+    if !input.peek(token::Paren) {
+        return Ok(());
+    }
+    let content;
+    let _paren = parenthesized!(content in input);
+    while !content.is_empty() {
+        let name: Ident = content.parse()?;
+        let _eq: Token![=] = content.parse()?;
+        arg(&name, &content.parse()?)?;
+        if !content.is_empty() {
+            let _comma: Token![,] = content.parse()?;
+        }
+    }
+    Ok(())
+}
+
+// Parses the optional `(base = <address>)` argument of an image format.
+fn image_base(input: ParseStream) -> Result<u32> {
+    let mut base = 0;
+    format_args(input, |name, value| {
+        if name != "base" {
+            return Err(Error::new_spanned(name, "unknown image argument"));
+        }
+        base = value.base10_parse()?;
+        Ok(())
+    })?;
+    Ok(base)
+}
+
+// Parses the optional `(group = <n>, width = <n>, offset = <address>)`
+// arguments of a hexdump format, returning `None` without arguments.
+fn dump(input: ParseStream, group: usize) -> Result<Option<Dump>> {
+    if !input.peek(token::Paren) {
+        return Ok(None);
+    }
+    let mut dump = Dump {
+        group,
+        width: 16,
+        offset: 0,
+    };
+    format_args(input, |name, value| {
+        if name == "offset" {
+            dump.offset = value.base10_parse()?;
+            return Ok(());
+        }
+        let n = value.base10_parse()?;
+        if n == 0 {
+            return Err(Error::new_spanned(value, "must not be zero"));
+        }
+        if name == "group" {
+            dump.group = n;
+        } else if name == "width" {
+            dump.width = n;
+        } else {
+            return Err(Error::new_spanned(name, "unknown hexdump argument"));
+        }
+        Ok(())
+    })?;
+    Ok(Some(dump))
+}
+
+// Parses the name and arguments of a value format (e.g. `hex` or
+// `xxd(width = 8)`), returning `None` for `compact`.
+fn value_format(input: ParseStream) -> Result<Option<Format>> {
+    let ident: Ident = input.parse()?;
+    Ok(Some(match ident.to_string().as_str() {
+        "block" => Format::Block,
+        "bin" => Format::Binary,
+        "dec" => Format::Decimal,
+        "oct" => Format::Octal,
+        "hex" => Format::Hex,
+        "hexstr" => Format::HexStr,
+        "hexdump" => Format::Hexdump(dump(input, 8)?),
+        "xxd" => Format::Xxd(dump(input, 2)?),
+        "base64" => Format::Base64,
+        "base64url" => Format::Base64Url,
+        "base32" => Format::Base32,
+        "c_array" => Format::CArray,
+        "xxd_i" => Format::XxdInclude,
+        "ihex" => Format::IntelHex(image_base(input)?),
+        "srec" => Format::Srec(image_base(input)?),
+        "quoted" => Format::Quoted,
+        "unquoted" => Format::Unquoted,
+        "compact" => return Ok(None),
+        _ => return Err(Error::new_spanned(ident, "unknown annotation type")),
+    }))
+}
+
+// Parses the `(offset = <n>, length = <n>, label = "...", format = <format>)`
+// arguments of a region, where the format is optional.
+fn byte_region(input: ParseStream) -> Result<Region> {
+    let content;
+    let paren = parenthesized!(content in input);
+    let (mut offset, mut length, mut label) = (None, None, None);
+    let mut format = Format::None;
+    while !content.is_empty() {
+        let name: Ident = content.parse()?;
+        let _eq: Token![=] = content.parse()?;
+        if name == "offset" {
+            offset = Some(content.parse::<LitInt>()?.base10_parse()?);
+        } else if name == "length" {
+            length = Some(content.parse::<LitInt>()?.base10_parse()?);
+        } else if name == "label" {
+            label = Some(content.parse::<LitStr>()?.value());
+        } else if name == "format" {
+            format = match value_format(&content)? {
+                Some(
+                    f @ (Format::Binary
+                    | Format::Decimal
+                    | Format::Octal
+                    | Format::Hex
+                    | Format::HexStr
+                    | Format::Base64
+                    | Format::Base64Url
+                    | Format::Base32
+                    | Format::CArray),
+                ) => f,
+                _ => return Err(Error::new_spanned(name, "unsupported region format")),
+            };
+        } else {
+            return Err(Error::new_spanned(name, "unknown region argument"));
+        }
+        if !content.is_empty() {
+            let _comma: Token![,] = content.parse()?;
+        }
+    }
+    match (offset, length, label) {
+        (Some(offset), Some(length), Some(label)) => Ok(Region {
+            offset,
+            length,
+            label,
+            format,
+        }),
+        _ => Err(Error::new(
+            paren.span.join(),
+            "region requires an offset, length and label",
+        )),
+    }
+}
+
 fn parse_annotate_attribute<'a>(attrs: &mut Attrs<'a>, attr: &'a Attribute) -> Result<()> {
     syn::custom_keyword!(format);
     syn::custom_keyword!(comment);
+    syn::custom_keyword!(comment_style);
+    syn::custom_keyword!(doc_comments);
+    syn::custom_keyword!(width);
+    syn::custom_keyword!(bits);
+    syn::custom_keyword!(group);
+    syn::custom_keyword!(region);
+    syn::custom_keyword!(regions);
 
     attr.parse_args_with(|input: ParseStream| {
         let mut more = true;
@@ -62,24 +545,23 @@ fn parse_annotate_attribute<'a>(attrs: &mut Attrs<'a>, attr: &'a Attribute) -> R
             if input.peek(format) {
                 let _kw = input.parse::<format>()?;
                 let _eq: Token![=] = input.parse()?;
+                match value_format(input)? {
+                    Some(format) => attrs.format = format,
+                    // Compact is a layout rather than a value format, so a
+                    // container may be both compact and e.g. hex.
+                    None => attrs.compact = true,
+                }
+            } else if input.peek(comment_style) {
+                let _kw = input.parse::<comment_style>()?;
+                let _eq: Token![=] = input.parse()?;
                 let ident: Ident = input.parse()?;
-                let istr = ident.to_string();
-                let format = match istr.as_str() {
-                    "block" => Format::Block,
-                    "bin" => Format::Binary,
-                    "dec" => Format::Decimal,
-                    "oct" => Format::Octal,
-                    "hex" => Format::Hex,
-                    "hexstr" => Format::HexStr,
-                    "hexdump" => Format::Hexdump,
-                    "xxd" => Format::Xxd,
-                    "compact" => Format::Compact,
-                    _ => Format::None,
+                attrs.comment_style = match ident.to_string().as_str() {
+                    "standard" => CommentStyle::Standard,
+                    "block" => CommentStyle::Block,
+                    "hash" => CommentStyle::Hash,
+                    "slashslash" => CommentStyle::SlashSlash,
+                    _ => return Err(Error::new_spanned(ident, "unknown comment style")),
                 };
-                if format == Format::None {
-                    return Err(Error::new_spanned(attr, "unknown annotation type"));
-                }
-                attrs.format = format;
             } else if input.peek(comment) {
                 let _kw = input.parse::<comment>()?;
                 let _eq: Token![=] = input.parse()?;
@@ -97,6 +579,42 @@ fn parse_annotate_attribute<'a>(attrs: &mut Attrs<'a>, attr: &'a Attribute) -> R
                     let comment: LitStr = input.parse()?;
                     attrs.comment = Comment::Static(comment.value());
                 }
+            } else if input.peek(width) {
+                let _kw = input.parse::<width>()?;
+                let _eq: Token![=] = input.parse()?;
+                attrs.width = Some(input.parse::<LitInt>()?.base10_parse()?);
+            } else if input.peek(bits) {
+                let _kw = input.parse::<bits>()?;
+                let _eq: Token![=] = input.parse()?;
+                attrs.bits = Some(input.parse::<LitInt>()?.base10_parse()?);
+            } else if input.peek(group) {
+                let _kw = input.parse::<group>()?;
+                let _eq: Token![=] = input.parse()?;
+                attrs.group = Some(input.parse::<LitInt>()?.base10_parse()?);
+            } else if input.peek(regions) {
+                let _kw = input.parse::<regions>()?;
+                let _eq: Token![=] = input.parse()?;
+                let ident: Ident = input.parse()?;
+                if !function_call(input)? {
+                    return Err(Error::new_spanned(attr, "Function args not permitted"));
+                }
+                attrs.regions = match attrs.regions {
+                    Regions::None => Regions::Function(ident),
+                    _ => return Err(Error::new_spanned(attr, "conflicting regions")),
+                };
+            } else if input.peek(region) {
+                let _kw = input.parse::<region>()?;
+                let region = byte_region(input)?;
+                match &mut attrs.regions {
+                    Regions::None => attrs.regions = Regions::Static(vec![region]),
+                    Regions::Static(regions) => regions.push(region),
+                    Regions::Function(_) => {
+                        return Err(Error::new_spanned(attr, "conflicting regions"))
+                    }
+                }
+            } else if input.peek(doc_comments) {
+                let _kw = input.parse::<doc_comments>()?;
+                attrs.doc_comments = true;
             } else {
                 return Err(Error::new_spanned(attr, "parse error"));
             }
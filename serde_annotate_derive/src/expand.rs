@@ -1,5 +1,5 @@
-use crate::ast::{Enum, Field, Input, Struct, Variant};
-use crate::attr::{Attrs, Comment, Format};
+use crate::ast::{Enum, Field, Input, Struct};
+use crate::attr::{Attrs, Comment, CommentStyle, Dump, Format, Regions, RenameRule};
 use proc_macro2::TokenStream;
 use quote::quote;
 use syn::{DeriveInput, Index, Member, Result};
@@ -7,6 +7,12 @@ use syn::{DeriveInput, Index, Member, Result};
 pub fn derive(mut node: DeriveInput) -> Result<TokenStream> {
     let input = Input::from_syn(&node)?;
 
+    // Container-level `#[serde]` attributes are forwarded to the helper type,
+    // so the helper is only given the type's name if it isn't renamed.
+    let renamed = match &input {
+        Input::Struct(input) => input.attrs.serde.rename.is_some(),
+        Input::Enum(input) => input.attrs.serde.rename.is_some(),
+    };
     let annotate_imp = match input {
         Input::Struct(input) => impl_struct(input),
         Input::Enum(input) => impl_enum(input),
@@ -22,6 +28,7 @@ pub fn derive(mut node: DeriveInput) -> Result<TokenStream> {
     let helper = syn::Ident::new(&format!("{}Helper", name), name.span());
 
     // Filter out all `#[annotate]` attributes from the derive inputs.
+    node.attrs.retain(|x| !x.path().is_ident("annotate"));
     match &mut node.data {
         syn::Data::Struct(data_struct) => {
             for f in data_struct.fields.iter_mut() {
@@ -40,19 +47,19 @@ pub fn derive(mut node: DeriveInput) -> Result<TokenStream> {
     }
     node.ident = helper.clone();
 
-    for attr in node.attrs.iter() {
-        if attr.path().is_ident("serde") {
-            return Err(syn::Error::new_spanned(attr, "use of `#[serde]` on type together with `#[derive(Annotate)]` is not yet supported"));
-        }
-    }
-
     let name_str = syn::LitStr::new(&name.to_string(), name.span());
+    let rename = if renamed {
+        quote! {}
+    } else {
+        quote! { #[serde(rename = #name_str)] }
+    };
     Ok(quote! {
         const _: () = {
             #annotate_imp
 
             #[derive(::serde::Serialize)]
-            #[serde(remote = #name_str, rename = #name_str)]
+            #[serde(remote = #name_str)]
+            #rename
             #node
 
             impl ::serde::Serialize for #name {
@@ -69,37 +76,108 @@ pub fn derive(mut node: DeriveInput) -> Result<TokenStream> {
     })
 }
 
-fn impl_format(a: &Attrs) -> TokenStream {
-    match &a.format {
+pub fn impl_format(a: &Attrs) -> TokenStream {
+    if a.compact {
+        return quote! { Some(::serde_annotate::annotate::Format::Compact) };
+    }
+    impl_value_format(&a.format)
+}
+
+fn impl_value_format(format: &Format) -> TokenStream {
+    match format {
         Format::None => quote! { None },
         Format::Block => quote! { Some(::serde_annotate::annotate::Format::Block) },
         Format::Binary => quote! { Some(::serde_annotate::annotate::Format::Binary) },
         Format::Decimal => quote! { Some(::serde_annotate::annotate::Format::Decimal) },
         Format::Hex => quote! { Some(::serde_annotate::annotate::Format::Hex) },
         Format::Octal => quote! { Some(::serde_annotate::annotate::Format::Octal) },
-        Format::Compact => quote! { Some(::serde_annotate::annotate::Format::Compact) },
         Format::HexStr => quote! { Some(::serde_annotate::annotate::Format::HexStr) },
-        Format::Hexdump => quote! { Some(::serde_annotate::annotate::Format::Hexdump) },
-        Format::Xxd => quote! { Some(::serde_annotate::annotate::Format::Xxd) },
+        Format::Hexdump(None) => quote! { Some(::serde_annotate::annotate::Format::Hexdump) },
+        Format::Xxd(None) => quote! { Some(::serde_annotate::annotate::Format::Xxd) },
+        Format::Hexdump(Some(Dump {
+            group,
+            width,
+            offset,
+        })) => quote! {
+            Some(::serde_annotate::annotate::Format::HexdumpLayout(
+                ::serde_annotate::DumpLayout {
+                    group: #group,
+                    width: #width,
+                    offset: #offset,
+                }
+            ))
+        },
+        Format::Xxd(Some(Dump {
+            group,
+            width,
+            offset,
+        })) => quote! {
+            Some(::serde_annotate::annotate::Format::XxdLayout(
+                ::serde_annotate::DumpLayout {
+                    group: #group,
+                    width: #width,
+                    offset: #offset,
+                }
+            ))
+        },
+        Format::Base64 => quote! { Some(::serde_annotate::annotate::Format::Base64) },
+        Format::Base64Url => quote! { Some(::serde_annotate::annotate::Format::Base64Url) },
+        Format::Base32 => quote! { Some(::serde_annotate::annotate::Format::Base32) },
+        Format::CArray => quote! { Some(::serde_annotate::annotate::Format::CArray) },
+        Format::XxdInclude => quote! { Some(::serde_annotate::annotate::Format::XxdInclude) },
+        Format::IntelHex(base) => {
+            quote! { Some(::serde_annotate::annotate::Format::IntelHex { base: #base }) }
+        }
+        Format::Srec(base) => {
+            quote! { Some(::serde_annotate::annotate::Format::Srec { base: #base }) }
+        }
+        Format::Quoted => quote! { Some(::serde_annotate::annotate::Format::Quoted) },
+        Format::Unquoted => quote! { Some(::serde_annotate::annotate::Format::Unquoted) },
     }
 }
 
-fn impl_field_format(fields: &[Field]) -> Vec<TokenStream> {
-    fields
-        .iter()
-        .map(|f| {
-            let format = impl_format(&f.attrs);
-            match &f.member {
-                Member::Named(id) => {
-                    let id = id.to_string();
-                    quote! { ::serde_annotate::annotate::MemberId::Name(#id) => #format }
-                }
-                Member::Unnamed(Index { index: i, .. }) => {
-                    quote! { ::serde_annotate::annotate::MemberId::Index(#i) => #format }
-                }
-            }
+// Members without a format of their own take the value format of their
+// container.  Likewise, the integer width and grouping of a member default
+// to those of its container.
+pub fn impl_member_format(a: &Attrs, container: &Attrs) -> TokenStream {
+    if a.compact {
+        return impl_format(a);
+    }
+    let format = if a.format != Format::None {
+        &a.format
+    } else {
+        &container.format
+    };
+    let width = a.digits(format).or_else(|| container.digits(format));
+    let group = a.group.or(container.group);
+    if width.is_none() && group.is_none() {
+        return impl_value_format(format);
+    }
+    let base = match format {
+        Format::None | Format::Decimal => quote! { Dec },
+        Format::Binary => quote! { Bin },
+        Format::Octal => quote! { Oct },
+        Format::Hex => quote! { Hex },
+        _ => return impl_value_format(format),
+    };
+    let width = width.unwrap_or(0);
+    let group = group.unwrap_or(0);
+    quote! {
+        Some(::serde_annotate::annotate::Format::Int {
+            base: ::serde_annotate::Base::#base,
+            width: #width,
+            group: #group,
         })
-        .collect::<Vec<_>>()
+    }
+}
+
+// The container itself is only annotated with its layout.
+pub fn impl_container_format(a: &Attrs) -> TokenStream {
+    if a.compact {
+        quote! { Some(::serde_annotate::annotate::Format::Compact) }
+    } else {
+        quote! { None }
+    }
 }
 
 fn impl_comment(a: &Attrs) -> TokenStream {
@@ -117,100 +195,203 @@ fn impl_comment(a: &Attrs) -> TokenStream {
     }
 }
 
-fn impl_field_comment(fields: &[Field]) -> Vec<TokenStream> {
+// Comments without a style of their own take the style of their container.
+fn impl_member_style(a: &Attrs, container: &Attrs) -> TokenStream {
+    match a.comment_style {
+        CommentStyle::None => impl_comment_style(container),
+        _ => impl_comment_style(a),
+    }
+}
+
+fn impl_comment_style(a: &Attrs) -> TokenStream {
+    match a.comment_style {
+        CommentStyle::None => quote! { None },
+        CommentStyle::Standard => quote! { Some(::serde_annotate::CommentFormat::Standard) },
+        CommentStyle::Block => quote! { Some(::serde_annotate::CommentFormat::Block) },
+        CommentStyle::Hash => quote! { Some(::serde_annotate::CommentFormat::Hash) },
+        CommentStyle::SlashSlash => quote! { Some(::serde_annotate::CommentFormat::SlashSlash) },
+    }
+}
+
+fn impl_regions(a: &Attrs) -> TokenStream {
+    match &a.regions {
+        Regions::None => quote! { None },
+        Regions::Static(regions) => {
+            let regions = regions.iter().map(|r| {
+                let (offset, length, label) = (r.offset, r.length, &r.label);
+                let format = impl_value_format(&r.format);
+                quote! {
+                    ::serde_annotate::annotate::Region {
+                        offset: #offset,
+                        length: #length,
+                        label: #label.to_string(),
+                        format: #format,
+                    }
+                }
+            });
+            quote! { Some(vec![#(#regions),*]) }
+        }
+        Regions::Function(id) => quote! {
+            Some(self.#id())
+        },
+    }
+}
+
+// Annotations are looked up by the name of the field in the serialized
+// document.
+fn impl_fields(
+    fields: &[Field],
+    rule: Option<RenameRule>,
+    annotation: impl Fn(&Attrs) -> TokenStream,
+) -> Vec<TokenStream> {
     fields
         .iter()
         .map(|f| {
-            let comment = impl_comment(&f.attrs);
+            let value = annotation(&f.attrs);
             match &f.member {
-                Member::Named(id) => {
-                    let id = id.to_string();
-                    quote! { ::serde_annotate::annotate::MemberId::Name(#id) => #comment }
+                Member::Named(_) => {
+                    let id = f.serialized_name(rule);
+                    quote! { ::serde_annotate::annotate::MemberId::Name(#id) => #value }
                 }
                 Member::Unnamed(Index { index: i, .. }) => {
-                    quote! { ::serde_annotate::annotate::MemberId::Index(#i) => #comment }
+                    quote! { ::serde_annotate::annotate::MemberId::Index(#i) => #value }
                 }
             }
         })
         .collect::<Vec<_>>()
 }
 
-fn impl_variants(variants: &[Variant]) -> (Vec<TokenStream>, Vec<TokenStream>) {
-    let formats = variants
-        .iter()
-        .map(|v| {
-            let variant = v.ident.to_string();
-            let formats = impl_field_format(&v.fields);
-            let vformat = impl_format(&v.attrs);
-            quote! {
-                #variant => match field {
-                    ::serde_annotate::annotate::MemberId::Variant => #vformat,
-                    #(#formats,)*
-                    _ => None,
-                }
-            }
-        })
-        .collect::<Vec<_>>();
-    let comments = variants
+fn impl_variants(input: &Enum, annotation: impl Fn(&Attrs) -> TokenStream) -> Vec<TokenStream> {
+    let rule = input.attrs.serde.rename_all;
+    input
+        .variants
         .iter()
         .map(|v| {
-            let variant = v.ident.to_string();
-            let comments = impl_field_comment(&v.fields);
-            let vcomment = impl_comment(&v.attrs);
+            let variant = v.serialized_name(rule);
+            let fields = impl_fields(&v.fields, input.field_rule(v), &annotation);
+            let value = annotation(&v.attrs);
             quote! {
                 #variant => match field {
-                    ::serde_annotate::annotate::MemberId::Variant => #vcomment,
-                    #(#comments,)*
+                    ::serde_annotate::annotate::MemberId::Variant => #value,
+                    #(#fields,)*
                     _ => None,
                 }
             }
         })
-        .collect::<Vec<_>>();
-
-    (formats, comments)
+        .collect::<Vec<_>>()
 }
 
 fn impl_struct(input: Struct) -> TokenStream {
-    let formats = impl_field_format(&input.fields);
-    let comments = impl_field_comment(&input.fields);
+    let rule = input.attrs.serde.rename_all;
+    let container = &input.attrs;
+    let formats = impl_fields(&input.fields, rule, |a| impl_member_format(a, container));
+    let comments = impl_fields(&input.fields, rule, impl_comment);
+    let styles = impl_fields(&input.fields, rule, |a| impl_member_style(a, container));
+    let regions = impl_fields(&input.fields, rule, impl_regions);
+    let cformat = impl_container_format(container);
+    let ccomment = impl_comment(container);
+    let cstyle = impl_comment_style(container);
     let name = &input.ident;
     quote! {
         impl ::serde_annotate::annotate::Annotate for #name {
             fn format(&self, _variant: Option<&str>, field: &::serde_annotate::annotate::MemberId) -> Option<::serde_annotate::annotate::Format> {
                 match field {
+                    ::serde_annotate::annotate::MemberId::Container => #cformat,
                     #(#formats,)*
                     _ => None,
                 }
             }
             fn comment(&self, _variant: Option<&str>, field: &::serde_annotate::annotate::MemberId) -> Option<String> {
                 match field {
+                    ::serde_annotate::annotate::MemberId::Container => #ccomment,
                     #(#comments,)*
                     _ => None,
                 }
             }
+            fn comment_style(&self, _variant: Option<&str>, field: &::serde_annotate::annotate::MemberId) -> Option<::serde_annotate::CommentFormat> {
+                match field {
+                    ::serde_annotate::annotate::MemberId::Container => #cstyle,
+                    #(#styles,)*
+                    _ => None,
+                }
+            }
+            fn regions(&self, _variant: Option<&str>, field: &::serde_annotate::annotate::MemberId) -> Option<Vec<::serde_annotate::annotate::Region>> {
+                match field {
+                    #(#regions,)*
+                    _ => None,
+                }
+            }
         }
     }
 }
 
 fn impl_enum(input: Enum) -> TokenStream {
-    let (formats, comments) = impl_variants(&input.variants);
+    let container = &input.attrs;
+    let formats = impl_variants(&input, |a| impl_member_format(a, container));
+    let comments = impl_variants(&input, impl_comment);
+    let styles = impl_variants(&input, |a| impl_member_style(a, container));
+    let regions = impl_variants(&input, impl_regions);
+    let cformat = impl_container_format(container);
+    let ccomment = impl_comment(container);
+    let cstyle = impl_comment_style(container);
     let name = &input.ident;
+    // Tagged and untagged variants are serialized as structs, without
+    // naming the variant, so the variant is found by examining `self`.
+    let variants = input.variants.iter().map(|v| {
+        let ident = &v.ident;
+        let variant = v.serialized_name(input.attrs.serde.rename_all);
+        quote! { #name::#ident { .. } => #variant }
+    });
     quote! {
+        impl #name {
+            fn __annotate_variant(&self) -> &'static str {
+                match *self {
+                    #(#variants,)*
+                }
+            }
+        }
+
         impl ::serde_annotate::annotate::Annotate for #name {
             fn format(&self, variant: Option<&str>, field: &::serde_annotate::annotate::MemberId) -> Option<::serde_annotate::annotate::Format> {
-                let variant = variant?;
+                if let ::serde_annotate::annotate::MemberId::Container = field {
+                    return #cformat;
+                }
+                let variant = variant.unwrap_or_else(|| self.__annotate_variant());
                 match variant {
                     #(#formats,)*
                     _ => None,
                 }
             }
             fn comment(&self, variant: Option<&str>, field: &::serde_annotate::annotate::MemberId) -> Option<String> {
-                let variant = variant?;
+                if let ::serde_annotate::annotate::MemberId::Container = field {
+                    return #ccomment;
+                }
+                let variant = variant.unwrap_or_else(|| self.__annotate_variant());
                 match variant {
                     #(#comments,)*
                     _ => None,
                 }
             }
+            fn comment_style(&self, variant: Option<&str>, field: &::serde_annotate::annotate::MemberId) -> Option<::serde_annotate::CommentFormat> {
+                if let ::serde_annotate::annotate::MemberId::Container = field {
+                    return #cstyle;
+                }
+                let variant = variant.unwrap_or_else(|| self.__annotate_variant());
+                match variant {
+                    #(#styles,)*
+                    _ => None,
+                }
+            }
+            fn regions(&self, variant: Option<&str>, field: &::serde_annotate::annotate::MemberId) -> Option<Vec<::serde_annotate::annotate::Region>> {
+                if let ::serde_annotate::annotate::MemberId::Container = field {
+                    return None;
+                }
+                let variant = variant.unwrap_or_else(|| self.__annotate_variant());
+                match variant {
+                    #(#regions,)*
+                    _ => None,
+                }
+            }
         }
     }
 }
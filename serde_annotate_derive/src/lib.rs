@@ -3,6 +3,7 @@ extern crate proc_macro;
 mod ast;
 mod attr;
 mod expand;
+mod schema;
 
 use proc_macro::TokenStream;
 use syn::{parse_macro_input, DeriveInput};
@@ -14,3 +15,11 @@ pub fn derive_annotate(input: TokenStream) -> TokenStream {
         .unwrap_or_else(|err| err.to_compile_error())
         .into()
 }
+
+#[proc_macro_derive(Schema, attributes(serde, annotate))]
+pub fn derive_schema(input: TokenStream) -> TokenStream {
+    let input = parse_macro_input!(input as DeriveInput);
+    schema::derive(input)
+        .unwrap_or_else(|err| err.to_compile_error())
+        .into()
+}
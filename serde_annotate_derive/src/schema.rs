@@ -0,0 +1,232 @@
+use crate::ast::{Enum, Field, Input, Struct, Style, Variant};
+use crate::attr::{Attrs, Comment, RenameRule};
+use crate::expand::{impl_container_format, impl_member_format};
+use proc_macro2::TokenStream;
+use quote::quote;
+use syn::{parse_quote, DeriveInput, Result};
+
+pub fn derive(node: DeriveInput) -> Result<TokenStream> {
+    let input = Input::from_syn(&node)?;
+    let (name, body) = match input {
+        Input::Struct(input) => (input.ident.clone(), impl_struct(&input)),
+        Input::Enum(input) => (input.ident.clone(), impl_enum(&input)),
+    };
+    let label = name.to_string();
+    // Every type parameter contributes to the schema through its fields.
+    let mut generics = node.generics.clone();
+    let params = generics
+        .type_params()
+        .map(|p| p.ident.clone())
+        .collect::<Vec<_>>();
+    let bounds = generics.make_where_clause();
+    for p in params {
+        bounds
+            .predicates
+            .push(parse_quote! { #p: ::serde_annotate::Schema });
+    }
+    let (impl_generics, ty_generics, where_clause) = generics.split_for_impl();
+    Ok(quote! {
+        impl #impl_generics ::serde_annotate::Schema for #name #ty_generics #where_clause {
+            fn schema() -> ::serde_annotate::Document {
+                ::serde_annotate::schema::named::<Self>(#label, || { #body })
+            }
+        }
+    })
+}
+
+fn description(a: &Attrs) -> TokenStream {
+    // Only static comments can be known without an instance of the type.
+    match &a.comment {
+        Comment::Static(s) => quote! { Some(#s) },
+        _ => quote! { None },
+    }
+}
+
+fn member(f: &Field, container: &Attrs) -> TokenStream {
+    let ty = f.ty;
+    let description = description(&f.attrs);
+    let format = impl_member_format(&f.attrs, container);
+    quote! {
+        ::serde_annotate::schema::member::<#ty>(#description, #format)
+    }
+}
+
+fn fields<'a>(fields: &'a [Field<'a>]) -> Vec<&'a Field<'a>> {
+    fields.iter().filter(|f| !f.attrs.serde.skip).collect()
+}
+
+fn impl_fields(
+    style: Style,
+    fields: &[&Field],
+    rule: Option<RenameRule>,
+    deny_unknown_fields: bool,
+    container: &Attrs,
+) -> TokenStream {
+    match style {
+        Style::Unit => quote! {
+            <() as ::serde_annotate::Schema>::schema()
+        },
+        Style::Tuple if fields.len() == 1 => member(fields[0], container),
+        Style::Tuple => {
+            let members = fields.iter().map(|f| member(f, container));
+            quote! {
+                ::serde_annotate::schema::tuple(vec![#(#members),*])
+            }
+        }
+        Style::Struct => {
+            let (flattened, fields): (Vec<&Field>, Vec<&Field>) =
+                fields.iter().partition(|f| f.attrs.serde.flatten);
+            let flattened = flattened.iter().map(|f| {
+                let ty = f.ty;
+                quote! {
+                    let schema = ::serde_annotate::schema::flatten(
+                        schema,
+                        <#ty as ::serde_annotate::Schema>::schema(),
+                    );
+                }
+            });
+            let properties = fields.iter().map(|f| {
+                // Unwrap is ok: struct fields are named.
+                let name = f.serialized_name(rule).unwrap();
+                let member = member(f, container);
+                let ty = f.ty;
+                let required = if f.attrs.serde.optional {
+                    quote! { false }
+                } else {
+                    quote! { <#ty as ::serde_annotate::Schema>::required() }
+                };
+                quote! {
+                    properties.push((#name, #member));
+                    if #required {
+                        required.push(#name);
+                    }
+                }
+            });
+            let deny = if deny_unknown_fields {
+                quote! { let schema = ::serde_annotate::schema::deny_unknown_fields(schema); }
+            } else {
+                quote! {}
+            };
+            quote! {
+                let mut properties = Vec::new();
+                let mut required = Vec::new();
+                #(#properties)*
+                let schema = ::serde_annotate::schema::structure(properties, required);
+                #(#flattened)*
+                #deny
+                schema
+            }
+        }
+    }
+}
+
+fn impl_struct(input: &Struct) -> TokenStream {
+    let serde = &input.attrs.serde;
+    let fields = fields(&input.fields);
+    if serde.transparent {
+        // A transparent struct serializes as its only field.
+        if let Some(f) = fields.first() {
+            return member(f, &input.attrs);
+        }
+    }
+    let value = impl_fields(
+        input.style,
+        &fields,
+        serde.rename_all,
+        serde.deny_unknown_fields,
+        &input.attrs,
+    );
+    let value = match &serde.tag {
+        Some(tag) => {
+            let name = serde
+                .rename
+                .clone()
+                .unwrap_or_else(|| input.ident.to_string());
+            quote! {
+                ::serde_annotate::schema::tagged_variant(#tag, #name, None, Some({ #value }))
+            }
+        }
+        None => value,
+    };
+    impl_container(&input.attrs, value)
+}
+
+fn impl_container(a: &Attrs, schema: TokenStream) -> TokenStream {
+    let description = description(a);
+    let format = impl_container_format(a);
+    quote! {
+        ::serde_annotate::schema::container({ #schema }, #description, #format)
+    }
+}
+
+fn impl_variant(input: &Enum, v: &Variant) -> TokenStream {
+    let serde = &input.attrs.serde;
+    let name = v.serialized_name(serde.rename_all);
+    let description = description(&v.attrs);
+    let value = match v.style {
+        Style::Unit => None,
+        _ => Some(impl_fields(
+            v.style,
+            &fields(&v.fields),
+            input.field_rule(v),
+            serde.deny_unknown_fields,
+            &input.attrs,
+        )),
+    };
+    match (&serde.tag, &serde.content, value) {
+        _ if serde.untagged => {
+            let value = impl_fields(
+                v.style,
+                &fields(&v.fields),
+                input.field_rule(v),
+                false,
+                &input.attrs,
+            );
+            quote! {
+                ::serde_annotate::schema::untagged_variant(#description, { #value })
+            }
+        }
+        (Some(tag), Some(content), value) => {
+            let value = match value {
+                Some(value) => quote! { Some({ #value }) },
+                None => quote! { None },
+            };
+            quote! {
+                ::serde_annotate::schema::adjacent_variant(#tag, #content, #name, #description, #value)
+            }
+        }
+        (Some(tag), None, value) => {
+            let value = match value {
+                Some(value) => quote! { Some({ #value }) },
+                None => quote! { None },
+            };
+            quote! {
+                ::serde_annotate::schema::tagged_variant(#tag, #name, #description, #value)
+            }
+        }
+        (None, _, None) => quote! {
+            ::serde_annotate::schema::unit_variant(#name, #description)
+        },
+        (None, _, Some(value)) => quote! {
+            ::serde_annotate::schema::variant(#name, #description, { #value })
+        },
+    }
+}
+
+fn impl_enum(input: &Enum) -> TokenStream {
+    let variants = input
+        .variants
+        .iter()
+        .filter(|v| !v.attrs.serde.skip)
+        .map(|v| impl_variant(input, v));
+    let schema = if input.attrs.serde.untagged {
+        quote! {
+            ::serde_annotate::schema::any_of(vec![#(#variants),*])
+        }
+    } else {
+        quote! {
+            ::serde_annotate::schema::one_of(vec![#(#variants),*])
+        }
+    };
+    impl_container(&input.attrs, schema)
+}